@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+use tokio::time;
+use tracing::warn;
+
+use crate::{
+    rewrite,
+    state::{now_secs, BuildState},
+    AppState,
+};
+
+/// How often access times are written to the state file
+const PERSIST_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// When each project was last requested, kept in memory and written to the state file every
+/// [`PERSIST_INTERVAL`]. Only the time is kept, nothing about who asked.
+#[derive(Debug, Default)]
+pub struct AccessTimes {
+    /// Unix timestamps by slug, 0 for never
+    last: HashMap<String, AtomicU64>,
+    /// Whether anything changed since the last [`AccessTimes::flush_into`]
+    dirty: AtomicBool,
+}
+
+impl AccessTimes {
+    /// Tracks `slugs`, starting from the times saved in `build_state`
+    pub fn new<'a>(slugs: impl IntoIterator<Item = &'a String>, build_state: &BuildState) -> Self {
+        let last = slugs
+            .into_iter()
+            .map(|slug| {
+                let saved = build_state
+                    .project(slug)
+                    .and_then(|p| p.last_accessed)
+                    .unwrap_or_default();
+                (slug.clone(), AtomicU64::new(saved))
+            })
+            .collect();
+        Self {
+            last,
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn touch(&self, slug: &str, now: u64) {
+        if let Some(last) = self.last.get(slug)
+            && last.fetch_max(now, Ordering::Relaxed) < now
+        {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, slug: &str) -> Option<u64> {
+        let last = self.last.get(slug)?.load(Ordering::Relaxed);
+        (last > 0).then_some(last)
+    }
+
+    /// Copies the access times into `build_state`, returning whether any changed since the
+    /// last call
+    pub fn flush_into(&self, build_state: &mut BuildState) -> bool {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        for slug in self.last.keys() {
+            if let Some(last) = self.get(slug) {
+                build_state.record_access(slug, last);
+            }
+        }
+        true
+    }
+}
+
+/// Notes the time of every request for a project's docs
+pub async fn record_access(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(state) = req.app_data::<web::Data<Arc<AppState>>>()
+        && let Some((slug, _)) = rewrite::project_path(state, req.path())
+    {
+        state.access.touch(slug, now_secs());
+    }
+    next.call(req).await
+}
+
+/// Writes the access times to the state file if any changed
+pub async fn persist(state: &AppState) {
+    let mut build_state = state.build_state.write().await;
+    if state.access.flush_into(&mut build_state)
+        && let Err(e) = build_state.save(&state.base_path).await
+    {
+        warn!("Failed to save access times: {}", e);
+    }
+}
+
+/// Persists access times every [`PERSIST_INTERVAL`]
+pub fn spawn_persist(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticks =
+            time::interval_at(time::Instant::now() + PERSIST_INTERVAL, PERSIST_INTERVAL);
+        loop {
+            ticks.tick().await;
+            persist(&state).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_times() {
+        let mut build_state = BuildState::default();
+        build_state.record_access("old", 100);
+        let slugs = ["old".to_string(), "new".to_string()];
+        let access = AccessTimes::new(&slugs, &build_state);
+
+        assert_eq!(access.get("old"), Some(100));
+        assert_eq!(access.get("new"), None);
+        assert!(!access.flush_into(&mut build_state));
+
+        access.touch("new", 200);
+        // a request finishing late doesn't move the time back
+        access.touch("new", 150);
+        access.touch("unknown", 200);
+        assert_eq!(access.get("new"), Some(200));
+        assert!(access.flush_into(&mut build_state));
+        assert_eq!(build_state.project("new").unwrap().last_accessed, Some(200));
+        assert!(!access.flush_into(&mut build_state));
+    }
+}
@@ -0,0 +1,577 @@
+use std::{path::PathBuf, sync::Arc};
+
+use actix_files::NamedFile;
+use actix_web::{
+    get,
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    post, web, HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    artifact::{self, ArchiveFormat},
+    config::{BuildSystem, IndexSort, Project},
+    docs_index::{self, Symbol},
+    error::{AppError, AppResult},
+    git::{commit_web_url, source_web_url, CommitInfo, ReleaseStatus},
+    history::{dir_size, estimate_rebuild, load_size_history, RebuildEstimate, SizeRecord},
+    manifest, metrics,
+    pipeline::ProjectOutcome,
+    report, snapshots,
+    state::{now_secs, parse_date, BuildState},
+    upload,
+    utils::{is_contained_path, normalize_relative},
+    AppState,
+};
+
+pub const LOCAL_SOURCE: &str = "local working copy";
+
+/// The project served at `slug`
+pub fn project<'a>(state: &'a AppState, slug: &str) -> AppResult<&'a Project> {
+    state
+        .projects
+        .get(slug)
+        .ok_or_else(|| AppError::NotFound(format!("project '{}'", slug)))
+}
+
+/// The project served at `slug`, refusing one whose docs are proxied rather than on disk
+pub fn local_project<'a>(state: &'a AppState, slug: &str) -> AppResult<&'a Project> {
+    let project = project(state, slug)?;
+    if project.proxy.is_some() {
+        return Err(AppError::BadRequest(format!(
+            "project '{}' serves proxied docs",
+            slug
+        )));
+    }
+    Ok(project)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectSummary<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    /// Branch of the project these docs are built from, for projects with `branches`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<&'a str>,
+    /// Slug of the project whose Gradle build also writes these docs, for its extra `targets`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant_of: Option<&'a str>,
+    /// Old slugs redirecting to this project
+    pub also_known_as: &'a [String],
+    pub build_system: &'a BuildSystem,
+    pub url: String,
+    pub last_built: Option<u64>,
+    /// Unix timestamp of the last request for the docs
+    pub last_accessed: Option<u64>,
+    /// Repository URL, or [`LOCAL_SOURCE`] for projects without one
+    pub source: &'a str,
+    pub commit: Option<CommitSummary<'a>>,
+    /// SHA-256 of the `Cargo.lock` the docs were built with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cargo_lock_sha256: Option<&'a str>,
+    /// How the built commit compares with the newest release tag, for repositories with one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release: Option<&'a ReleaseStatus>,
+    /// The project's sources at the built commit, with `source_url_template`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// Compiler errors of the last build, empty if it succeeded
+    pub last_errors: &'a [String],
+    /// Problems with the output of the last successful build
+    pub warnings: &'a [String],
+    /// Outcome of the update and build when the server started, with `update_on_start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup: Option<&'a ProjectOutcome>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitSummary<'a> {
+    #[serde(flatten)]
+    pub info: &'a CommitInfo,
+    /// Link to the commit on GitHub/GitLab, when the host is recognized
+    pub url: Option<String>,
+}
+
+impl<'a> ProjectSummary<'a> {
+    pub fn new(project: &'a Project, state: &'a AppState, build_state: &'a BuildState) -> Self {
+        // variants are as fresh as the build of their project
+        let built = project
+            .variant
+            .as_ref()
+            .map_or(&project.url_path, |v| &v.parent);
+        let build = build_state.project(built);
+        let commit = build
+            .and_then(|b| b.commit.as_ref())
+            .map(|info| CommitSummary {
+                info,
+                url: project
+                    .config
+                    .repo
+                    .as_deref()
+                    .and_then(|repo| commit_web_url(repo, &info.id)),
+            });
+        // before the first build the branch, or whatever the forge shows by default
+        let revision = build
+            .and_then(|b| b.commit.as_ref())
+            .map(|info| info.id.as_str())
+            .or(project.branch.as_ref().map(|b| b.name.as_str()))
+            .unwrap_or("HEAD");
+        let source_url = project
+            .config
+            .source_url_template
+            .as_deref()
+            .map(|template| source_web_url(template, revision));
+
+        Self {
+            slug: &project.url_path,
+            name: project.name(),
+            branch: project.branch.as_ref().map(|b| b.name.as_str()),
+            variant_of: project.variant.as_ref().map(|v| v.parent.as_str()),
+            also_known_as: &project.aliases,
+            build_system: &project.config.build_system,
+            url: format!("/{}/", project.url_path),
+            last_built: build.and_then(|b| b.last_built),
+            last_accessed: state.access.get(&project.url_path),
+            source: project.config.repo.as_deref().unwrap_or(LOCAL_SOURCE),
+            commit,
+            cargo_lock_sha256: build.and_then(|b| b.cargo_lock_sha256.as_deref()),
+            release: build.and_then(|b| b.release.as_ref()),
+            source_url,
+            last_errors: build.map(|b| b.last_errors.as_slice()).unwrap_or_default(),
+            warnings: build.map(|b| b.warnings.as_slice()).unwrap_or_default(),
+            startup: state.startup.get(built),
+        }
+    }
+}
+
+/// Summaries of all projects sorted by slug, as listed by both the API and the index
+pub fn project_summaries<'a>(
+    state: &'a AppState,
+    build_state: &'a BuildState,
+) -> Vec<ProjectSummary<'a>> {
+    let mut projects = state
+        .projects
+        .values()
+        .map(|p| ProjectSummary::new(p, state, build_state))
+        .collect::<Vec<_>>();
+    projects.sort_by(|a, b| a.slug.cmp(b.slug));
+    projects
+}
+
+/// Orders summaries (as returned by [`project_summaries`]) for the index page
+pub fn sort_summaries(summaries: &mut [ProjectSummary], sort: IndexSort) {
+    summaries.sort_by_cached_key(|s| s.name.to_lowercase());
+    match sort {
+        IndexSort::Name => {}
+        IndexSort::LastBuilt => summaries.sort_by_key(|s| std::cmp::Reverse(s.last_built)),
+        IndexSort::BuildSystem => summaries.sort_by_key(|s| s.build_system.name()),
+    }
+}
+
+/// What the running binary was built from, as embedded by `build.rs`
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_date: &'static str,
+    pub rustc_version: &'static str,
+}
+
+pub const VERSION_INFO: VersionInfo = VersionInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_commit: env!("GIT_COMMIT_HASH"),
+    build_date: env!("BUILD_DATE"),
+    rustc_version: env!("RUSTC_VERSION"),
+};
+
+#[get("/api/version")]
+pub async fn version() -> impl Responder {
+    HttpResponse::Ok().json(VERSION_INFO)
+}
+
+#[get("/api/projects")]
+pub async fn list_projects(state: web::Data<Arc<AppState>>) -> impl Responder {
+    let build_state = state.build_state.read().await;
+    HttpResponse::Ok().json(project_summaries(&state, &build_state))
+}
+
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/status")]
+pub async fn project_status(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+
+    let build_state = state.build_state.read().await;
+    Ok(HttpResponse::Ok().json(ProjectSummary::new(project, &state, &build_state)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SizeHistoryQuery {
+    /// Only return builds from this date on, as `YYYY-MM-DD` or a Unix timestamp
+    since: Option<String>,
+}
+
+fn parse_since(since: &str) -> AppResult<u64> {
+    since
+        .parse::<u64>()
+        .ok()
+        .or_else(|| parse_date(since).map(|secs| secs.max(0) as u64))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "invalid 'since' value '{}', expected YYYY-MM-DD or a Unix timestamp",
+                since
+            ))
+        })
+}
+
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/size-history")]
+pub async fn size_history(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+    query: web::Query<SizeHistoryQuery>,
+) -> AppResult<web::Json<Vec<SizeRecord>>> {
+    let project = project(&state, &slug)?;
+
+    let since = query.since.as_deref().map(parse_since).transpose()?;
+
+    let records = load_size_history(&state.base_path, &project.url_path, since).await?;
+    Ok(web::Json(records))
+}
+
+/// Expected duration of the project's next build, from the durations of its previous ones
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/rebuild-estimate")]
+pub async fn rebuild_estimate(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<web::Json<RebuildEstimate>> {
+    let project = project(&state, &slug)?;
+
+    let records = load_size_history(&state.base_path, &project.url_path, None).await?;
+    Ok(web::Json(estimate_rebuild(&records)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StaleQuery {
+    /// Projects not requested in this many days are stale
+    #[serde(default = "default_stale_days")]
+    days: u64,
+}
+
+fn default_stale_days() -> u64 {
+    90
+}
+
+#[derive(Debug, Serialize)]
+pub struct StaleProject {
+    pub slug: String,
+    /// `None` if never requested since access times were first recorded
+    pub last_accessed: Option<u64>,
+    /// Size of the project's directory under `libs_path`: checkout, build output and docs
+    pub size_bytes: u64,
+}
+
+/// Projects whose docs weren't requested within the last `days`, least recently used first
+#[get("/api/reports/stale")]
+pub async fn stale_report(
+    state: web::Data<Arc<AppState>>,
+    query: web::Query<StaleQuery>,
+) -> AppResult<web::Json<Vec<StaleProject>>> {
+    let cutoff = now_secs().saturating_sub(query.days.saturating_mul(24 * 60 * 60));
+
+    let mut stale = Vec::new();
+    for project in state.projects.values() {
+        let last_accessed = state.access.get(&project.url_path);
+        if last_accessed.is_some_and(|last| last >= cutoff) {
+            continue;
+        }
+        let path = state.base_path.join(&project.config.path);
+        let size_bytes = if fs::try_exists(&path).await? {
+            dir_size(&path).await?
+        } else {
+            0
+        };
+        stale.push(StaleProject {
+            slug: project.url_path.clone(),
+            last_accessed,
+            size_bytes,
+        });
+    }
+    stale.sort_by(|a, b| {
+        a.last_accessed
+            .cmp(&b.last_accessed)
+            .then_with(|| a.slug.cmp(&b.slug))
+    });
+    Ok(web::Json(stale))
+}
+
+/// Runs `git gc` in the project's repository, answering 409 if one is already running
+#[post("/api/projects/{project:[^/]+(?:/[^/]+)?}/gc")]
+pub async fn gc(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+
+    let path = state.base_path.join(&project.config.path);
+    match state.maintenance.gc(&path).await {
+        Some(report) => Ok(HttpResponse::Ok().json(report?)),
+        None => Ok(HttpResponse::Conflict().body("garbage collection already running")),
+    }
+}
+
+/// Endpoints that change things, and the metrics, served only on the admin listener. Nothing
+/// authenticates them, so they're left out entirely without one.
+pub fn admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(gc)
+        .service(upload::upload_docs)
+        .service(snapshots::create_snapshot)
+        .service(snapshots::delete_snapshot)
+        .service(metrics::metrics);
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtifactQuery {
+    /// `tar.gz` (the default) or `zip`
+    format: Option<String>,
+}
+
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/artifact")]
+pub async fn artifact(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+    query: web::Query<ArtifactQuery>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+    let format = ArchiveFormat::from_query(query.format.as_deref())?;
+
+    // held until the archive is fully written, including the directory walk
+    let Some(permit) = state.heavy_ops.acquire().await else {
+        return Ok(HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, "30"))
+            .body("too many archives being generated, try again later"));
+    };
+    let files = artifact::list_files(
+        &project.docs_path,
+        &project.serve_filter,
+        &project.ignore_rules,
+    )
+    .await
+    .map_err(|_| AppError::NotFound(format!("docs of project '{}'", slug)))?;
+    let archive = artifact::stream(project.docs_path.clone(), files, format, permit);
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "{}-docs.{}",
+                project.url_path.replace('/', "-"),
+                format.extension()
+            ))],
+        })
+        // clients can verify the extracted files against the build's checksums
+        .insert_header((
+            header::LINK,
+            format!(
+                "</api/projects/{}/manifest>; rel=\"describedby\"",
+                project.url_path
+            ),
+        ))
+        .streaming(ReaderStream::new(archive)))
+}
+
+/// Outcome of the project's last build, as written next to its docs
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/report")]
+pub async fn build_report(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+
+    let json = report::load(&report::path(&state.base_path, project), &project.url_path).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(json))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileQuery {
+    /// File to return, relative to the docs directory
+    path: String,
+}
+
+/// Where `path` is in the project's docs, after checking it stays inside them and that the
+/// serve filter allows it. `./` and repeated `/` are dropped first, so they can't get a path
+/// past anchored patterns.
+fn requested_file(project: &Project, path: &str) -> AppResult<PathBuf> {
+    if !is_contained_path(path) {
+        return Err(AppError::BadRequest(format!(
+            "path '{}' must be relative to the docs directory",
+            path
+        )));
+    }
+    let file = normalize_relative(path);
+    if !project.serve_filter.allows(&file) {
+        return Err(AppError::NotFound(path.to_string()));
+    }
+    Ok(file)
+}
+
+/// One file of the project's docs, with the content type of its extension. Files the serve
+/// filter rejects are as missing as files that don't exist.
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/file")]
+pub async fn docs_file(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+    query: web::Query<FileQuery>,
+) -> AppResult<NamedFile> {
+    let project = local_project(&state, &slug)?;
+    let file = requested_file(project, &query.path)?;
+
+    crate::serve_docs_file(&project.docs_path, &file.to_string_lossy()).await
+}
+
+/// Links to missing files in the project's docs, found after its last successful build
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/links")]
+pub async fn broken_links(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+    if !project.config.check_links {
+        return Err(AppError::NotFound(format!(
+            "link check of project '{}'",
+            slug
+        )));
+    }
+
+    let build_state = state.build_state.read().await;
+    let links = build_state
+        .project(&project.url_path)
+        .map_or(&[][..], |p| &p.broken_links);
+    Ok(HttpResponse::Ok().json(links))
+}
+
+/// Symbols documented by the project, read from rustdoc's search index or javadoc's index
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/docs-index")]
+pub async fn docs_index(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<web::Json<Vec<Symbol>>> {
+    let project = local_project(&state, &slug)?;
+
+    docs_index::load(&project.docs_path)
+        .await?
+        .map(web::Json)
+        .ok_or_else(|| AppError::NotFound(format!("symbol index of project '{}'", slug)))
+}
+
+/// SHA-256 checksums of the files served for a project, computed after its last build
+#[get("/api/projects/{project:[^/]+(?:/[^/]+)?}/manifest")]
+pub async fn docs_manifest(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let project = project(&state, &slug)?;
+
+    let json = manifest::load(&state.base_path, &project.url_path).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(json))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_requested_file() {
+        let libs = TempDir::new().unwrap();
+        let projects = crate::config::test_projects(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\nbuild_command = \"true\"\nnever_serve = [\"/private/**\", \"/a/b\"]\n",
+        )
+        .await;
+        let project = &projects["lib"];
+
+        assert_eq!(
+            requested_file(project, "./guide//intro.html").unwrap(),
+            Path::new("guide/intro.html")
+        );
+        for path in [
+            "private/secret.html",
+            "./private/secret.html",
+            ".//private/./secret.html",
+            "a/b",
+            "a//b",
+            "./a/b",
+        ] {
+            assert!(
+                matches!(requested_file(project, path), Err(AppError::NotFound(_))),
+                "{}",
+                path
+            );
+        }
+        assert!(matches!(
+            requested_file(project, "guide/../../x.html"),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    fn summary<'a>(
+        slug: &'a str,
+        name: &'a str,
+        build_system: &'a BuildSystem,
+        last_built: Option<u64>,
+    ) -> ProjectSummary<'a> {
+        ProjectSummary {
+            slug,
+            name,
+            branch: None,
+            variant_of: None,
+            also_known_as: &[],
+            build_system,
+            url: format!("/{}/", slug),
+            last_built,
+            last_accessed: None,
+            source: LOCAL_SOURCE,
+            commit: None,
+            cargo_lock_sha256: None,
+            release: None,
+            source_url: None,
+            last_errors: &[],
+            warnings: &[],
+            startup: None,
+        }
+    }
+
+    #[test]
+    fn test_sort_summaries() {
+        let (cargo, zig) = (BuildSystem::Cargo, BuildSystem::Zig);
+        let summaries = || {
+            vec![
+                summary("b", "beta", &zig, Some(10)),
+                summary("a", "Zeta", &cargo, None),
+                summary("c", "alpha", &zig, Some(5)),
+                summary("d", "delta", &cargo, Some(10)),
+            ]
+        };
+        let sorted = |sort| {
+            let mut summaries = summaries();
+            sort_summaries(&mut summaries, sort);
+            summaries.iter().map(|s| s.slug).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(IndexSort::Name), ["c", "b", "d", "a"]);
+        // ties keep the alphabetical order
+        assert_eq!(sorted(IndexSort::LastBuilt), ["b", "d", "c", "a"]);
+        assert_eq!(sorted(IndexSort::BuildSystem), ["d", "a", "c", "b"]);
+    }
+}
@@ -0,0 +1,702 @@
+use std::{path::Path, sync::Arc};
+
+use actix_files::Files;
+use actix_web::{
+    body::MessageBody,
+    dev::{RequestHead, ServiceFactory, ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::{self, Logger},
+    web, App, HttpMessage, HttpRequest, HttpResponse,
+};
+
+use crate::{
+    access, api, badges, favicon, index, metrics, portal, precompress, redirect_keeping_query,
+    rewrite, serve_docs_file, snapshots, unknown_path, utils::alias_location, versions, AppState,
+};
+
+/// Marks a request for a file the project's serve filter rejected
+struct Filtered;
+
+/// Docs, the read-only API and a route per project. The metrics are served here too unless
+/// `separate_admin`, where the admin listener has them.
+pub fn public_app(
+    state: Arc<AppState>,
+    separate_admin: bool,
+    logger: Logger,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let state = web::Data::new(state);
+
+    // create routes for each project
+    let mut app = App::new()
+        .app_data(state.clone())
+        .wrap(middleware::from_fn(precompress::serve_precompressed))
+        .wrap(middleware::from_fn(rewrite::rewrite_paths))
+        .wrap(middleware::from_fn(access::record_access))
+        .wrap(logger)
+        .wrap(middleware::from_fn(metrics::track_requests))
+        .service(index)
+        .service(favicon)
+        .service(badges::badge_proxy)
+        .service(api::version)
+        .service(api::list_projects)
+        .service(api::project_status)
+        .service(api::size_history)
+        .service(api::rebuild_estimate)
+        .service(api::artifact)
+        .service(api::docs_manifest)
+        .service(api::build_report)
+        .service(api::broken_links)
+        .service(api::docs_index)
+        .service(api::docs_file)
+        .service(api::stale_report)
+        .service(portal::portal_manifest)
+        .default_service(web::to(unknown_path));
+    if !separate_admin {
+        app = app.service(metrics::metrics);
+    }
+
+    for project in state.projects.values() {
+        let docs_path = project.docs_path.clone();
+        let route = project.url_path.clone();
+        let resource_path = format!("/{}", route);
+
+        let index_url = project.index_url();
+
+        // old slugs redirect permanently, keeping the rest of the path and the query, and
+        // the method with a `308` for anything but GET and HEAD
+        for alias in &project.aliases {
+            let slug = route.clone();
+            app = app.service(
+                web::resource(vec![
+                    format!("/{}", alias),
+                    format!("/{}/{{tail:.*}}", alias),
+                ])
+                .to(move |req: HttpRequest| {
+                    let location = alias_location(
+                        &slug,
+                        req.match_info().get("tail").unwrap_or_default(),
+                        req.query_string(),
+                    );
+                    let mut response = if matches!(*req.method(), Method::GET | Method::HEAD) {
+                        HttpResponse::MovedPermanently()
+                    } else {
+                        HttpResponse::PermanentRedirect()
+                    };
+                    async move { response.append_header(("Location", location)).finish() }
+                }),
+            );
+        }
+
+        // the project's own slug leads to its default branch
+        if let Some(branch) = project.branch.as_ref().filter(|b| b.is_default) {
+            let index_url = index_url.clone();
+            app = app.service(
+                web::resource(vec![
+                    format!("/{}", branch.parent),
+                    format!("/{}/", branch.parent),
+                ])
+                .to(move |req: HttpRequest| {
+                    let response = redirect_keeping_query(&req, &index_url);
+                    async move { response }
+                }),
+            );
+        }
+
+        // closure with captured variables for each project
+        let index_url_clone = index_url.clone();
+        app = app.service(web::resource(&resource_path).to(move |req: HttpRequest| {
+            let response = redirect_keeping_query(&req, &index_url_clone);
+            async move { response }
+        }));
+
+        // proxied docs replace local files entirely
+        if let Some(proxy) = project.proxy.clone() {
+            app = app.service(web::resource(format!("/{}/{{tail:.*}}", route)).to(
+                move |req: HttpRequest, body: web::Bytes| {
+                    let proxy = proxy.clone();
+                    async move {
+                        let tail = req.match_info().get("tail").unwrap_or_default();
+                        proxy.forward(&req, body, tail).await
+                    }
+                },
+            ));
+            continue;
+        }
+
+        // named snapshots take precedence over a `snapshots` directory of the docs
+        let slug = route.clone();
+        app = app.service(
+            web::resource(format!("/{}/snapshots/{{name}}/{{tail:.*}}", route)).to(
+                move |req: HttpRequest, state: web::Data<Arc<AppState>>| {
+                    let slug = slug.clone();
+                    async move {
+                        let info = req.match_info();
+                        snapshots::serve(
+                            &state.base_path,
+                            &state.projects[&slug],
+                            info.get("name").unwrap_or_default(),
+                            info.get("tail").unwrap_or_default(),
+                        )
+                        .await
+                    }
+                },
+            ),
+        );
+
+        // versioned static docs get a picker at the root, and `latest` follows the newest
+        if project.config.versions_from_dirs {
+            let name = project.name().to_string();
+            let (slug, versions_path) = (route.clone(), docs_path.clone());
+            app = app.service(web::resource(format!("/{}/", route)).to(move || {
+                let (name, slug, versions_path) =
+                    (name.clone(), slug.clone(), versions_path.clone());
+                async move { versions::index_page(&name, &slug, &versions_path).await }
+            }));
+
+            let (slug, versions_path) = (route.clone(), docs_path.clone());
+            app = app.service(
+                web::resource(vec![
+                    format!("/{}/latest", route),
+                    format!("/{}/latest/{{tail:.*}}", route),
+                ])
+                .to(move |req: HttpRequest| {
+                    let (slug, versions_path) = (slug.clone(), versions_path.clone());
+                    async move {
+                        versions::latest_redirect(
+                            &slug,
+                            &versions_path,
+                            req.match_info().get("tail").unwrap_or_default(),
+                            req.query_string(),
+                        )
+                        .await
+                    }
+                }),
+            );
+        }
+
+        // single-file artifacts are served directly at the project root
+        if let Some(entry_file) = project.config.entry_file.clone() {
+            let docs_path = docs_path.clone();
+            app = app.service(web::resource(format!("/{}/", route)).to(move || {
+                let docs_path = docs_path.clone();
+                let entry_file = entry_file.clone();
+                async move { serve_docs_file(&docs_path, &entry_file).await }
+            }));
+        }
+
+        // filtered paths are rejected before touching the filesystem
+        let serve_filter = project.serve_filter.clone();
+        let index_file = project.index_file_name().to_string();
+        let path_filter = move |path: &Path, head: &RequestHead| {
+            let allowed = if path.as_os_str().is_empty() || head.uri.path().ends_with('/') {
+                serve_filter.allows(&path.join(&index_file))
+            } else {
+                serve_filter.allows(path)
+            };
+            if !allowed {
+                head.extensions_mut().insert(Filtered);
+            }
+            allowed
+        };
+
+        // misses get the project's own 404 page if it has one
+        let slug = route.clone();
+        app = app.service(
+            Files::new(&format!("/{}", route), docs_path)
+                .index_file(project.index_file_name())
+                .path_filter(path_filter)
+                .default_handler(web::to(
+                    move |req: HttpRequest, state: web::Data<Arc<AppState>>| {
+                        let index_url = index_url.clone();
+                        let slug = slug.clone();
+                        let filtered = req.extensions().contains::<Filtered>();
+                        async move {
+                            if let Some(page) =
+                                state.error_pages.not_found(&state.projects[&slug]).await
+                            {
+                                return page;
+                            }
+                            if filtered {
+                                return HttpResponse::NotFound().finish();
+                            }
+                            redirect_keeping_query(&req, &index_url)
+                        }
+                    },
+                )),
+        );
+    }
+
+    app
+}
+
+/// The endpoints that change things, sharing the public listener's state
+pub fn admin_app(
+    state: Arc<AppState>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(web::Data::new(state))
+        .wrap(Logger::default())
+        .configure(api::admin_routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+
+    use super::*;
+    use crate::{
+        config::{initialize_projects, parse_config},
+        state::BuildState,
+    };
+
+    const PROJECTS: &str = r#"
+[[projects]]
+path = "cargo-project"
+build_system = "cargo"
+aliases = ["old-cargo-project"]
+
+[[projects]]
+path = "custom-project"
+build_system = "custom"
+build_command = "true"
+"#;
+
+    /// The state `run` sets up for the fixture projects, without building them
+    async fn test_state() -> Arc<AppState> {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let config = parse_config(&fixtures, PROJECTS);
+        let mut projects = initialize_projects(&config).await.unwrap();
+        for project in projects.values_mut() {
+            project.resolve_index_file();
+        }
+        let build_state = BuildState::load(&config.libs_path).await;
+        Arc::new(AppState::new(&config, projects, build_state, HashMap::new()).unwrap())
+    }
+
+    async fn text(response: ServiceResponse<impl MessageBody>) -> String {
+        String::from_utf8(read_body(response).await.to_vec()).unwrap()
+    }
+
+    fn get(uri: &str) -> TestRequest {
+        TestRequest::get().uri(uri)
+    }
+
+    #[actix_web::test]
+    async fn test_index_lists_projects() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let body = text(response).await;
+        assert!(body.contains(r#"<a href="/cargo-project/">cargo-project</a>"#));
+        assert!(body.contains(r#"<a href="/custom-project/">custom-project</a>"#));
+        // aliases aren't listed as projects of their own
+        assert!(!body.contains("old-cargo-project"));
+    }
+
+    #[actix_web::test]
+    async fn test_index_negotiates_json() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let request = get("/").insert_header(("Accept", "application/json"));
+        let response = call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let index: serde_json::Value = read_body_json(response).await;
+        let api = call_service(&app, get("/api/projects").to_request()).await;
+        assert_eq!(index, read_body_json::<serde_json::Value, _>(api).await);
+
+        for accept in ["text/html", "*/*"] {
+            let request = get("/").insert_header(("Accept", accept));
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()["content-type"], "text/html");
+            assert!(text(response)
+                .await
+                .contains(r#"<a href="/cargo-project/">cargo-project</a>"#));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_serves_generated_favicon() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/favicon.ico").to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+        assert!(text(response).await.contains(r##"fill="#0366d6""##));
+    }
+
+    #[actix_web::test]
+    async fn test_api_lists_projects() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/api/projects").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let projects: serde_json::Value = read_body_json(response).await;
+        let slugs = projects
+            .as_array()
+            .expect("expected a JSON array")
+            .iter()
+            .map(|p| p["slug"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(slugs, ["cargo-project", "custom-project"]);
+        assert_eq!(projects[0]["build_system"], "cargo");
+        assert_eq!(projects[0]["also_known_as"][0], "old-cargo-project");
+        assert_eq!(projects[1]["source"], "local working copy");
+    }
+
+    #[actix_web::test]
+    async fn test_reports_version() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/api/version").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let version: serde_json::Value = read_body_json(response).await;
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        for key in ["git_commit", "build_date", "rustc_version"] {
+            assert!(version[key].is_string(), "{}", key);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_serves_project_docs() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        for (slug, content) in [
+            ("cargo-project", "cargo fixture docs"),
+            ("custom-project", "custom fixture docs"),
+        ] {
+            let response = call_service(&app, get(&format!("/{}/", slug)).to_request()).await;
+            assert_eq!(response.status(), 200, "/{}/", slug);
+            assert!(text(response).await.contains(content));
+
+            // the bare project path redirects to the docs
+            let response = call_service(&app, get(&format!("/{}", slug)).to_request()).await;
+            assert_eq!(response.status(), 302, "/{}", slug);
+            assert_eq!(response.headers()["location"], format!("/{}/", slug));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_alias_redirects_permanently() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        for (path, location) in [
+            ("/old-cargo-project", "/cargo-project/"),
+            ("/old-cargo-project/", "/cargo-project/"),
+            (
+                "/old-cargo-project/sub/page.html?search=x",
+                "/cargo-project/sub/page.html?search=x",
+            ),
+        ] {
+            let response = call_service(&app, get(path).to_request()).await;
+            assert_eq!(response.status(), 301, "{}", path);
+            assert_eq!(response.headers()["location"], location, "{}", path);
+        }
+
+        // other methods keep theirs
+        let request = TestRequest::post().uri("/old-cargo-project/sub?x=1");
+        let response = call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 308);
+        assert_eq!(response.headers()["location"], "/cargo-project/sub?x=1");
+    }
+
+    #[actix_web::test]
+    async fn test_project_redirects_keep_query() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        for (path, location) in [
+            ("/cargo-project", "/cargo-project/"),
+            (
+                "/cargo-project?search=Foo%20Bar&kind=struct",
+                "/cargo-project/?search=Foo%20Bar&kind=struct",
+            ),
+            // misses lead to the docs' start, query included
+            (
+                "/custom-project/missing.html?q=%C3%A9",
+                "/custom-project/?q=%C3%A9",
+            ),
+        ] {
+            let response = call_service(&app, get(path).to_request()).await;
+            assert_eq!(response.status(), 302, "{}", path);
+            assert_eq!(response.headers()["location"], location, "{}", path);
+        }
+
+        // paths already ending in a slash are served as they are
+        let response = call_service(&app, get("/custom-project/?search=x").to_request()).await;
+        assert_eq!(response.status(), 200);
+
+        // other methods are told to repeat the request as it was
+        let request = TestRequest::post().uri("/cargo-project?x=1");
+        let response = call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 307);
+        assert_eq!(response.headers()["location"], "/cargo-project/?x=1");
+    }
+
+    #[actix_web::test]
+    async fn test_not_found() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        for path in [
+            // custom-project has no docs index, and links need `check_links`
+            "/api/projects/custom-project/docs-index",
+            "/api/projects/missing-project/docs-index",
+            "/api/projects/cargo-project/links",
+            "/api/projects/missing/status",
+            "/missing/",
+            // `*.key` is in the default never_serve list
+            "/custom-project/deploy.key",
+            "/custom-project/snapshots/v1.0/",
+        ] {
+            let response = call_service(&app, get(path).to_request()).await;
+            assert_eq!(response.status(), 404, "{}", path);
+        }
+        let response = call_service(&app, get("/custom-project/index.html").to_request()).await;
+        assert_eq!(response.status(), 200);
+
+        // the manifest and the report are only written by a build
+        let uri = "/api/projects/custom-project/manifest";
+        let response = call_service(&app, get(uri).to_request()).await;
+        assert_eq!(response.status(), 404);
+        let uri = "/api/projects/custom-project/report";
+        let response = call_service(&app, get(uri).to_request()).await;
+        assert_eq!(response.status(), 404);
+        assert!(text(response).await.contains("build report"));
+    }
+
+    #[actix_web::test]
+    async fn test_downloads_docs_archives() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        for (format, content_type, magic) in [
+            ("", "application/gzip", &[0x1f, 0x8b][..]),
+            ("?format=zip", "application/zip", &b"PK"[..]),
+        ] {
+            let uri = format!("/api/projects/custom-project/artifact{}", format);
+            let response = call_service(&app, get(&uri).to_request()).await;
+            assert_eq!(response.status(), 200, "{}", format);
+            assert_eq!(response.headers()["content-type"], content_type);
+            assert_eq!(
+                response.headers()["link"],
+                "</api/projects/custom-project/manifest>; rel=\"describedby\""
+            );
+            assert!(read_body(response).await.starts_with(magic), "{}", format);
+        }
+
+        let uri = "/api/projects/custom-project/artifact?format=rar";
+        let response = call_service(&app, get(uri).to_request()).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn test_estimates_rebuild_without_history() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let uri = "/api/projects/custom-project/rebuild-estimate";
+        let response = call_service(&app, get(uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        let estimate: serde_json::Value = read_body_json(response).await;
+        assert_eq!(
+            estimate,
+            serde_json::json!({
+                "estimated_seconds": null,
+                "confidence": "low",
+                "sample_size": 0,
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_returns_single_docs_files() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let uri = "/api/projects/custom-project/file?path=index.html";
+        let response = call_service(&app, get(uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        assert!(text(response).await.contains("custom fixture docs"));
+
+        for (path, status) in [
+            ("../config.toml", 400),
+            ("/etc/passwd", 400),
+            ("deploy.key", 404),
+            ("./deploy.key", 404),
+            (".//index.html", 200),
+            ("missing.html", 404),
+            (".", 404),
+        ] {
+            let uri = format!("/api/projects/custom-project/file?path={}", path);
+            let response = call_service(&app, get(&uri).to_request()).await;
+            assert_eq!(response.status(), status, "{}", path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_slug_suggests_similar_projects() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/cargo/sub/page.html").to_request()).await;
+        assert_eq!(response.status(), 404);
+        let body = text(response).await;
+        assert!(body.contains("href=\"/cargo-project/\""));
+        assert!(!body.contains("custom-project"));
+    }
+
+    #[actix_web::test]
+    async fn test_stale_report_skips_requested_projects() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+        let response = call_service(&app, get("/custom-project/").to_request()).await;
+        assert_eq!(response.status(), 200);
+
+        let projects = call_service(&app, get("/api/projects").to_request()).await;
+        let projects: serde_json::Value = read_body_json(projects).await;
+        let custom = projects
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["slug"] == "custom-project")
+            .unwrap();
+        assert!(custom["last_accessed"].is_u64());
+
+        let stale = call_service(&app, get("/api/reports/stale?days=90").to_request()).await;
+        let stale: serde_json::Value = read_body_json(stale).await;
+        let slugs = stale
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["slug"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(slugs.contains(&"cargo-project"));
+        assert!(!slugs.contains(&"custom-project"));
+    }
+
+    #[actix_web::test]
+    async fn test_portal_manifest_supports_etags() {
+        let app = init_service(public_app(test_state().await, false, Logger::default())).await;
+
+        let response = call_service(&app, get("/api/manifest").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let etag = response.headers()["etag"].clone();
+        let manifest: serde_json::Value = read_body_json(response).await;
+        assert_eq!(manifest["schema_version"], 1);
+        assert_eq!(manifest["projects"][0]["slug"], "cargo-project");
+        assert_eq!(manifest["projects"][1]["url"], "/custom-project/");
+
+        let request = get("/api/manifest").insert_header(("If-None-Match", etag));
+        let unchanged = call_service(&app, request.to_request()).await;
+        assert_eq!(unchanged.status(), 304);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_endpoints_only_on_admin_listener() {
+        let state = test_state().await;
+        let admin = init_service(admin_app(state.clone())).await;
+        let mutating = [
+            (Method::POST, "/api/projects/cargo-project/gc"),
+            (Method::PUT, "/api/projects/custom-project/docs"),
+            (Method::POST, "/api/projects/custom-project/snapshots"),
+            (
+                Method::DELETE,
+                "/api/projects/custom-project/snapshots/v1.0",
+            ),
+        ];
+
+        for separate_admin in [false, true] {
+            let public =
+                init_service(public_app(state.clone(), separate_admin, Logger::default())).await;
+            for (method, path) in &mutating {
+                let request = TestRequest::default()
+                    .method(method.clone())
+                    .uri(path)
+                    .insert_header(("Content-Type", "application/json"))
+                    .set_payload(r#"{"name": "v1.0"}"#);
+                let response = call_service(&public, request.to_request()).await;
+                assert_eq!(response.status(), 404, "{} {}", method, path);
+                // answered by no route, rather than by the endpoint not finding something
+                assert_eq!(
+                    text(response).await,
+                    format!("Not found: {}", path),
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+
+            // the metrics move to the admin listener along with them
+            let response = call_service(&public, get("/metrics").to_request()).await;
+            let status = if separate_admin { 404 } else { 200 };
+            assert_eq!(response.status(), status, "{}", separate_admin);
+        }
+
+        // the fixtures aren't git repositories, which only the admin listener gets to report
+        let request = TestRequest::post().uri("/api/projects/cargo-project/gc");
+        let response = call_service(&admin, request.to_request()).await;
+        assert_eq!(response.status(), 404);
+        assert!(text(response).await.contains("git repository"));
+
+        // docs and the read-only API stay public only
+        for path in ["/cargo-project/", "/api/projects/cargo-project/status"] {
+            let response = call_service(&admin, get(path).to_request()).await;
+            assert_eq!(response.status(), 404, "{}", path);
+        }
+
+        let response = call_service(&admin, get("/metrics").to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert!(text(response)
+            .await
+            .contains("lichen_http_connections_total "));
+    }
+
+    #[actix_web::test]
+    async fn test_snapshot_names_are_validated() {
+        let admin = init_service(admin_app(test_state().await)).await;
+
+        let request = TestRequest::post()
+            .uri("/api/projects/custom-project/snapshots")
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(r#"{"name": "../up"}"#);
+        let response = call_service(&admin, request.to_request()).await;
+        assert_eq!(response.status(), 400);
+
+        let request = TestRequest::delete().uri("/api/projects/custom-project/snapshots/v1.0");
+        let response = call_service(&admin, request.to_request()).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_upload_requires_docs_field() {
+        let admin = init_service(admin_app(test_state().await)).await;
+
+        let request = TestRequest::put()
+            .uri("/api/projects/custom-project/docs")
+            .insert_header(("Content-Type", "multipart/form-data; boundary=x"))
+            .set_payload(
+                "--x\r\nContent-Disposition: form-data; name=\"other\"\r\n\r\nvalue\r\n--x--\r\n",
+            );
+        let response = call_service(&admin, request.to_request()).await;
+        assert_eq!(response.status(), 400);
+        assert!(text(response).await.contains("'docs'"));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_compression::tokio::write::GzipEncoder;
+use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use tokio::{
+    fs,
+    io::{AsyncWriteExt, DuplexStream},
+};
+use tokio_util::compat::FuturesAsyncWriteCompatExt;
+use tracing::{error, warn};
+
+use crate::{
+    error::{AppError, AppResult},
+    filter::{IgnoreRules, ServeFilter},
+    limits::HeavyPermit,
+};
+
+/// Bytes buffered between the archive writer and the response
+const STREAM_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Parses the `format` query parameter, defaulting to `tar.gz`
+    pub fn from_query(format: Option<&str>) -> AppResult<Self> {
+        match format {
+            None | Some("tar.gz" | "tgz") => Ok(ArchiveFormat::TarGz),
+            Some("zip") => Ok(ArchiveFormat::Zip),
+            Some(other) => Err(AppError::BadRequest(format!(
+                "unknown archive format '{}', expected tar.gz or zip",
+                other
+            ))),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "application/gzip",
+            ArchiveFormat::Zip => "application/zip",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// Regular files below `root` that `filter` allows and `ignore` doesn't match, relative to
+/// `root` and sorted. Symlinks aren't followed, so nothing outside the docs ends up in an
+/// archive.
+pub async fn list_files(
+    root: &Path,
+    filter: &ServeFilter,
+    ignore: &IgnoreRules,
+) -> AppResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(root.join(&dir)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let relative = dir.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if ignore.is_ignored(&relative, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(relative);
+            } else if file_type.is_file() && filter.allows(&relative) {
+                files.push(relative);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Archives `files` (relative to `root`) in the background, returning the read end of the
+/// archive as it's written. Errors midway are logged and cut the archive short. `permit` is
+/// released once the archive is complete or the client went away.
+pub fn stream(
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    format: ArchiveFormat,
+    permit: HeavyPermit,
+) -> DuplexStream {
+    let (reader, writer) = tokio::io::duplex(STREAM_BUFFER);
+
+    tokio::spawn(async move {
+        let _permit = permit;
+        let result = match format {
+            ArchiveFormat::TarGz => write_tar_gz(&root, &files, writer).await,
+            ArchiveFormat::Zip => write_zip(&root, &files, writer).await,
+        };
+        if let Err(e) = result {
+            error!("Failed to archive {}: {}", root.display(), e);
+        }
+    });
+
+    reader
+}
+
+async fn write_tar_gz(root: &Path, files: &[PathBuf], writer: DuplexStream) -> io::Result<()> {
+    let mut tar = tokio_tar::Builder::new(GzipEncoder::new(writer));
+    for file in files {
+        tar.append_path_with_name(root.join(file), file).await?;
+    }
+
+    let mut gzip = tar.into_inner().await?;
+    gzip.shutdown().await
+}
+
+/// Name of `file` inside a ZIP archive, with `/` separators. `None` for names that aren't
+/// UTF-8, which ZIP archives have no portable way of storing.
+fn zip_entry_name(file: &Path) -> Option<String> {
+    file.components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()
+        .map(|parts| parts.join("/"))
+}
+
+async fn write_zip(root: &Path, files: &[PathBuf], writer: DuplexStream) -> io::Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    for file in files {
+        let Some(name) = zip_entry_name(file) else {
+            warn!(
+                "Leaving {} out of the ZIP archive of {}, its name isn't UTF-8",
+                file.display(),
+                root.display()
+            );
+            continue;
+        };
+        // entries are streamed through a fixed-size buffer, however large the file
+        let mut source = fs::File::open(root.join(file)).await?;
+        let mut entry = zip
+            .write_entry_stream(ZipEntryBuilder::new(name.into(), Compression::Deflate))
+            .await
+            .map_err(io::Error::other)?
+            .compat_write();
+        tokio::io::copy(&mut source, &mut entry).await?;
+        entry.into_inner().close().await.map_err(io::Error::other)?;
+    }
+
+    zip.close().await.map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_archive_format_from_query() {
+        assert_eq!(
+            ArchiveFormat::from_query(None).unwrap(),
+            ArchiveFormat::TarGz
+        );
+        assert_eq!(
+            ArchiveFormat::from_query(Some("tar.gz")).unwrap(),
+            ArchiveFormat::TarGz
+        );
+        assert_eq!(
+            ArchiveFormat::from_query(Some("zip")).unwrap(),
+            ArchiveFormat::Zip
+        );
+        assert!(ArchiveFormat::from_query(Some("rar")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_files_applies_filter() {
+        let docs = TempDir::new().unwrap();
+        for file in ["index.html", "a/b/page.html", "deploy.key", "a/.git/config"] {
+            let path = docs.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+
+        let filter =
+            ServeFilter::new(&[], &["*.key".to_string(), ".git/**".to_string()], "lib").unwrap();
+        let files = list_files(docs.path(), &filter, &IgnoreRules::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            files,
+            [PathBuf::from("a/b/page.html"), PathBuf::from("index.html")]
+        );
+
+        let ignore = IgnoreRules::new(&["b/".to_string()], &[], "lib").unwrap();
+        let files = list_files(docs.path(), &filter, &ignore).await.unwrap();
+        assert_eq!(files, [PathBuf::from("index.html")]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_archives_with_non_utf8_name() {
+        use tokio::io::AsyncReadExt;
+
+        let docs = TempDir::new().unwrap();
+        let latin1 = crate::utils::path_from_bytes(b"caf\xe9.html".to_vec());
+        std::fs::write(docs.path().join(&latin1), "caf\u{e9}").unwrap();
+        std::fs::write(docs.path().join("index.html"), "index").unwrap();
+
+        let files = list_files(
+            docs.path(),
+            &ServeFilter::new(&[], &[], "lib").unwrap(),
+            &IgnoreRules::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(files, [latin1.clone(), PathBuf::from("index.html")]);
+        assert_eq!(zip_entry_name(&latin1), None);
+        assert_eq!(
+            zip_entry_name(Path::new("a/b.html")).as_deref(),
+            Some("a/b.html")
+        );
+
+        // the ZIP archive leaves the file out, the tarball keeps its name as it is
+        let (mut reader, writer) = tokio::io::duplex(STREAM_BUFFER);
+        let mut zip = Vec::new();
+        let (written, read) = tokio::join!(
+            write_zip(docs.path(), &files, writer),
+            reader.read_to_end(&mut zip)
+        );
+        written.unwrap();
+        read.unwrap();
+        assert!(!zip.is_empty());
+
+        let (mut reader, writer) = tokio::io::duplex(STREAM_BUFFER);
+        let mut tar_gz = Vec::new();
+        let (written, read) = tokio::join!(
+            write_tar_gz(docs.path(), &files, writer),
+            reader.read_to_end(&mut tar_gz)
+        );
+        written.unwrap();
+        read.unwrap();
+        let mut archive = tokio_tar::Archive::new(
+            async_compression::tokio::bufread::GzipDecoder::new(&tar_gz[..]),
+        );
+        let mut names = Vec::new();
+        let mut entries = archive.entries().unwrap();
+        while let Some(entry) = futures_util::StreamExt::next(&mut entries).await {
+            names.push(entry.unwrap().path().unwrap().into_owned());
+        }
+        assert_eq!(names, files);
+    }
+}
@@ -0,0 +1,259 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use actix_web::{get, http::header, web, HttpResponse};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{
+    config::{BuildSystem, Project},
+    error::{AppError, AppResult},
+    utils::escape_html,
+    AppState,
+};
+
+/// How long fetching a proxied badge may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest badge image passed through, badges are a few KiB at most
+const MAX_BADGE_BYTES: usize = 1024 * 1024;
+/// Redirects followed between allowed hosts
+const MAX_REDIRECTS: usize = 5;
+/// Badges are served from lichen's origin, so SVGs mustn't run scripts or load anything
+const BADGE_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'";
+
+/// An image linking somewhere, shown next to a project on the index
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Badge {
+    pub img: String,
+    #[serde(default)]
+    pub href: Option<String>,
+}
+
+/// Name of the package in a `Cargo.toml`, `None` for workspaces without one
+fn cargo_package_name(manifest: &str) -> Option<String> {
+    let manifest: toml::Table = manifest.parse().ok()?;
+    manifest
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Name of the package in the `Cargo.toml` of `project_dir`, `None` while the repository
+/// isn't cloned yet
+pub async fn cargo_package(project_dir: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(project_dir.join("Cargo.toml"))
+        .await
+        .ok()?;
+    cargo_package_name(&manifest)
+}
+
+fn crates_io_badge(crate_name: &str) -> Badge {
+    Badge {
+        img: format!("https://img.shields.io/crates/v/{}", crate_name),
+        href: Some(format!("https://crates.io/crates/{}", crate_name)),
+    }
+}
+
+/// The project's configured badges, plus with `auto_badges` the ones derived from its sources
+pub async fn project_badges(project: &Project, libs_path: &Path) -> Vec<Badge> {
+    let mut badges = project.config.badges.clone();
+
+    if project.config.auto_badges && matches!(project.config.build_system, BuildSystem::Cargo) {
+        if let Some(name) = cargo_package(&libs_path.join(&project.config.path)).await {
+            badges.push(crates_io_badge(&name));
+        }
+    }
+
+    badges
+}
+
+/// Serves badges of allowed hosts through lichen, for clients that can only reach the server
+#[derive(Debug)]
+pub struct BadgeProxy {
+    hosts: Vec<String>,
+    client: reqwest::Client,
+}
+
+impl BadgeProxy {
+    pub fn new(hosts: &[String]) -> AppResult<Self> {
+        // every hop has to stay on an allowed host, or a redirect could reach anything
+        let redirect_hosts = hosts.to_vec();
+        let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_allowed(&redirect_hosts, attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(redirects)
+            .build()
+            .map_err(|e| AppError::Config(format!("failed to create badge client: {}", e)))?;
+        Ok(Self {
+            hosts: hosts.to_vec(),
+            client,
+        })
+    }
+
+    /// The parsed `url` if it's an http(s) URL on an allowed host
+    fn allowed_url(&self, url: &str) -> Option<reqwest::Url> {
+        let url = reqwest::Url::parse(url).ok()?;
+        is_allowed(&self.hosts, &url).then_some(url)
+    }
+
+    /// Where the index loads `img` from: through the proxy for allowed hosts, directly otherwise
+    pub fn image_src(&self, img: &str) -> String {
+        if self.allowed_url(img).is_none() {
+            return img.to_string();
+        }
+
+        let mut url = reqwest::Url::parse("http://localhost/badge-proxy").expect("valid URL");
+        url.query_pairs_mut().append_pair("url", img);
+        format!("{}?{}", url.path(), url.query().unwrap_or_default())
+    }
+}
+
+/// Whether `url` is an http(s) URL on one of `hosts`
+fn is_allowed(hosts: &[String], url: &reqwest::Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+        && url
+            .host_str()
+            .is_some_and(|host| hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+}
+
+/// HTML of a project's badges on the index
+pub fn render(badges: &[Badge], proxy: &BadgeProxy) -> String {
+    badges
+        .iter()
+        .map(|badge| {
+            let img = format!(
+                "<img src=\"{}\" alt=\"\">",
+                escape_html(&proxy.image_src(&badge.img))
+            );
+            match &badge.href {
+                Some(href) => format!("<a href=\"{}\">{}</a>", escape_html(href), img),
+                None => img,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BadgeProxyQuery {
+    url: String,
+}
+
+#[get("/badge-proxy")]
+pub async fn badge_proxy(
+    state: web::Data<Arc<AppState>>,
+    query: web::Query<BadgeProxyQuery>,
+) -> AppResult<HttpResponse> {
+    let proxy = &state.badge_proxy;
+    let Some(url) = proxy.allowed_url(&query.url) else {
+        return Ok(HttpResponse::Forbidden().body("host not allowed by badge_proxy_hosts"));
+    };
+
+    let Ok(upstream) = proxy.client.get(url).send().await else {
+        return Ok(HttpResponse::BadGateway().finish());
+    };
+    let content_type = upstream
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !upstream.status().is_success() || !content_type.starts_with("image/") {
+        return Ok(HttpResponse::BadGateway().finish());
+    }
+    if upstream
+        .content_length()
+        .is_some_and(|len| len > MAX_BADGE_BYTES as u64)
+    {
+        return Ok(HttpResponse::BadGateway().body("badge too large"));
+    }
+
+    // chunked responses have no length to check up front
+    let mut body = Vec::new();
+    let mut chunks = upstream.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let Ok(chunk) = chunk else {
+            return Ok(HttpResponse::BadGateway().finish());
+        };
+        if body.len() + chunk.len() > MAX_BADGE_BYTES {
+            return Ok(HttpResponse::BadGateway().body("badge too large"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .append_header(("Cache-Control", "public, max-age=3600"))
+        .append_header((header::CONTENT_SECURITY_POLICY, BADGE_CSP))
+        .append_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy() -> BadgeProxy {
+        BadgeProxy::new(&["img.shields.io".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_cargo_package_name() {
+        assert_eq!(
+            cargo_package_name("[package]\nname = \"lichen\"\nversion = \"0.1.0\"\n").as_deref(),
+            Some("lichen")
+        );
+        assert_eq!(cargo_package_name("[workspace]\nmembers = [\"a\"]\n"), None);
+        assert_eq!(cargo_package_name("not toml ["), None);
+    }
+
+    #[test]
+    fn test_image_src() {
+        let proxy = proxy();
+        assert_eq!(
+            proxy.image_src("https://img.shields.io/crates/v/serde"),
+            "/badge-proxy?url=https%3A%2F%2Fimg.shields.io%2Fcrates%2Fv%2Fserde"
+        );
+        assert_eq!(
+            proxy.image_src("https://example.com/ci.svg"),
+            "https://example.com/ci.svg"
+        );
+        assert!(proxy.allowed_url("ftp://img.shields.io/x").is_none());
+        assert!(proxy.allowed_url("https://IMG.shields.io/x").is_some());
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let hosts = ["img.shields.io".to_string()];
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+        assert!(is_allowed(&hosts, &url("https://img.shields.io/badge/x")));
+        // where a redirect of an allowed host may lead
+        assert!(!is_allowed(&hosts, &url("http://169.254.169.254/latest")));
+        assert!(!is_allowed(&hosts, &url("http://localhost:8080/admin")));
+        assert!(!is_allowed(&hosts, &url("file:///etc/passwd")));
+    }
+
+    #[test]
+    fn test_render() {
+        let badges = [
+            crates_io_badge("serde"),
+            Badge {
+                img: "https://example.com/ci.svg?branch=\"main\"".to_string(),
+                href: None,
+            },
+        ];
+        assert_eq!(
+            render(&badges, &BadgeProxy::new(&[]).unwrap()),
+            "<a href=\"https://crates.io/crates/serde\"><img src=\"https://img.shields.io/crates/v/serde\" alt=\"\"></a>\
+             <img src=\"https://example.com/ci.svg?branch=&quot;main&quot;\" alt=\"\">"
+        );
+    }
+}
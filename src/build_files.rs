@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use tokio::fs;
+
+use crate::{
+    config::BuildSystem,
+    error::{AppError, AppResult},
+    pydoc,
+};
+
+/// Files one of which a Gradle project needs
+const GRADLE_FILES: [&str; 4] = [
+    "build.gradle",
+    "build.gradle.kts",
+    "settings.gradle",
+    "settings.gradle.kts",
+];
+
+/// Config files one of which a VuePress site needs
+pub const VUEPRESS_CONFIGS: [&str; 2] = ["docs/.vuepress/config.js", "docs/.vuepress/config.ts"];
+
+/// Config files one of which a Docusaurus site needs
+pub const DOCUSAURUS_CONFIGS: [&str; 2] = ["docusaurus.config.js", "docusaurus.config.ts"];
+
+async fn has_any(project_path: &Path, files: &[&str]) -> bool {
+    for file in files {
+        if fs::try_exists(project_path.join(file))
+            .await
+            .unwrap_or(false)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Which JavaScript docs framework the site at `project_path` is built with, going by its
+/// config file
+pub async fn detect_js_site(project_path: &Path) -> Option<BuildSystem> {
+    if has_any(project_path, &VUEPRESS_CONFIGS).await {
+        Some(BuildSystem::Vuepress)
+    } else if has_any(project_path, &DOCUSAURUS_CONFIGS).await {
+        Some(BuildSystem::Docusaurus)
+    } else {
+        None
+    }
+}
+
+async fn has_zig_file(dir: &Path) -> bool {
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return false;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.path().extension().is_some_and(|e| e == "zig") {
+            return true;
+        }
+    }
+    false
+}
+
+/// Checks that `project_path` has what its build system starts from, so a wrong path or an
+/// incomplete checkout is reported as such rather than as a failing build command
+pub async fn check(build_system: &BuildSystem, project_path: &Path) -> AppResult<()> {
+    let missing = |expected: &str| {
+        Err(AppError::MissingBuildFile {
+            expected: expected.to_string(),
+            path: project_path.to_path_buf(),
+        })
+    };
+
+    match build_system {
+        BuildSystem::Cargo => {
+            if !fs::try_exists(project_path.join("Cargo.toml")).await? {
+                return missing("Cargo.toml");
+            }
+        }
+        BuildSystem::Gradle => {
+            for file in GRADLE_FILES {
+                if fs::try_exists(project_path.join(file)).await? {
+                    return Ok(());
+                }
+            }
+            return missing(&GRADLE_FILES.join(" or "));
+        }
+        BuildSystem::Zig => {
+            if !has_zig_file(project_path).await && !has_zig_file(&project_path.join("src")).await {
+                return missing("a .zig file");
+            }
+        }
+        BuildSystem::Pydoc => {
+            if pydoc::find_modules(project_path).await.is_empty() {
+                return missing(&format!("a .py file in {}/", pydoc::SOURCE_DIR));
+            }
+        }
+        BuildSystem::Vuepress | BuildSystem::Docusaurus => {
+            let configs = match build_system {
+                BuildSystem::Vuepress => VUEPRESS_CONFIGS,
+                _ => DOCUSAURUS_CONFIGS,
+            };
+            if !has_any(project_path, &configs).await {
+                // a site of the other framework is most likely a wrong `build_system`
+                return match detect_js_site(project_path).await {
+                    Some(detected) => missing(&format!(
+                        "{} (found a {} site, set build_system = \"{}\")",
+                        configs.join(" or "),
+                        detected.name(),
+                        detected.name()
+                    )),
+                    None => missing(&configs.join(" or ")),
+                };
+            }
+        }
+        // .NET and static projects aren't built, custom commands can need anything
+        BuildSystem::DotNet | BuildSystem::Custom | BuildSystem::None => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check() {
+        let project = TempDir::new().unwrap();
+        let path = project.path();
+
+        for build_system in [BuildSystem::Cargo, BuildSystem::Gradle, BuildSystem::Zig] {
+            let error = check(&build_system, path).await.unwrap_err();
+            assert!(
+                matches!(&error, AppError::MissingBuildFile { path: p, .. } if p == path),
+                "{:?}",
+                error
+            );
+        }
+        assert!(check(&BuildSystem::Custom, path).await.is_ok());
+        assert_eq!(detect_js_site(path).await, None);
+
+        std::fs::write(path.join("Cargo.toml"), "").unwrap();
+        std::fs::write(path.join("build.gradle.kts"), "").unwrap();
+        std::fs::create_dir(path.join("src")).unwrap();
+        std::fs::write(path.join("src/lib.zig"), "").unwrap();
+        for build_system in [BuildSystem::Cargo, BuildSystem::Gradle, BuildSystem::Zig] {
+            assert!(check(&build_system, path).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_js_sites() {
+        let project = TempDir::new().unwrap();
+        let path = project.path();
+
+        std::fs::write(path.join("docusaurus.config.js"), "").unwrap();
+        assert_eq!(detect_js_site(path).await, Some(BuildSystem::Docusaurus));
+        assert!(check(&BuildSystem::Docusaurus, path).await.is_ok());
+        let error = check(&BuildSystem::Vuepress, path).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("found a docusaurus site, set build_system = \"docusaurus\""),
+            "{}",
+            error
+        );
+
+        std::fs::remove_file(path.join("docusaurus.config.js")).unwrap();
+        std::fs::create_dir_all(path.join("docs/.vuepress")).unwrap();
+        std::fs::write(path.join("docs/.vuepress/config.ts"), "").unwrap();
+        assert_eq!(detect_js_site(path).await, Some(BuildSystem::Vuepress));
+        assert!(check(&BuildSystem::Vuepress, path).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_error_names_expected_file() {
+        let project = TempDir::new().unwrap();
+        let error = check(&BuildSystem::Cargo, project.path())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("expected Cargo.toml in"));
+    }
+}
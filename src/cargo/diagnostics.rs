@@ -0,0 +1,27 @@
+/// Extracts up to `max` compiler errors (lines starting with `error[`) from `cargo doc` stderr.
+///
+/// When the error is followed by its ` --> file:line:col` location, the location is appended,
+/// e.g. ``error[E0425]: cannot find value `x` in this scope (src/lib.rs:3:5)``.
+pub fn parse_errors(stderr: &str, max: usize) -> Vec<String> {
+    let mut lines = stderr.lines().peekable();
+    let mut errors = Vec::new();
+
+    while errors.len() < max {
+        let Some(line) = lines.next() else {
+            break;
+        };
+        if !line.starts_with("error[") {
+            continue;
+        }
+
+        let location = lines
+            .peek()
+            .and_then(|next| next.trim_start().strip_prefix("--> "));
+        match location {
+            Some(location) => errors.push(format!("{} ({})", line, location.trim())),
+            None => errors.push(line.to_string()),
+        }
+    }
+
+    errors
+}
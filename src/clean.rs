@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    config::{BuildSystem, Project},
+    error::{AppError, AppResult},
+    git,
+    history::{dir_size, project_logs_dir},
+    BuildCommand,
+};
+
+/// A directory `lichen clean` deletes
+#[derive(Debug, Serialize)]
+pub struct Removal {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Everything `lichen clean` does for one project
+#[derive(Debug, Serialize)]
+pub struct CleanPlan<'a> {
+    pub slug: &'a str,
+    pub remove: Vec<Removal>,
+    /// The build system's own clean, only with `--deep`
+    pub commands: Vec<BuildCommand>,
+    /// Build output directory the commands shrink, measured to report freed space
+    #[serde(skip)]
+    output_dir: Option<PathBuf>,
+}
+
+/// Canonicalizes `path` and makes sure it lies strictly inside `root`
+async fn ensure_inside(path: &Path, root: &Path) -> AppResult<PathBuf> {
+    let root = fs::canonicalize(root).await?;
+    let resolved = fs::canonicalize(path).await?;
+    if resolved == root || !resolved.starts_with(&root) {
+        return Err(AppError::Config(format!(
+            "refusing to delete {}, it resolves outside {}",
+            path.display(),
+            root.display()
+        )));
+    }
+    Ok(resolved)
+}
+
+/// The build system's own clean command, and the directory it cleans up
+fn deep_clean(project: &Project, project_path: &Path) -> Option<(BuildCommand, PathBuf)> {
+    match project.config.build_system {
+        BuildSystem::Cargo => Some((
+            BuildCommand::new("cargo", &["clean", "--doc"], project_path),
+            project_path.join("target"),
+        )),
+        BuildSystem::Gradle => {
+            let gradlew = project_path.join("gradlew");
+            let program = if gradlew.exists() {
+                gradlew
+            } else {
+                PathBuf::from("gradle")
+            };
+            Some((
+                BuildCommand::new(program, &["clean"], project_path),
+                project_path.join("build"),
+            ))
+        }
+        // also drops the `.docusaurus` cache
+        BuildSystem::Docusaurus => Some((
+            BuildCommand::new("npx", &["docusaurus", "clear"], project_path),
+            project_path.join("build"),
+        )),
+        BuildSystem::Zig
+        | BuildSystem::DotNet
+        | BuildSystem::Pydoc
+        | BuildSystem::Vuepress
+        | BuildSystem::Custom
+        | BuildSystem::None => None,
+    }
+}
+
+/// Whether the docs directory can be deleted. Build systems with an output directory of
+/// their own recreate it on the next build, but the others build into `docs`, where the
+/// docs' sources often live too, so it's only deleted if git tracks nothing in it.
+async fn docs_removable(project: &Project) -> AppResult<bool> {
+    if matches!(
+        project.config.build_system,
+        BuildSystem::Cargo | BuildSystem::Gradle | BuildSystem::Vuepress | BuildSystem::Docusaurus
+    ) {
+        return Ok(true);
+    }
+    if !fs::try_exists(&project.docs_path).await? {
+        return Ok(false);
+    }
+
+    let reason = match git::tracks_files_in(&project.docs_path).await? {
+        Some(false) => return Ok(true),
+        Some(true) => "git tracks files in it",
+        None => "it isn't in a git working copy, so it may hold sources",
+    };
+    warn!("Keeping {}, {}", project.docs_path.display(), reason);
+    Ok(false)
+}
+
+pub async fn plan<'a>(
+    project: &'a Project,
+    libs_path: &Path,
+    deep: bool,
+) -> AppResult<CleanPlan<'a>> {
+    let logs_dir = project_logs_dir(libs_path, &project.url_path);
+    let mut paths = vec![&logs_dir];
+    // static docs can't be rebuilt once removed
+    if !project.is_static() && docs_removable(project).await? {
+        paths.insert(0, &project.docs_path);
+    }
+
+    let mut remove = Vec::new();
+    for path in paths {
+        if !fs::try_exists(path).await? {
+            continue;
+        }
+        let path = ensure_inside(path, libs_path).await?;
+        let size_bytes = dir_size(&path).await?;
+        remove.push(Removal { path, size_bytes });
+    }
+
+    let project_path = libs_path.join(&project.config.path);
+    let deep_clean = if deep {
+        deep_clean(project, &project_path)
+    } else {
+        None
+    };
+    let (commands, output_dir) = match deep_clean {
+        Some((command, output_dir)) => (vec![command], Some(output_dir)),
+        None => (Vec::new(), None),
+    };
+
+    Ok(CleanPlan {
+        slug: &project.url_path,
+        remove,
+        commands,
+        output_dir,
+    })
+}
+
+async fn size_if_exists(path: &Path) -> u64 {
+    dir_size(path).await.unwrap_or_default()
+}
+
+/// Carries out `plan`, returning how many bytes were freed
+pub async fn execute(plan: &CleanPlan<'_>) -> AppResult<u64> {
+    let mut freed = 0;
+
+    for removal in &plan.remove {
+        info!("Removing {}", removal.path.display());
+        fs::remove_dir_all(&removal.path).await?;
+        freed += removal.size_bytes;
+    }
+
+    if plan.commands.is_empty() {
+        return Ok(freed);
+    }
+
+    let before = match &plan.output_dir {
+        Some(dir) => size_if_exists(dir).await,
+        None => 0,
+    };
+    for command in &plan.commands {
+        info!("Running {}", command);
+        let status = tokio::process::Command::new(&command.program)
+            .args(&command.args)
+            .current_dir(&command.cwd)
+            .status()
+            .await?;
+        if !status.success() {
+            warn!("{} exited with {}", command, status);
+            return Err(AppError::BuildFailed {
+                status: status.to_string(),
+                errors: Vec::new(),
+            });
+        }
+    }
+    let after = match &plan.output_dir {
+        Some(dir) => size_if_exists(dir).await,
+        None => 0,
+    };
+
+    Ok(freed + before.saturating_sub(after))
+}
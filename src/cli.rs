@@ -0,0 +1,544 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use clap::{
+    builder::PossibleValuesParser, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command,
+};
+use clap_complete::Shell;
+use serde::Serialize;
+
+use crate::{
+    api::LOCAL_SOURCE,
+    clean::CleanPlan,
+    config::{BuildSystem, Config, ConfigFormat, Project},
+    error::AppResult,
+    git::repo_web_url,
+    pipeline::ProjectOutcome,
+    rewrite::Rewrite,
+    utils::format_size,
+    BuildCommand,
+};
+
+/// Parsed command line
+#[derive(Debug)]
+pub struct Cli {
+    pub command: CliCommand,
+    /// Number of `-v` flags
+    pub verbose: u8,
+    pub quiet: bool,
+    /// Config file given with `--config`
+    pub config: Option<PathBuf>,
+    /// Syntax of the config file given with `--config-format`, otherwise from its extension
+    pub config_format: Option<ConfigFormat>,
+    /// Detach from the terminal before serving
+    pub daemon: bool,
+    pub pid_file: Option<PathBuf>,
+    /// Exit instead of serving if the startup update of any project fails
+    pub strict_startup: bool,
+}
+
+/// What the binary was asked to do
+#[derive(Debug)]
+pub enum CliCommand {
+    /// Run the documentation server (the default)
+    Serve,
+    /// Build the selected projects once and exit, optionally updating them first
+    Run {
+        update: bool,
+        targets: Targets,
+        /// Only print what would run
+        dry_run: bool,
+        json: bool,
+    },
+    /// Print the resolved configuration of every project
+    List { json: bool },
+    /// Delete the selected projects' docs and logs, and with `deep` their build output
+    Clean {
+        targets: Targets,
+        deep: bool,
+        dry_run: bool,
+        json: bool,
+    },
+    /// Time builds of the selected projects from their working copies, `repeat` times each
+    Bench {
+        targets: Targets,
+        repeat: u32,
+        json: bool,
+    },
+    /// Print a completion script for `shell`
+    Completions { shell: Shell },
+    /// Print the man page
+    Man,
+}
+
+#[derive(Debug)]
+pub enum Targets {
+    All,
+    Slugs(Vec<String>),
+}
+
+const RUN_DRY_RUN_HELP: &str =
+    "Print the git operations and commands that would run, without running them";
+
+fn json_arg() -> Arg {
+    Arg::new("json")
+        .long("json")
+        .action(ArgAction::SetTrue)
+        .help("Print machine-readable JSON")
+}
+
+fn oneshot_command(name: &'static str, about: &'static str, dry_run: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help(dry_run),
+        )
+        .arg(json_arg().requires("dry-run"))
+        .arg(
+            Arg::new("slugs")
+                .value_name("SLUG")
+                .num_args(1..)
+                .help("Projects to process, by URL slug"),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .action(ArgAction::SetTrue)
+                .help("Process every configured project"),
+        )
+        .group(
+            ArgGroup::new("targets")
+                .args(["slugs", "all"])
+                .required(true),
+        )
+}
+
+pub fn command() -> Command {
+    Command::new("lichen")
+        .about("Hosts documentation for multiple projects")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .global(true)
+                .help("Log more: -v for debug, -vv for trace"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("verbose")
+                .help("Only log warnings and errors"),
+        )
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .global(true)
+                .help("Config file to read [default: $LICHEN_CONFIG, then ./config.toml]"),
+        )
+        .arg(
+            Arg::new("config-format")
+                .long("config-format")
+                .value_name("FORMAT")
+                .value_parser(["toml", "yaml"])
+                .global(true)
+                .help("Syntax of the config file [default: yaml for .yaml/.yml files, else toml]"),
+        )
+        .arg(
+            Arg::new("daemon")
+                .long("daemon")
+                .action(ArgAction::SetTrue)
+                .help("Run the server in the background, logging to log_file"),
+        )
+        .arg(
+            Arg::new("pid-file")
+                .long("pid-file")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Write the server's pid to PATH once it is listening"),
+        )
+        .arg(
+            Arg::new("strict-startup")
+                .long("strict-startup")
+                .action(ArgAction::SetTrue)
+                .help("Exit with an error if any project fails to update or build on startup"),
+        )
+        .subcommand(oneshot_command(
+            "build",
+            "Build docs from the current working copies and exit",
+            RUN_DRY_RUN_HELP,
+        ))
+        .subcommand(oneshot_command(
+            "update",
+            "Update projects from their repositories, build their docs and exit",
+            RUN_DRY_RUN_HELP,
+        ))
+        .subcommand(
+            oneshot_command(
+                "clean",
+                "Delete generated docs and logs so the next build starts from scratch",
+                "Print what would be deleted, with sizes, without deleting anything",
+            )
+            .arg(
+                Arg::new("deep")
+                    .long("deep")
+                    .action(ArgAction::SetTrue)
+                    .help(
+                    "Also run the build system's own clean (`cargo clean --doc`, `gradle clean`)",
+                ),
+            ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("Print each project's configuration after defaults are applied")
+                .arg(json_arg()),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Time doc builds from the working copies, slowest first")
+                .arg(
+                    Arg::new("project")
+                        .long("project")
+                        .value_name("SLUG")
+                        .action(ArgAction::Append)
+                        .help("Only benchmark this project, may be repeated [default: all]"),
+                )
+                .arg(
+                    Arg::new("repeat")
+                        .long("repeat")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .default_value("1")
+                        .help("Build each project N times and report the mean"),
+                )
+                .arg(json_arg()),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(value_parser!(Shell))
+                        .help("Shell to generate completions for"),
+                ),
+        )
+        .subcommand(Command::new("man").about("Print the man page in roff format"))
+}
+
+fn targets(matches: &ArgMatches) -> Targets {
+    if matches.get_flag("all") {
+        Targets::All
+    } else {
+        Targets::Slugs(
+            matches
+                .get_many::<String>("slugs")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+pub fn parse() -> Cli {
+    let matches = command().get_matches();
+    let command = match matches.subcommand() {
+        Some(("build", args)) => CliCommand::Run {
+            update: false,
+            targets: targets(args),
+            dry_run: args.get_flag("dry-run"),
+            json: args.get_flag("json"),
+        },
+        Some(("update", args)) => CliCommand::Run {
+            update: true,
+            targets: targets(args),
+            dry_run: args.get_flag("dry-run"),
+            json: args.get_flag("json"),
+        },
+        Some(("clean", args)) => CliCommand::Clean {
+            targets: targets(args),
+            deep: args.get_flag("deep"),
+            dry_run: args.get_flag("dry-run"),
+            json: args.get_flag("json"),
+        },
+        Some(("bench", args)) => CliCommand::Bench {
+            targets: match args.get_many::<String>("project") {
+                Some(slugs) => Targets::Slugs(slugs.cloned().collect()),
+                None => Targets::All,
+            },
+            repeat: *args.get_one::<u32>("repeat").expect("repeat has a default"),
+            json: args.get_flag("json"),
+        },
+        Some(("list", args)) => CliCommand::List {
+            json: args.get_flag("json"),
+        },
+        Some(("completions", args)) => CliCommand::Completions {
+            shell: *args.get_one::<Shell>("shell").expect("shell is required"),
+        },
+        Some(("man", _)) => CliCommand::Man,
+        _ => CliCommand::Serve,
+    };
+
+    Cli {
+        command,
+        verbose: matches.get_count("verbose"),
+        quiet: matches.get_flag("quiet"),
+        config: matches.get_one::<PathBuf>("config").cloned(),
+        config_format: matches.get_one::<String>("config-format").map(|format| {
+            match format.as_str() {
+                "yaml" => ConfigFormat::Yaml,
+                _ => ConfigFormat::Toml,
+            }
+        }),
+        daemon: matches.get_flag("daemon"),
+        pid_file: matches.get_one::<PathBuf>("pid-file").cloned(),
+        strict_startup: matches.get_flag("strict-startup"),
+    }
+}
+
+/// Prints the completion script for `shell`, completing `build`/`update`/`clean` arguments and
+/// `bench --project` with `slugs` (empty when the config couldn't be read)
+pub fn print_completions(shell: Shell, slugs: &[String]) {
+    let mut command = command();
+    if !slugs.is_empty() {
+        for name in ["build", "update", "clean"] {
+            command = command.mut_subcommand(name, |sub| {
+                sub.mut_arg("slugs", |arg| {
+                    arg.value_parser(PossibleValuesParser::new(slugs.iter().cloned()))
+                })
+            });
+        }
+        command = command.mut_subcommand("bench", |sub| {
+            sub.mut_arg("project", |arg| {
+                arg.value_parser(PossibleValuesParser::new(slugs.iter().cloned()))
+            })
+        });
+    }
+
+    clap_complete::generate(shell, &mut command, "lichen", &mut io::stdout());
+}
+
+pub fn print_man() -> AppResult<()> {
+    clap_mangen::Man::new(command()).render(&mut io::stdout())?;
+    Ok(())
+}
+
+pub fn print_clean_plans(plans: &[CleanPlan], json: bool) -> AppResult<()> {
+    if json {
+        return print_json(&plans);
+    }
+
+    for plan in plans {
+        println!("{}", plan.slug);
+        for removal in &plan.remove {
+            println!(
+                "  remove: {} ({})",
+                removal.path.display(),
+                format_size(removal.size_bytes)
+            );
+        }
+        for command in &plan.commands {
+            println!("  run: {}", command);
+        }
+        if plan.remove.is_empty() && plan.commands.is_empty() {
+            println!("  nothing to clean");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints one row per project with the status of each step
+pub fn print_summary(outcomes: &[(&str, ProjectOutcome)]) {
+    for line in summary_lines(outcomes) {
+        println!("{}", line);
+    }
+}
+
+/// Aligned table of [`print_summary`], header first
+pub fn summary_lines(outcomes: &[(&str, ProjectOutcome)]) -> Vec<String> {
+    let width = outcomes
+        .iter()
+        .map(|(slug, _)| slug.len())
+        .chain(std::iter::once("PROJECT".len()))
+        .max()
+        .unwrap_or_default();
+    let update_width = outcomes
+        .iter()
+        .map(|(_, o)| o.update.to_string().len())
+        .chain(std::iter::once("UPDATE".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!(
+        "{:<width$}  {:<update_width$}  BUILD",
+        "PROJECT", "UPDATE"
+    )];
+    for (slug, outcome) in outcomes {
+        lines.push(format!(
+            "{:<width$}  {:<update_width$}  {}",
+            slug, outcome.update, outcome.build
+        ));
+    }
+    lines
+}
+
+/// Build times `bench` measured for one project
+#[derive(Debug, Serialize)]
+pub struct BenchResult<'a> {
+    pub slug: &'a str,
+    pub build_system: &'static str,
+    /// Builds that succeeded; a failed build ends the project's repeats
+    pub runs: u32,
+    pub mean_ms: u64,
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub error: Option<String>,
+}
+
+pub fn print_bench(results: &[BenchResult], json: bool) -> AppResult<()> {
+    if json {
+        return print_json(&results);
+    }
+
+    let width = results
+        .iter()
+        .map(|r| r.slug.len())
+        .chain(std::iter::once("PROJECT".len()))
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:<width$}  {:<8}  {:>10}  {:>10}  {:>10}  RUNS",
+        "PROJECT", "SYSTEM", "MEAN", "MIN", "MAX"
+    );
+    for result in results {
+        let times = if result.runs > 0 {
+            format!(
+                "{:>10}  {:>10}  {:>10}",
+                format_millis(result.mean_ms),
+                format_millis(result.min_ms),
+                format_millis(result.max_ms)
+            )
+        } else {
+            format!("{:>10}  {:>10}  {:>10}", "-", "-", "-")
+        };
+        print!(
+            "{:<width$}  {:<8}  {}  {}",
+            result.slug, result.build_system, times, result.runs
+        );
+        match &result.error {
+            Some(error) => println!("  failed: {}", error),
+            None => println!(),
+        }
+    }
+
+    Ok(())
+}
+
+/// `1234` as `1.234s`
+fn format_millis(ms: u64) -> String {
+    format!("{}.{:03}s", ms / 1000, ms % 1000)
+}
+
+fn print_json(value: &impl Serialize) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(value).map_err(std::io::Error::from)?;
+    println!("{}", json);
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct ResolvedProject<'a> {
+    slug: &'a str,
+    path: PathBuf,
+    docs_path: &'a Path,
+    build_system: &'a BuildSystem,
+    /// Followed when origin's default branch can't be detected
+    fallback_branches: &'a [String],
+    repo: Option<&'a str>,
+    repo_web_url: Option<String>,
+    rewrites: &'a [Rewrite],
+}
+
+/// Prints `projects` as `lichen list` shows them
+pub fn print_projects(projects: &[&Project], config: &Config, json: bool) -> AppResult<()> {
+    let resolved = projects
+        .iter()
+        .map(|p| ResolvedProject {
+            slug: &p.url_path,
+            path: config.libs_path.join(&p.config.path),
+            docs_path: &p.docs_path,
+            build_system: &p.config.build_system,
+            fallback_branches: &config.git.default_branches,
+            repo: p.config.repo.as_deref(),
+            repo_web_url: p.config.repo.as_deref().and_then(repo_web_url),
+            rewrites: &p.rewrites,
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        return print_json(&resolved);
+    }
+
+    for project in resolved {
+        println!("{}", project.slug);
+        println!("  path:         {}", project.path.display());
+        println!("  docs_path:    {}", project.docs_path.display());
+        println!("  build_system: {}", project.build_system.name());
+        println!(
+            "  branches:     origin's default, else {}",
+            project.fallback_branches.join(", ")
+        );
+        match (project.repo, project.repo_web_url) {
+            (Some(repo), Some(web)) => println!("  repo:         {} ({})", repo, web),
+            (Some(repo), None) => println!("  repo:         {}", repo),
+            (None, _) => println!("  repo:         {}", LOCAL_SOURCE),
+        }
+        for rewrite in project.rewrites {
+            println!("  rewrite:      {}", rewrite);
+        }
+    }
+
+    Ok(())
+}
+
+/// What `build`/`update --dry-run` would do for one project
+#[derive(Debug, Serialize)]
+pub struct DryRunPlan<'a> {
+    pub slug: &'a str,
+    pub git: Vec<String>,
+    pub commands: Vec<BuildCommand>,
+}
+
+pub fn print_plans(plans: &[DryRunPlan], json: bool) -> AppResult<()> {
+    if json {
+        return print_json(&plans);
+    }
+
+    for plan in plans {
+        println!("{}", plan.slug);
+        for operation in &plan.git {
+            println!("  git: {}", operation);
+        }
+        for command in &plan.commands {
+            println!("  run: {}", command);
+        }
+        if plan.git.is_empty() && plan.commands.is_empty() {
+            println!("  nothing to do");
+        }
+    }
+
+    Ok(())
+}
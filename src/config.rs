@@ -0,0 +1,1783 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use crate::{
+    badges::Badge,
+    error::{AppError, AppResult},
+    filter::{IgnoreRules, ServeFilter, DEFAULT_IGNORE, DEFAULT_NEVER_SERVE},
+    git::{url_has_credentials, validate_proxy, GitConfig, ProjectGitConfig, RepoAuth},
+    hooks::WebhookConfig,
+    limits::LimitsConfig,
+    maintenance::MaintenanceConfig,
+    proxy::DocsProxy,
+    pydoc,
+    rewrite::{self, Rewrite, RewriteConfig},
+    sandbox::SandboxConfig,
+    tls::TlsConfig,
+    utils::{is_contained_path, sanitize_path},
+    zig::toolchain::ZigVersion,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub libs_path: PathBuf,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub update_on_start: bool,
+    /// Default log level, overridden by `LICHEN_LOG` and `-v`/`-q`
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// File logs are appended to instead of stdout
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+    /// Connection limits of the HTTP server
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Branch selection when updating repositories
+    #[serde(default)]
+    pub git: GitConfig,
+    /// Repository housekeeping
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// Isolation of build commands
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+    /// Serve over HTTPS instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Separate listener for the endpoints that change things, kept off `port`
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
+    /// Concurrency of archive generation and similar work while serving
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// CSS color of the generated favicon
+    #[serde(default = "default_favicon_color")]
+    pub favicon_color: String,
+    /// Hosts whose badge images are fetched through `/badge-proxy`, none if empty
+    #[serde(default)]
+    pub badge_proxy_hosts: Vec<String>,
+    /// Build systems projects may use, all of them if unset
+    #[serde(default)]
+    pub allowed_build_systems: Option<Vec<BuildSystem>>,
+    /// Order of the projects on the index page
+    #[serde(default, alias = "project_sort")]
+    pub index_sort: IndexSort,
+    /// Redirect paths naming no project to the one project with a very similar slug, if any
+    #[serde(default)]
+    pub redirect_unknown_slugs: bool,
+    /// `.gitignore`-style rules of files left out of docs sizes and archives, for every project
+    #[serde(default = "default_ignore")]
+    pub ignore: Vec<String>,
+    pub projects: Vec<ProjectConfig>,
+}
+
+/// How the index page orders projects. Every order is stable, falling back to the name.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexSort {
+    /// Alphabetical on the display name
+    #[default]
+    Name,
+    /// Most recently built first, never built ones last
+    LastBuilt,
+    /// Grouped by build system
+    BuildSystem,
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    /// How long idle keep-alive connections are kept open, 0 disables keep-alive
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u64,
+    /// How long a client has to send a request's headers before getting a 408, 0 disables the
+    /// limit
+    #[serde(default = "default_client_request_timeout_secs")]
+    pub client_request_timeout_secs: u64,
+    /// Concurrent connections per worker
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Concurrent TLS handshakes per worker, only relevant with `[tls]`
+    #[serde(default)]
+    pub max_connection_rate: Option<usize>,
+    /// How long requests are still accepted after a SIGTERM, before the server stops
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive_secs: default_keep_alive_secs(),
+            client_request_timeout_secs: default_client_request_timeout_secs(),
+            max_connections: None,
+            max_connection_rate: None,
+            shutdown_grace_period_secs: default_shutdown_grace_period_secs(),
+        }
+    }
+}
+
+fn default_keep_alive_secs() -> u64 {
+    5
+}
+
+fn default_client_request_timeout_secs() -> u64 {
+    5
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminConfig {
+    /// Address the admin listener binds to
+    #[serde(default = "default_admin_bind")]
+    pub bind: String,
+    pub port: u16,
+}
+
+fn default_admin_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_favicon_color() -> String {
+    "#0366d6".to_string()
+}
+
+/// One of the docs tasks of a Gradle project with `targets`
+#[derive(Debug, Deserialize, Clone)]
+pub struct GradleTarget {
+    pub task: String,
+    /// Where the task writes the docs, relative to the project directory
+    pub path: PathBuf,
+    /// Appended to the project's slug as `/{slug}-{suffix}/`; the target without one is
+    /// served at `/{slug}/`
+    #[serde(default)]
+    pub slug_suffix: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProjectConfig {
+    pub path: String,
+    /// Name shown on the index and in the API instead of `path`
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Former slugs that redirect permanently to this project
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub repo: Option<String>,
+    /// Link to the project's sources on its forge, with `{commit}` for the built commit and
+    /// `{path}`/`{line}` for a file and line, e.g.
+    /// `https://github.com/org/repo/blob/{commit}/{path}#L{line}`
+    #[serde(default)]
+    pub source_url_template: Option<String>,
+    /// Credentials for `repo`, kept out of the URL itself
+    #[serde(default)]
+    pub auth: Option<RepoAuth>,
+    #[serde(default)]
+    pub git: ProjectGitConfig,
+    /// Branches whose docs are each built and served at `/{slug}/{branch}/`
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Branch `/{slug}/` leads to, the first of `branches` if unset
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    pub build_system: BuildSystem,
+    /// Directory of static docs served as they are, relative to `libs_path`, only with
+    /// `build_system = "none"`
+    #[serde(default)]
+    pub docs_path: Option<PathBuf>,
+    /// Serve each version-named subdirectory of `docs_path` with a version picker at `/{slug}/`
+    #[serde(default)]
+    pub versions_from_dirs: bool,
+    #[serde(default)]
+    pub build_command: Option<String>,
+    /// Commands of a custom build, run in order until one fails
+    #[serde(default)]
+    pub build_commands: Option<Vec<String>>,
+    /// Run custom commands through `/bin/sh -c` (`cmd.exe /C` on Windows) instead of splitting
+    /// them on whitespace
+    #[serde(default)]
+    pub spawn_shell: Option<bool>,
+    /// Shell script run before the build, relative to the project directory
+    #[serde(default)]
+    pub pre_build_script: Option<PathBuf>,
+    /// How long `pre_build_script` may run before its process group is killed
+    #[serde(default = "default_pre_build_timeout_secs")]
+    pub pre_build_timeout_secs: u64,
+    /// Gradle task generating the docs, e.g. `dokkaHtml` (default: `javadoc`)
+    #[serde(default)]
+    pub gradle_task: Option<String>,
+    /// Don't run `clean` before the Gradle task
+    #[serde(default)]
+    pub skip_clean: Option<bool>,
+    /// Several Gradle docs tasks run in one invocation, each served at a slug of its own
+    #[serde(default)]
+    pub targets: Vec<GradleTarget>,
+    /// Oldest `zig` the project's docs may be built with, e.g. `0.14.0`
+    #[serde(default)]
+    pub min_zig_version: Option<String>,
+    /// Python interpreter running `pydoc`, `python3` if unset
+    #[serde(default)]
+    pub python_path: Option<String>,
+    /// Single file (e.g. `manual.pdf`) served directly at `/{slug}/`, relative to the docs path
+    #[serde(default)]
+    pub entry_file: Option<String>,
+    /// Entry point of the generated docs, relative to the docs path
+    #[serde(default = "default_index_file", alias = "custom_index_file")]
+    pub index_file: String,
+    /// Look for the docs in common output directories after each build, instead of where
+    /// `build_system` puts them
+    #[serde(default)]
+    pub detect_docs_path: bool,
+    /// Where each build's report is written, relative to the project directory (default:
+    /// `.lichen-report.json` next to the docs directory)
+    #[serde(default)]
+    pub build_report_path: Option<PathBuf>,
+    /// Look for links to missing files in the docs after each build
+    #[serde(default)]
+    pub check_links: bool,
+    /// Write `.br` and `.gz` variants of text files after each build, served to clients
+    /// accepting them
+    #[serde(default)]
+    pub precompress: bool,
+    /// Files compressed per second by `precompress`, so it doesn't compete with builds
+    #[serde(default = "default_precompress_files_per_sec")]
+    pub precompress_files_per_sec: u32,
+    /// Directory the served docs are copied to after each successful build or upload,
+    /// replacing its previous contents
+    #[serde(default)]
+    pub copy_to: Option<PathBuf>,
+    /// Fail builds whose docs directory is missing, empty or lacks the index file
+    #[serde(default)]
+    pub fail_on_empty_docs: bool,
+    /// How many compiler errors of a failed `cargo doc` are kept for the status API
+    #[serde(default = "default_max_build_errors")]
+    pub max_build_errors: usize,
+    /// Endpoints notified whenever a build of the project completes
+    #[serde(default)]
+    pub build_hooks: Vec<WebhookConfig>,
+    /// Globs of the only files served from the docs directory, everything if empty
+    #[serde(default)]
+    pub serve_only: Vec<String>,
+    /// Globs of files never served, even if `serve_only` matches them
+    #[serde(default = "default_never_serve")]
+    pub never_serve: Vec<String>,
+    /// Rules added to the global `ignore` ones for this project
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// External site whose docs are served under the project's slug instead of local files
+    #[serde(default)]
+    pub proxy_docs_url: Option<String>,
+    /// How long a proxied request may take, in seconds
+    #[serde(default = "default_proxy_timeout_secs")]
+    pub proxy_timeout_secs: u64,
+    /// Rules rewriting paths inside the project before they're served, the first match wins
+    #[serde(default)]
+    pub rewrites: Vec<RewriteConfig>,
+    /// Badges shown next to the project on the index
+    #[serde(default)]
+    pub badges: Vec<Badge>,
+    /// Also show badges derived from the project's sources, e.g. its crates.io version
+    #[serde(default)]
+    pub auto_badges: bool,
+}
+
+fn default_index_file() -> String {
+    "index.html".to_string()
+}
+
+fn default_max_build_errors() -> usize {
+    5
+}
+
+fn default_pre_build_timeout_secs() -> u64 {
+    600
+}
+
+fn default_precompress_files_per_sec() -> u32 {
+    100
+}
+
+fn default_proxy_timeout_secs() -> u64 {
+    30
+}
+
+/// Where Gradle writes the output of a docs task, using Dokka's defaults for its tasks
+fn gradle_docs_dir(task: Option<&str>) -> &'static str {
+    match task {
+        Some("dokkaHtml") => "build/dokka/html",
+        Some("dokkaGfm") => "build/dokka/gfm",
+        Some("dokkaJavadoc") => "build/dokka/javadoc",
+        Some("dokkaJekyll") => "build/dokka/jekyll",
+        _ => "build/docs/javadoc",
+    }
+}
+
+fn default_never_serve() -> Vec<String> {
+    DEFAULT_NEVER_SERVE.iter().map(|p| p.to_string()).collect()
+}
+
+fn default_ignore() -> Vec<String> {
+    DEFAULT_IGNORE.iter().map(|p| p.to_string()).collect()
+}
+
+impl ProjectConfig {
+    /// Interpreter `pydoc` projects are documented with
+    pub fn python(&self) -> &str {
+        self.python_path.as_deref().unwrap_or(pydoc::DEFAULT_PYTHON)
+    }
+
+    /// Parsed `min_zig_version`
+    pub fn min_zig_version(&self) -> AppResult<Option<ZigVersion>> {
+        self.min_zig_version
+            .as_deref()
+            .map(|version| {
+                ZigVersion::parse(version).ok_or_else(|| {
+                    AppError::Config(format!(
+                        "min_zig_version '{}' of project '{}' isn't a version like 0.14.0",
+                        version, self.path
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Commands of a custom build: `build_commands`, or the deprecated single `build_command`
+    pub fn custom_commands(&self) -> Vec<&str> {
+        match (&self.build_commands, &self.build_command) {
+            (Some(commands), _) => commands.iter().map(String::as_str).collect(),
+            (None, Some(command)) => vec![command.as_str()],
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// Arguments passed to Gradle: `clean` unless skipped, then the docs task, or the tasks of
+    /// all `targets`
+    pub fn gradle_args(&self) -> Vec<&str> {
+        let clean = (!self.skip_clean.unwrap_or(false)).then_some("clean");
+        let tasks = if self.targets.is_empty() {
+            vec![self.gradle_task.as_deref().unwrap_or("javadoc")]
+        } else {
+            self.targets.iter().map(|t| t.task.as_str()).collect()
+        };
+        clean.into_iter().chain(tasks).collect()
+    }
+
+    /// The target of `targets` served at the project's own slug
+    fn main_target(&self) -> Option<&GradleTarget> {
+        self.targets.iter().find(|t| t.slug_suffix.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildSystem {
+    Gradle,
+    Cargo,
+    Zig,
+    DotNet,
+    /// Module pages written by `python -m pydoc -w`
+    Pydoc,
+    /// VuePress site built with `npm run build` from `docs/`
+    Vuepress,
+    /// Docusaurus site built with `npx docusaurus build`
+    Docusaurus,
+    Custom,
+    /// Docs that are already there, served without updating or building anything
+    None,
+}
+
+impl BuildSystem {
+    /// Name as written in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildSystem::Gradle => "gradle",
+            BuildSystem::Cargo => "cargo",
+            BuildSystem::Zig => "zig",
+            BuildSystem::DotNet => "dotnet",
+            BuildSystem::Pydoc => "pydoc",
+            BuildSystem::Vuepress => "vuepress",
+            BuildSystem::Docusaurus => "docusaurus",
+            BuildSystem::Custom => "custom",
+            BuildSystem::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub config: ProjectConfig,
+    pub docs_path: PathBuf,
+    pub url_path: String,
+    /// Sanitized [`ProjectConfig::aliases`]
+    pub aliases: Vec<String>,
+    pub serve_filter: ServeFilter,
+    /// Compiled global and project `ignore` rules
+    pub ignore_rules: IgnoreRules,
+    /// Upstream of a project with `proxy_docs_url`
+    pub proxy: Option<DocsProxy>,
+    /// Compiled [`ProjectConfig::rewrites`]
+    pub rewrites: Vec<Rewrite>,
+    /// Which of [`ProjectConfig::branches`] this is, for projects building several
+    pub branch: Option<ProjectBranch>,
+    /// Which of [`ProjectConfig::targets`] this is, for all but the one at the project's slug
+    pub variant: Option<ProjectVariant>,
+}
+
+/// Docs of one of the extra `targets` of a Gradle project, built along with the project
+#[derive(Debug, Clone)]
+pub struct ProjectVariant {
+    /// Sanitized `slug_suffix`
+    pub name: String,
+    pub task: String,
+    /// Slug of the project building it
+    pub parent: String,
+}
+
+/// One of the branches of a project built at several branches
+#[derive(Debug, Clone)]
+pub struct ProjectBranch {
+    pub name: String,
+    /// Slug shared by all the branches, redirecting to the default one
+    pub parent: String,
+    pub is_default: bool,
+}
+
+impl Project {
+    /// Whether the docs are served as they are, with `build_system = "none"`
+    pub fn is_static(&self) -> bool {
+        matches!(self.config.build_system, BuildSystem::None)
+    }
+
+    /// Human-readable name: `display_name`, or the configured path
+    pub fn name(&self) -> &str {
+        self.config
+            .display_name
+            .as_deref()
+            .unwrap_or(&self.config.path)
+    }
+
+    /// Where requests for the project root end up. Nested index files are redirected to
+    /// directly, so relative links inside them keep resolving.
+    pub fn index_url(&self) -> String {
+        match self.config.index_file.rsplit_once('/') {
+            Some(_) => format!("/{}/{}", self.url_path, self.config.index_file),
+            None => format!("/{}/", self.url_path),
+        }
+    }
+
+    /// Falls back to `index.html` if the docs have one but not the configured `index_file`,
+    /// e.g. after a generator update renamed its entry point
+    pub fn resolve_index_file(&mut self) {
+        let default = default_index_file();
+        if self.config.index_file == default
+            || self.docs_path.join(&self.config.index_file).is_file()
+            || !self.docs_path.join(&default).is_file()
+        {
+            return;
+        }
+        warn!(
+            "index_file {} of project '{}' isn't in {}, serving {} instead",
+            self.config.index_file,
+            self.config.path,
+            self.docs_path.display(),
+            default
+        );
+        self.config.index_file = default;
+    }
+
+    /// File name looked up when a directory of the docs is requested
+    pub fn index_file_name(&self) -> &str {
+        self.config
+            .index_file
+            .rsplit_once('/')
+            .map_or(self.config.index_file.as_str(), |(_, name)| name)
+    }
+}
+
+/// Environment variable naming the config file when `--config` isn't given
+pub const CONFIG_ENV: &str = "LICHEN_CONFIG";
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+fn resolve_config_path(flag: Option<&Path>, env: Option<OsString>) -> PathBuf {
+    match (flag, env) {
+        (Some(flag), _) => flag.to_path_buf(),
+        (None, Some(env)) if !env.is_empty() => PathBuf::from(env),
+        _ => PathBuf::from(DEFAULT_CONFIG_FILE),
+    }
+}
+
+/// The config file to read: `--config`, then `LICHEN_CONFIG`, then `config.toml` in the
+/// current directory
+pub fn config_path(flag: Option<&Path>) -> PathBuf {
+    resolve_config_path(flag, std::env::var_os(CONFIG_ENV))
+}
+
+/// Syntax of a config file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// YAML for `.yaml` and `.yml` files, TOML for anything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// Reads the config file at `path`, in `format` or the one its extension suggests
+pub async fn load_config(path: &Path, format: Option<ConfigFormat>) -> AppResult<Config> {
+    let config_str = fs::read_to_string(path)
+        .await
+        .map_err(|e| AppError::Config(format!("failed to read {}: {}", path.display(), e)))?;
+    match format.unwrap_or_else(|| ConfigFormat::from_path(path)) {
+        ConfigFormat::Toml => parse_config_str(&config_str),
+        ConfigFormat::Yaml => parse_yaml_config_str(&config_str),
+    }
+}
+
+fn parse_config_str(contents: &str) -> AppResult<Config> {
+    apply_default_build_system(contents.parse()?)
+}
+
+/// Parses a YAML config file with the same keys as the TOML one. Keys set to null (`repo: ~`,
+/// or nothing at all) count as left out, since TOML has no null.
+fn parse_yaml_config_str(contents: &str) -> AppResult<Config> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(contents)
+        .map_err(|e| AppError::Config(format!("invalid YAML config: {}", e)))?;
+    strip_nulls(&mut value);
+    let table = serde_yaml::from_value(value)
+        .map_err(|e| AppError::Config(format!("invalid YAML config: {}", e)))?;
+    apply_default_build_system(table)
+}
+
+/// Removes the keys of every mapping in `value` whose value is null
+fn strip_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.retain(|_, value| !value.is_null());
+            mapping.values_mut().for_each(strip_nulls);
+        }
+        serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(strip_nulls),
+        serde_yaml::Value::Tagged(tagged) => strip_nulls(&mut tagged.value),
+        _ => {}
+    }
+}
+
+/// Fills in the `build_system` of projects that don't set one from the top-level
+/// `default_build_system`
+fn apply_default_build_system(mut table: toml::Table) -> AppResult<Config> {
+    if let Some(default) = table.remove("default_build_system")
+        && let Some(projects) = table.get_mut("projects").and_then(|p| p.as_array_mut())
+    {
+        for project in projects.iter_mut().filter_map(|p| p.as_table_mut()) {
+            project
+                .entry("build_system")
+                .or_insert_with(|| default.clone());
+        }
+    }
+    Ok(table.try_into()?)
+}
+
+/// First path segments of lichen's own routes, which no project, alias or branch may take over
+const RESERVED_SLUGS: &[&str] = &["api", "metrics", "favicon.ico", "badge-proxy"];
+
+/// Sanitizes `name` into a URL slug, refusing one that's empty once sanitized (e.g. `"_"`,
+/// which would take over every other route) or that names one of lichen's own routes. `what`
+/// says whose slug it is in errors, e.g. `alias 'old' of project 'lib'`.
+fn route_slug(name: &str, what: &str) -> AppResult<String> {
+    let slug = sanitize_path(name);
+    if slug.is_empty() {
+        return Err(AppError::Config(format!(
+            "{} has no letters or digits",
+            what
+        )));
+    }
+    let raw = name.trim_matches('/').to_ascii_lowercase();
+    if RESERVED_SLUGS.contains(&slug.as_str()) || RESERVED_SLUGS.contains(&raw.as_str()) {
+        return Err(AppError::Config(format!(
+            "{} is reserved for lichen's own routes",
+            what
+        )));
+    }
+    Ok(slug)
+}
+
+/// Claims `slug` for `project`, failing if another project or alias already uses it
+fn claim_slug<'a>(
+    taken: &mut HashMap<String, &'a str>,
+    slug: &str,
+    project: &'a str,
+) -> AppResult<()> {
+    match taken.insert(slug.to_string(), project) {
+        Some(owner) => Err(AppError::Config(format!(
+            "URL slug '{}' of project '{}' is already used by project '{}'",
+            slug, project, owner
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub async fn initialize_projects(config: &Config) -> AppResult<HashMap<String, Project>> {
+    config.sandbox.validate()?;
+    if let Some(proxy) = &config.git.proxy {
+        validate_proxy(proxy)?;
+    }
+
+    let mut projects = HashMap::new();
+    let mut taken = HashMap::new();
+
+    // canonical slugs first, so an alias naming another project is reported as such
+    for project_cfg in &config.projects {
+        let slug = route_slug(
+            &project_cfg.path,
+            &format!("project '{}'", project_cfg.path),
+        )?;
+        claim_slug(&mut taken, &slug, &project_cfg.path)?;
+        for target in project_cfg
+            .targets
+            .iter()
+            .filter(|t| !t.slug_suffix.is_empty())
+        {
+            let variant = route_slug(
+                &variant_slug(&slug, &target.slug_suffix),
+                &format!(
+                    "slug_suffix '{}' of project '{}'",
+                    target.slug_suffix, project_cfg.path
+                ),
+            )?;
+            claim_slug(&mut taken, &variant, &project_cfg.path)?;
+        }
+    }
+
+    for project_cfg in &config.projects {
+        if let Some(allowed) = &config.allowed_build_systems
+            && !allowed.contains(&project_cfg.build_system)
+        {
+            return Err(AppError::Config(format!(
+                "project '{}' uses build_system \"{}\", but allowed_build_systems only permits {}",
+                project_cfg.path,
+                project_cfg.build_system.name(),
+                allowed
+                    .iter()
+                    .map(|b| format!("\"{}\"", b.name()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let url_path = sanitize_path(&project_cfg.path);
+        let aliases = project_cfg
+            .aliases
+            .iter()
+            .map(|alias| {
+                route_slug(
+                    alias,
+                    &format!("alias '{}' of project '{}'", alias, project_cfg.path),
+                )
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        for alias in &aliases {
+            claim_slug(&mut taken, alias, &project_cfg.path)?;
+        }
+
+        if let BuildSystem::None = project_cfg.build_system {
+            validate_static_docs(config, project_cfg).await?;
+        } else if project_cfg.versions_from_dirs {
+            return Err(AppError::Config(format!(
+                "versions_from_dirs of project '{}' needs build_system = \"none\"",
+                project_cfg.path
+            )));
+        } else if project_cfg.docs_path.is_some() {
+            warn!(
+                "docs_path of project '{}' is ignored, it's only used with build_system = \"none\"",
+                project_cfg.path
+            );
+        }
+        let docs_path = docs_path(&config.libs_path, project_cfg);
+
+        if let Some(entry_file) = &project_cfg.entry_file
+            && !is_contained_path(entry_file)
+        {
+            return Err(AppError::Config(format!(
+                "entry_file '{}' of project '{}' must be a relative path inside its docs directory",
+                entry_file, project_cfg.path
+            )));
+        }
+
+        if !is_contained_path(&project_cfg.index_file) {
+            return Err(AppError::Config(format!(
+                "index_file '{}' of project '{}' must be a relative path inside its docs directory",
+                project_cfg.index_file, project_cfg.path
+            )));
+        }
+
+        if let Some(copy_to) = &project_cfg.copy_to
+            && (copy_to.starts_with(&docs_path) || docs_path.starts_with(copy_to))
+        {
+            return Err(AppError::Config(format!(
+                "copy_to '{}' of project '{}' must be outside its docs directory",
+                copy_to.display(),
+                project_cfg.path
+            )));
+        }
+
+        if let Some(template) = &project_cfg.source_url_template
+            && !template.starts_with("https://")
+            && !template.starts_with("http://")
+        {
+            return Err(AppError::Config(format!(
+                "source_url_template '{}' of project '{}' must be an http(s) URL",
+                template, project_cfg.path
+            )));
+        }
+
+        if let Some(path) = project_cfg
+            .git
+            .sparse_paths
+            .iter()
+            .find(|path| !is_contained_path(path))
+        {
+            return Err(AppError::Config(format!(
+                "sparse path '{}' of project '{}' must be a relative path inside the repository",
+                path, project_cfg.path
+            )));
+        }
+
+        if project_cfg.build_command.is_some() {
+            if project_cfg.build_commands.is_some() {
+                return Err(AppError::Config(format!(
+                    "project '{}' sets both build_command and build_commands, keep only build_commands",
+                    project_cfg.path
+                )));
+            }
+            warn!(
+                "build_command of project '{}' is deprecated, use build_commands = [...] instead",
+                project_cfg.path
+            );
+        }
+
+        if let Some(proxy) = &project_cfg.git.proxy {
+            validate_proxy(proxy)?;
+        }
+
+        for hook in &project_cfg.build_hooks {
+            hook.validate()?;
+        }
+        project_cfg.min_zig_version()?;
+
+        let serve_filter = ServeFilter::new(
+            &project_cfg.serve_only,
+            &project_cfg.never_serve,
+            &project_cfg.path,
+        )?;
+        let ignore_rules =
+            IgnoreRules::new(&config.ignore, &project_cfg.ignore, &project_cfg.path)?;
+
+        validate_targets(project_cfg)?;
+        if !matches!(project_cfg.build_system, BuildSystem::Gradle)
+            && (project_cfg.gradle_task.is_some() || project_cfg.skip_clean.is_some())
+        {
+            warn!(
+                "gradle_task and skip_clean of project '{}' are ignored, it isn't built with Gradle",
+                project_cfg.path
+            );
+        }
+
+        let proxy = project_cfg
+            .proxy_docs_url
+            .as_deref()
+            .map(|url| DocsProxy::new(url, Duration::from_secs(project_cfg.proxy_timeout_secs)))
+            .transpose()?;
+        let rewrites = rewrite::compile(&project_cfg.rewrites, &project_cfg.path)?;
+
+        if let Some(repo) = &project_cfg.repo
+            && url_has_credentials(repo)
+        {
+            warn!(
+                "Repository URL of project '{}' embeds credentials, move them to [projects.auth] instead",
+                project_cfg.path
+            );
+        }
+
+        let project = Project {
+            config: project_cfg.clone(),
+            docs_path,
+            url_path: url_path.clone(),
+            aliases,
+            serve_filter,
+            ignore_rules,
+            proxy,
+            rewrites,
+            branch: None,
+            variant: None,
+        };
+
+        if !project_cfg.targets.is_empty() {
+            for variant in variant_projects(&project, &config.libs_path) {
+                projects.insert(variant.url_path.clone(), variant);
+            }
+            projects.insert(url_path, project);
+        } else if project_cfg.branches.is_empty() {
+            projects.insert(url_path, project);
+        } else {
+            for branch in branch_projects(project, &config.libs_path)? {
+                projects.insert(branch.url_path.clone(), branch);
+            }
+        }
+    }
+
+    Ok(projects)
+}
+
+/// Where the docs of a project are generated, or kept for projects without a build
+fn docs_path(libs_path: &Path, project_cfg: &ProjectConfig) -> PathBuf {
+    let project_path = libs_path.join(&project_cfg.path);
+    match project_cfg.build_system {
+        BuildSystem::Gradle => match project_cfg.main_target() {
+            Some(target) => project_path.join(&target.path),
+            None => project_path.join(gradle_docs_dir(project_cfg.gradle_task.as_deref())),
+        },
+        BuildSystem::Cargo => project_path.join("target/doc"),
+        BuildSystem::Zig => project_path.join("docs"),
+        // TODO: determine actual folder: bin/net-x.0/.../
+        BuildSystem::DotNet => project_path.join("docs"),
+        BuildSystem::Pydoc => project_path.join(pydoc::DOCS_DIR),
+        BuildSystem::Vuepress => project_path.join("docs/.vuepress/dist"),
+        BuildSystem::Docusaurus => project_path.join("build"),
+        BuildSystem::Custom => project_path.join("docs"),
+        BuildSystem::None => match &project_cfg.docs_path {
+            Some(docs_path) => libs_path.join(docs_path),
+            None => project_path,
+        },
+    }
+}
+
+/// Static docs need a `docs_path` and nothing to update them from. A missing directory is
+/// only warned about, since it may be filled in after startup.
+async fn validate_static_docs(config: &Config, project_cfg: &ProjectConfig) -> AppResult<()> {
+    let Some(docs_path) = &project_cfg.docs_path else {
+        return Err(AppError::Config(format!(
+            "project '{}' has build_system = \"none\" and needs a docs_path",
+            project_cfg.path
+        )));
+    };
+    if project_cfg.repo.is_some() || !project_cfg.branches.is_empty() {
+        return Err(AppError::Config(format!(
+            "project '{}' has build_system = \"none\", so it can't set repo or branches",
+            project_cfg.path
+        )));
+    }
+
+    let docs_path = config.libs_path.join(docs_path);
+    if !fs::metadata(&docs_path).await.is_ok_and(|m| m.is_dir()) {
+        warn!(
+            "docs_path {} of project '{}' isn't a directory, it will be served as soon as it is",
+            docs_path.display(),
+            project_cfg.path
+        );
+    }
+    Ok(())
+}
+
+/// Splits a project with `branches` into one project per branch, each with a checkout of its
+/// own in `{path}@{branch}` and served at `/{slug}/{branch}/`. The project's aliases lead to
+/// the default branch.
+fn branch_projects(project: Project, libs_path: &Path) -> AppResult<Vec<Project>> {
+    let cfg = &project.config;
+    if cfg.repo.is_none() || cfg.proxy_docs_url.is_some() {
+        return Err(AppError::Config(format!(
+            "project '{}' sets branches, which needs a repo and no proxy_docs_url",
+            cfg.path
+        )));
+    }
+    let default = cfg.default_branch.as_ref().unwrap_or(&cfg.branches[0]);
+    if !cfg.branches.contains(default) {
+        return Err(AppError::Config(format!(
+            "default_branch '{}' of project '{}' isn't one of its branches",
+            default, cfg.path
+        )));
+    }
+
+    let mut slugs = HashSet::new();
+    let mut projects = Vec::with_capacity(cfg.branches.len());
+    for name in &cfg.branches {
+        let slug = route_slug(
+            name,
+            &format!("branch '{}' of project '{}'", name, cfg.path),
+        )?;
+        if !slugs.insert(slug.clone()) {
+            return Err(AppError::Config(format!(
+                "branch '{}' of project '{}' has a duplicate URL slug '{}'",
+                name, cfg.path, slug
+            )));
+        }
+
+        let mut config = cfg.clone();
+        config.display_name = Some(project.name().to_string());
+        config.path = format!("{}@{}", cfg.path, slug);
+        let is_default = name == default;
+        projects.push(Project {
+            docs_path: docs_path(libs_path, &config),
+            config,
+            url_path: format!("{}/{}", project.url_path, slug),
+            aliases: if is_default {
+                project.aliases.clone()
+            } else {
+                Vec::new()
+            },
+            serve_filter: project.serve_filter.clone(),
+            ignore_rules: project.ignore_rules.clone(),
+            proxy: None,
+            rewrites: project.rewrites.clone(),
+            branch: Some(ProjectBranch {
+                name: name.clone(),
+                parent: project.url_path.clone(),
+                is_default,
+            }),
+            variant: None,
+        });
+    }
+
+    Ok(projects)
+}
+
+/// Slug of the docs of a target with `slug_suffix`
+fn variant_slug(slug: &str, suffix: &str) -> String {
+    format!("{}-{}", slug, sanitize_path(suffix))
+}
+
+fn validate_targets(cfg: &ProjectConfig) -> AppResult<()> {
+    if cfg.targets.is_empty() {
+        return Ok(());
+    }
+    let invalid = |reason: &str| {
+        Err(AppError::Config(format!(
+            "targets of project '{}' {}",
+            cfg.path, reason
+        )))
+    };
+
+    if !matches!(cfg.build_system, BuildSystem::Gradle) {
+        return invalid("need build_system = \"gradle\"");
+    }
+    if cfg.gradle_task.is_some() {
+        return invalid("replace gradle_task, set only one of them");
+    }
+    if !cfg.branches.is_empty() {
+        return invalid("can't be combined with branches");
+    }
+    if cfg
+        .targets
+        .iter()
+        .filter(|t| t.slug_suffix.is_empty())
+        .count()
+        != 1
+    {
+        return invalid(
+            "need exactly one target without a slug_suffix, served at the project's slug",
+        );
+    }
+
+    let mut suffixes = HashSet::new();
+    for target in &cfg.targets {
+        if !is_contained_path(&target.path.to_string_lossy()) {
+            return invalid(&format!(
+                "must write inside the project directory, not to '{}'",
+                target.path.display()
+            ));
+        }
+        let suffix = sanitize_path(&target.slug_suffix);
+        if !target.slug_suffix.is_empty() && (suffix.is_empty() || !suffixes.insert(suffix)) {
+            return invalid(&format!(
+                "have an empty or duplicate slug_suffix '{}'",
+                target.slug_suffix
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The projects serving the docs of the `targets` with a `slug_suffix`. They share the
+/// project's checkout and are built by its Gradle run, at `/{slug}-{suffix}/`.
+fn variant_projects(project: &Project, libs_path: &Path) -> Vec<Project> {
+    let project_path = libs_path.join(&project.config.path);
+    project
+        .config
+        .targets
+        .iter()
+        .filter(|t| !t.slug_suffix.is_empty())
+        .map(|target| Project {
+            config: project.config.clone(),
+            docs_path: project_path.join(&target.path),
+            url_path: variant_slug(&project.url_path, &target.slug_suffix),
+            aliases: Vec::new(),
+            serve_filter: project.serve_filter.clone(),
+            ignore_rules: project.ignore_rules.clone(),
+            proxy: None,
+            rewrites: project.rewrites.clone(),
+            branch: None,
+            variant: Some(ProjectVariant {
+                name: sanitize_path(&target.slug_suffix),
+                task: target.task.clone(),
+                parent: project.url_path.clone(),
+            }),
+        })
+        .collect()
+}
+
+/// A config of `libs_path` and `projects`, the TOML of its `[[projects]]`, parsed like a
+/// config file
+#[cfg(test)]
+pub(crate) fn parse_config(libs_path: &Path, projects: &str) -> Config {
+    parse_config_str(&format!(
+        "libs_path = {:?}\n{}",
+        libs_path.to_string_lossy(),
+        projects
+    ))
+    .unwrap()
+}
+
+/// The projects of a [`parse_config`] config, by slug, as the server sets them up
+#[cfg(test)]
+pub(crate) async fn test_projects(libs_path: &Path, projects: &str) -> HashMap<String, Project> {
+    initialize_projects(&parse_config(libs_path, projects))
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_initialize_projects() {
+        let libs = TempDir::new().unwrap();
+        let cases = [
+            ("gradle", "Java Lib", "java-lib", "build/docs/javadoc"),
+            ("cargo", "rust_lib", "rust-lib", "target/doc"),
+            ("zig", "zig-lib", "zig-lib", "docs"),
+            ("dotnet", "DotNet.Lib", "dotnet-lib", "docs"),
+            ("pydoc", "py_lib", "py-lib", "docs"),
+            ("vuepress", "sdk-guide", "sdk-guide", "docs/.vuepress/dist"),
+            ("docusaurus", "website", "website", "build"),
+            ("custom", "custom", "custom", "docs"),
+        ];
+
+        let mut projects = String::new();
+        for (build_system, path, _, _) in cases {
+            std::fs::create_dir_all(libs.path().join(path)).unwrap();
+            projects.push_str(&format!(
+                "[[projects]]\npath = {:?}\nbuild_system = {:?}\n",
+                path, build_system
+            ));
+        }
+
+        let config = parse_config(libs.path(), &projects);
+        let initialized = initialize_projects(&config).await.unwrap();
+        assert_eq!(initialized.len(), cases.len());
+
+        for (_, path, slug, docs_dir) in cases {
+            let project = &initialized[slug];
+            assert_eq!(project.url_path, slug);
+            assert_eq!(project.docs_path, libs.path().join(path).join(docs_dir));
+            assert_eq!(project.config.index_file, "index.html");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_rejects_escaping_paths() {
+        let libs = TempDir::new().unwrap();
+
+        for field in ["entry_file", "index_file"] {
+            for value in ["../secret.html", "/etc/passwd", ""] {
+                let config = parse_config(
+                    libs.path(),
+                    &format!(
+                        "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\n{} = {:?}\n",
+                        field, value
+                    ),
+                );
+                assert!(
+                    initialize_projects(&config).await.is_err(),
+                    "{} = {:?} should be rejected",
+                    field,
+                    value
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_aliases() {
+        let libs = TempDir::new().unwrap();
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"new-name\"\nbuild_system = \"cargo\"\naliases = [\"Old_Name\"]\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        assert_eq!(projects["new-name"].aliases, ["old-name"]);
+        assert!(!projects.contains_key("old-name"));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_build_systems() {
+        let libs = TempDir::new().unwrap();
+        let projects = "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\n\
+                        [[projects]]\npath = \"b\"\nbuild_system = \"custom\"\nbuild_commands = [\"true\"]\n";
+
+        let config = parse_config(libs.path(), projects);
+        assert_eq!(initialize_projects(&config).await.unwrap().len(), 2);
+
+        let config = parse_config(
+            libs.path(),
+            &format!("allowed_build_systems = [\"cargo\", \"zig\"]\n{}", projects),
+        );
+        let error = initialize_projects(&config).await.unwrap_err().to_string();
+        assert!(
+            error.contains("'b'") && error.contains("\"custom\""),
+            "{}",
+            error
+        );
+
+        let unknown = format!(
+            "libs_path = {:?}\nallowed_build_systems = [\"make\"]\nprojects = []\n",
+            libs.path().to_string_lossy()
+        );
+        assert!(parse_config_str(&unknown).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_static_docs() {
+        let libs = TempDir::new().unwrap();
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"handbook\"\nbuild_system = \"none\"\ndocs_path = \"shared/handbook-html\"\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        let handbook = &projects["handbook"];
+        assert!(handbook.is_static());
+        assert_eq!(handbook.docs_path, libs.path().join("shared/handbook-html"));
+
+        for invalid in [
+            "[[projects]]\npath = \"handbook\"\nbuild_system = \"none\"\n",
+            "[[projects]]\npath = \"handbook\"\nbuild_system = \"none\"\ndocs_path = \"html\"\n\
+             repo = \"https://example.com/handbook.git\"\n",
+            "[[projects]]\npath = \"handbook\"\nbuild_system = \"cargo\"\nversions_from_dirs = true\n",
+        ] {
+            let config = parse_config(libs.path(), invalid);
+            assert!(initialize_projects(&config).await.is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_branches() {
+        let libs = TempDir::new().unwrap();
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nrepo = \"https://example.com/lib.git\"\nbuild_system = \"cargo\"\n\
+             aliases = [\"old\"]\nbranches = [\"main\", \"release/v2\"]\ndefault_branch = \"release/v2\"\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        assert_eq!(projects.len(), 2);
+
+        let main = &projects["lib/main"];
+        assert_eq!(main.config.path, "lib@main");
+        assert_eq!(main.docs_path, libs.path().join("lib@main/target/doc"));
+        assert!(main.aliases.is_empty());
+        let v2 = &projects["lib/release-v2"];
+        assert_eq!(v2.name(), "lib");
+        assert_eq!(v2.aliases, ["old"]);
+        let branch = v2.branch.as_ref().unwrap();
+        assert_eq!(
+            (branch.name.as_str(), branch.parent.as_str()),
+            ("release/v2", "lib")
+        );
+        assert!(branch.is_default);
+
+        for invalid in [
+            // no repo to check the branches out from
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nbranches = [\"main\"]\n",
+            "[[projects]]\npath = \"lib\"\nrepo = \"https://example.com/lib.git\"\nbuild_system = \"cargo\"\n\
+             branches = [\"main\"]\ndefault_branch = \"v2\"\n",
+            "[[projects]]\npath = \"lib\"\nrepo = \"https://example.com/lib.git\"\nbuild_system = \"cargo\"\n\
+             branches = [\"v2\", \"V2\"]\n",
+            // branches with no slug, or one of lichen's routes
+            "[[projects]]\npath = \"lib\"\nrepo = \"https://example.com/lib.git\"\nbuild_system = \"cargo\"\n\
+             branches = [\"main\", \"_\"]\n",
+            "[[projects]]\npath = \"lib\"\nrepo = \"https://example.com/lib.git\"\nbuild_system = \"cargo\"\n\
+             branches = [\"main\", \"api\"]\n",
+        ] {
+            let config = parse_config(libs.path(), invalid);
+            assert!(initialize_projects(&config).await.is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_rejects_slug_collisions() {
+        let libs = TempDir::new().unwrap();
+        let cases = [
+            // two projects sanitizing to the same slug
+            "[[projects]]\npath = \"my_lib\"\nbuild_system = \"cargo\"\n\
+             [[projects]]\npath = \"my-lib\"\nbuild_system = \"cargo\"\n",
+            // alias equal to the project's own slug
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"a\"]\n",
+            // alias equal to another project's slug, in either order
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"b\"]\n\
+             [[projects]]\npath = \"b\"\nbuild_system = \"cargo\"\n",
+            "[[projects]]\npath = \"b\"\nbuild_system = \"cargo\"\n\
+             [[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"b\"]\n",
+            // aliases pointing at each other
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"b\"]\n\
+             [[projects]]\npath = \"b\"\nbuild_system = \"cargo\"\naliases = [\"a\"]\n",
+            // the same alias on two projects
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"old\"]\n\
+             [[projects]]\npath = \"b\"\nbuild_system = \"cargo\"\naliases = [\"old\"]\n",
+            // aliases that are empty once sanitized, or name lichen's own routes
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"_\"]\n",
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"\"]\n",
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"API\"]\n",
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"metrics\"]\n",
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"favicon.ico\"]\n",
+            "[[projects]]\npath = \"a\"\nbuild_system = \"cargo\"\naliases = [\"badge-proxy\"]\n",
+            // and so are projects
+            "[[projects]]\npath = \"api\"\nbuild_system = \"cargo\"\n",
+            "[[projects]]\npath = \"metrics\"\nbuild_system = \"cargo\"\n",
+            "[[projects]]\npath = \"_\"\nbuild_system = \"cargo\"\n",
+            // and the slugs of targets with a slug_suffix
+            "[[projects]]\npath = \"badge\"\nbuild_system = \"gradle\"\n\
+             targets = [{ task = \"javadoc\", path = \"a\" }, { task = \"b\", path = \"b\", slug_suffix = \"proxy\" }]\n",
+        ];
+
+        for projects in cases {
+            let config = parse_config(libs.path(), projects);
+            assert!(
+                initialize_projects(&config).await.is_err(),
+                "{} should be rejected",
+                projects
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_projects_serve_filters() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        assert!(!projects["lib"].serve_filter.allows(Path::new(".env")));
+
+        // overriding never_serve replaces the defaults
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nnever_serve = []\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        assert!(projects["lib"].serve_filter.allows(Path::new(".env")));
+
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nserve_only = [\"*.{html\"]\n",
+        );
+        assert!(initialize_projects(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_display_name() {
+        let libs = TempDir::new().unwrap();
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"my-awesome-auth-library-v2\"\nbuild_system = \"cargo\"\n\
+             display_name = \"Auth Library\"\n",
+        );
+        let projects = initialize_projects(&config).await.unwrap();
+        let project = &projects["my-awesome-auth-library-v2"];
+        assert_eq!(project.name(), "Auth Library");
+    }
+
+    #[tokio::test]
+    async fn test_custom_commands() {
+        let libs = TempDir::new().unwrap();
+        let project = |options: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\n{}",
+                    options
+                ),
+            )
+        };
+
+        let config = project("build_commands = [\"npm install\", \"npm run docs\"]\n");
+        assert_eq!(
+            config.projects[0].custom_commands(),
+            ["npm install", "npm run docs"]
+        );
+        assert!(initialize_projects(&config).await.is_ok());
+
+        // deprecated, but still works
+        let config = project("build_command = \"make docs\"\n");
+        assert_eq!(config.projects[0].custom_commands(), ["make docs"]);
+        assert!(initialize_projects(&config).await.is_ok());
+
+        assert!(project("").projects[0].custom_commands().is_empty());
+
+        let config = project("build_command = \"make docs\"\nbuild_commands = [\"make\"]\n");
+        assert!(initialize_projects(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gradle_task() {
+        let libs = TempDir::new().unwrap();
+        let project = |options: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n{}",
+                    options
+                ),
+            )
+        };
+
+        let config = project("");
+        assert_eq!(config.projects[0].gradle_args(), ["clean", "javadoc"]);
+        let projects = initialize_projects(&config).await.unwrap();
+        assert_eq!(
+            projects["lib"].docs_path,
+            libs.path().join("lib/build/docs/javadoc")
+        );
+
+        let config = project("gradle_task = \"dokkaHtml\"\n");
+        assert_eq!(config.projects[0].gradle_args(), ["clean", "dokkaHtml"]);
+        let projects = initialize_projects(&config).await.unwrap();
+        assert_eq!(
+            projects["lib"].docs_path,
+            libs.path().join("lib/build/dokka/html")
+        );
+
+        let config = project("gradle_task = \"dokkaGfm\"\nskip_clean = true\n");
+        assert_eq!(config.projects[0].gradle_args(), ["dokkaGfm"]);
+
+        let config = project("skip_clean = false\n");
+        assert_eq!(config.projects[0].gradle_args(), ["clean", "javadoc"]);
+    }
+
+    #[tokio::test]
+    async fn test_gradle_targets() {
+        let libs = TempDir::new().unwrap();
+        let targets = "targets = [\n\
+             { task = \"javadoc\", path = \"build/docs/javadoc\" },\n\
+             { task = \"testFixturesJavadoc\", path = \"build/docs/testFixturesJavadoc\", slug_suffix = \"fixtures\" },\n\
+             ]\n";
+        let config = parse_config(
+            libs.path(),
+            &format!(
+                "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n{}",
+                targets
+            ),
+        );
+        assert_eq!(
+            config.projects[0].gradle_args(),
+            ["clean", "javadoc", "testFixturesJavadoc"]
+        );
+
+        let projects = initialize_projects(&config).await.unwrap();
+        assert_eq!(
+            projects["lib"].docs_path,
+            libs.path().join("lib/build/docs/javadoc")
+        );
+        assert!(projects["lib"].variant.is_none());
+        let fixtures = &projects["lib-fixtures"];
+        assert_eq!(
+            fixtures.docs_path,
+            libs.path().join("lib/build/docs/testFixturesJavadoc")
+        );
+        let variant = fixtures.variant.as_ref().unwrap();
+        assert_eq!(
+            (variant.name.as_str(), variant.parent.as_str()),
+            ("fixtures", "lib")
+        );
+
+        // the variant's slug is taken, whichever project comes first
+        for projects in [
+            format!(
+                "[[projects]]\npath = \"lib-fixtures\"\nbuild_system = \"cargo\"\n\
+                 [[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n{}",
+                targets
+            ),
+            format!(
+                "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n{}\
+                 [[projects]]\npath = \"other\"\nbuild_system = \"cargo\"\naliases = [\"lib-fixtures\"]\n",
+                targets
+            ),
+        ] {
+            let error = initialize_projects(&parse_config(libs.path(), &projects))
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("'lib-fixtures'"), "{}", error);
+        }
+
+        for invalid in [
+            "targets = [{ task = \"javadoc\", path = \"docs\", slug_suffix = \"a\" }]\n",
+            "targets = [{ task = \"javadoc\", path = \"../elsewhere\" }]\n",
+            "targets = [{ task = \"javadoc\", path = \"a\" }, { task = \"b\", path = \"b\", slug_suffix = \"x\" }, { task = \"c\", path = \"c\", slug_suffix = \"X\" }]\n",
+            "gradle_task = \"javadoc\"\ntargets = [{ task = \"javadoc\", path = \"a\" }]\n",
+        ] {
+            let config = parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n{}",
+                    invalid
+                ),
+            );
+            assert!(initialize_projects(&config).await.is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_min_zig_version() {
+        let libs = TempDir::new().unwrap();
+        let project = |version: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"zig\"\nmin_zig_version = {:?}\n",
+                    version
+                ),
+            )
+        };
+
+        let config = project("0.14.0");
+        assert_eq!(
+            config.projects[0].min_zig_version().unwrap(),
+            ZigVersion::parse("0.14.0")
+        );
+        assert!(initialize_projects(&config).await.is_ok());
+        assert!(initialize_projects(&project("latest")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sparse_paths() {
+        let libs = TempDir::new().unwrap();
+        let project = |paths: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n\n[projects.git]\nsparse_paths = {}\n",
+                    paths
+                ),
+            )
+        };
+
+        let config = project(r#"["src", "docs/api"]"#);
+        assert_eq!(config.projects[0].git.sparse_paths, ["src", "docs/api"]);
+        assert!(initialize_projects(&config).await.is_ok());
+        assert!(initialize_projects(&project(r#"["../other"]"#))
+            .await
+            .is_err());
+
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"gradle\"\n",
+        );
+        assert!(config.projects[0].git.sparse_paths.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_docs_url() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\n",
+        );
+        assert_eq!(config.projects[0].proxy_timeout_secs, 30);
+        assert!(initialize_projects(&config).await.unwrap()["lib"]
+            .proxy
+            .is_none());
+
+        let project = |url: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\nproxy_docs_url = {:?}\nproxy_timeout_secs = 5\n",
+                    url
+                ),
+            )
+        };
+        let projects = initialize_projects(&project("https://docs.rs/lib/latest"))
+            .await
+            .unwrap();
+        assert!(projects["lib"].proxy.is_some());
+        assert!(initialize_projects(&project("not a url")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_source_url_template() {
+        let libs = TempDir::new().unwrap();
+        let project = |template: &str| {
+            parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nsource_url_template = {:?}\n",
+                    template
+                ),
+            )
+        };
+
+        let template = "https://github.com/org/lib/blob/{commit}/{path}#L{line}";
+        let projects = initialize_projects(&project(template)).await.unwrap();
+        assert_eq!(
+            projects["lib"].config.source_url_template.as_deref(),
+            Some(template)
+        );
+        assert!(initialize_projects(&project("javascript:alert(1)"))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_badges() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(
+            libs.path(),
+            "badge_proxy_hosts = [\"img.shields.io\"]\n[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nauto_badges = true\nbadges = [{ img = \"https://ci.example.com/lib.svg\", href = \"https://ci.example.com/lib\" }, { img = \"https://example.com/x.svg\" }]\n",
+        );
+        assert_eq!(config.badge_proxy_hosts, ["img.shields.io"]);
+        let project = &config.projects[0];
+        assert!(project.auto_badges);
+        assert_eq!(project.badges.len(), 2);
+        assert_eq!(
+            project.badges[0].href.as_deref(),
+            Some("https://ci.example.com/lib")
+        );
+        assert_eq!(project.badges[1].href, None);
+
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\n",
+        );
+        assert!(config.badge_proxy_hosts.is_empty());
+        assert!(!config.projects[0].auto_badges);
+        assert!(config.projects[0].badges.is_empty());
+    }
+
+    #[test]
+    fn test_default_build_system() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(
+            libs.path(),
+            "default_build_system = \"custom\"\n[[projects]]\npath = \"a\"\n[[projects]]\npath = \"b\"\nbuild_system = \"cargo\"\n",
+        );
+        assert!(matches!(
+            config.projects[0].build_system,
+            BuildSystem::Custom
+        ));
+        assert!(matches!(
+            config.projects[1].build_system,
+            BuildSystem::Cargo
+        ));
+
+        let without_default = format!(
+            "libs_path = {:?}\n[[projects]]\npath = \"a\"\n",
+            libs.path().to_string_lossy()
+        );
+        assert!(parse_config_str(&without_default).is_err());
+        let invalid_default = format!(
+            "libs_path = {:?}\ndefault_build_system = \"make\"\n[[projects]]\npath = \"a\"\n",
+            libs.path().to_string_lossy()
+        );
+        assert!(parse_config_str(&invalid_default).is_err());
+    }
+
+    #[test]
+    fn test_server_config() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(libs.path(), "projects = []\n");
+        assert_eq!(config.server.keep_alive_secs, 5);
+        assert_eq!(config.server.client_request_timeout_secs, 5);
+        assert_eq!(config.server.max_connections, None);
+        assert_eq!(config.server.shutdown_grace_period_secs, 30);
+
+        let config = parse_config(
+            libs.path(),
+            "projects = []\n[server]\nkeep_alive_secs = 0\nclient_request_timeout_secs = 30\nmax_connections = 256\nmax_connection_rate = 32\nshutdown_grace_period_secs = 0\n",
+        );
+        assert_eq!(config.server.keep_alive_secs, 0);
+        assert_eq!(config.server.client_request_timeout_secs, 30);
+        assert_eq!(config.server.max_connections, Some(256));
+        assert_eq!(config.server.max_connection_rate, Some(32));
+        assert_eq!(config.server.shutdown_grace_period_secs, 0);
+        assert!(config.admin.is_none());
+
+        let config = parse_config(libs.path(), "projects = []\n[admin]\nport = 9090\n");
+        let admin = config.admin.unwrap();
+        assert_eq!((admin.bind.as_str(), admin.port), ("127.0.0.1", 9090));
+    }
+
+    #[test]
+    fn test_index_sort() {
+        let libs = TempDir::new().unwrap();
+
+        let config = parse_config(libs.path(), "projects = []\n");
+        assert_eq!(config.index_sort, IndexSort::Name);
+        let config = parse_config(libs.path(), "index_sort = \"last_built\"\nprojects = []\n");
+        assert_eq!(config.index_sort, IndexSort::LastBuilt);
+        let config = parse_config(
+            libs.path(),
+            "project_sort = \"build_system\"\nprojects = []\n",
+        );
+        assert_eq!(config.index_sort, IndexSort::BuildSystem);
+
+        let unknown = format!(
+            "libs_path = {:?}\nindex_sort = \"size\"\nprojects = []\n",
+            libs.path().to_string_lossy()
+        );
+        assert!(parse_config_str(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_resolve_config_path() {
+        let flag = Path::new("/etc/lichen/flag.toml");
+        let env = || Some(OsString::from("/etc/lichen/env.toml"));
+
+        assert_eq!(resolve_config_path(Some(flag), env()), flag);
+        assert_eq!(
+            resolve_config_path(None, env()),
+            Path::new("/etc/lichen/env.toml")
+        );
+        assert_eq!(
+            resolve_config_path(None, Some(OsString::new())),
+            Path::new("config.toml")
+        );
+        assert_eq!(resolve_config_path(None, None), Path::new("config.toml"));
+    }
+
+    #[test]
+    fn test_yaml_config() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/etc/lichen/config.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.toml")),
+            ConfigFormat::Toml
+        );
+
+        let config = parse_yaml_config_str(
+            "libs_path: /srv/libs\ndefault_build_system: custom\nserver:\n  keep_alive_secs: 0\nprojects:\n  - path: a\n    aliases: [old-a]\n  - path: b\n    build_system: cargo\n",
+        )
+        .unwrap();
+        assert_eq!(config.libs_path, Path::new("/srv/libs"));
+        assert_eq!(config.server.keep_alive_secs, 0);
+        assert!(matches!(
+            config.projects[0].build_system,
+            BuildSystem::Custom
+        ));
+        assert_eq!(config.projects[0].aliases, ["old-a"]);
+        assert!(matches!(
+            config.projects[1].build_system,
+            BuildSystem::Cargo
+        ));
+        assert!(parse_yaml_config_str("libs_path: [unclosed").is_err());
+
+        // null and empty values are left out
+        let config = parse_yaml_config_str(
+            "libs_path: /srv/libs\nlog_level: ~\nprojects:\n  - path: a\n    build_system: custom\n    repo: ~\n    proxy_docs_url:\n",
+        )
+        .unwrap();
+        assert_eq!(config.projects[0].repo, None);
+        assert_eq!(config.projects[0].proxy_docs_url, None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_index_file() {
+        let libs = TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        let config = parse_config(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\ncustom_index_file = \"modules.html\"\n",
+        );
+        let mut project = initialize_projects(&config)
+            .await
+            .unwrap()
+            .remove("lib")
+            .unwrap();
+
+        // nothing to fall back to yet
+        project.resolve_index_file();
+        assert_eq!(project.config.index_file, "modules.html");
+
+        std::fs::write(docs.join("modules.html"), "").unwrap();
+        std::fs::write(docs.join("index.html"), "").unwrap();
+        project.resolve_index_file();
+        assert_eq!(project.config.index_file, "modules.html");
+
+        std::fs::remove_file(docs.join("modules.html")).unwrap();
+        project.resolve_index_file();
+        assert_eq!(project.index_file_name(), "index.html");
+    }
+
+    #[test]
+    fn test_index_url() {
+        let libs = TempDir::new().unwrap();
+        let project = |index_file: &str| Project {
+            config: parse_config(
+                libs.path(),
+                &format!(
+                    "[[projects]]\npath = \"lib\"\nbuild_system = \"cargo\"\nindex_file = {:?}\n",
+                    index_file
+                ),
+            )
+            .projects
+            .remove(0),
+            docs_path: libs.path().join("lib/target/doc"),
+            url_path: "lib".to_string(),
+            aliases: Vec::new(),
+            serve_filter: ServeFilter::new(&[], &[], "lib").unwrap(),
+            ignore_rules: IgnoreRules::default(),
+            proxy: None,
+            rewrites: Vec::new(),
+            branch: None,
+            variant: None,
+        };
+
+        assert_eq!(project("index.html").index_url(), "/lib/");
+        assert_eq!(project("index.html").index_file_name(), "index.html");
+        assert_eq!(project("lib/index.html").index_url(), "/lib/lib/index.html");
+        assert_eq!(project("lib/index.html").index_file_name(), "index.html");
+        assert_eq!(project("index.html").name(), "lib");
+    }
+}
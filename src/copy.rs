@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    artifact,
+    config::Project,
+    error::AppResult,
+    filter::IgnoreRules,
+    upload::{sibling, swap_into_place},
+};
+
+/// Copies the served files of the project's docs to `destination`, replacing whatever was
+/// there once the copy is complete, so readers never see half of it. Returns the number of
+/// files copied.
+pub async fn copy_docs(project: &Project, destination: &Path) -> AppResult<usize> {
+    let files = artifact::list_files(
+        &project.docs_path,
+        &project.serve_filter,
+        &IgnoreRules::default(),
+    )
+    .await?;
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let staging = sibling(destination, "copy");
+    let copied = async {
+        fs::create_dir_all(&staging).await?;
+        for file in &files {
+            let target = staging.join(file);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::copy(project.docs_path.join(file), target).await?;
+        }
+        swap_into_place(&staging, destination).await
+    }
+    .await;
+
+    if let Err(e) = copied {
+        if let Err(e) = fs::remove_dir_all(&staging).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+        return Err(e.into());
+    }
+    Ok(files.len())
+}
+
+/// Runs [`copy_docs`] to the project's `copy_to` for freshly built docs, logging a failure
+/// without failing the build
+pub async fn after_build(project: &Project) {
+    let Some(destination) = &project.config.copy_to else {
+        return;
+    };
+    match copy_docs(project, destination).await {
+        Ok(files) => info!(
+            "Copied docs of {} to {}: {} files",
+            project.config.path,
+            destination.display(),
+            files
+        ),
+        Err(e) => warn!(
+            "Failed to copy docs of {} to {}: {}",
+            project.config.path,
+            destination.display(),
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_docs_replaces_destination() {
+        let libs = TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(docs.join("index.html"), "v2").unwrap();
+        std::fs::write(docs.join("guide/intro.html"), "intro").unwrap();
+        std::fs::write(docs.join("deploy.key"), "secret").unwrap();
+
+        let shared = TempDir::new().unwrap();
+        let destination = shared.path().join("mirror/lib");
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(destination.join("removed.html"), "v1").unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            &format!(
+                "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\ncopy_to = {:?}\n",
+                destination.to_string_lossy()
+            ),
+        )
+        .await;
+
+        assert_eq!(copy_docs(&projects["lib"], &destination).await.unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(destination.join("index.html")).unwrap(),
+            "v2"
+        );
+        assert!(destination.join("guide/intro.html").is_file());
+        assert!(!destination.join("removed.html").exists());
+        assert!(!destination.join("deploy.key").exists());
+        // nothing is left next to the destination
+        assert_eq!(
+            std::fs::read_dir(shared.path().join("mirror"))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        let inside_docs = crate::config::parse_config(
+            libs.path(),
+            &format!(
+                "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\ncopy_to = {:?}\n",
+                docs.join("mirror").to_string_lossy()
+            ),
+        );
+        assert!(crate::config::initialize_projects(&inside_docs)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fs, io, path::Path, process};
+
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+
+/// Fails if `path` names a process that is still running. Stale pid files are left in place
+/// and overwritten once the server is listening.
+pub fn check_pid_file(path: &Path) -> AppResult<()> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+
+    match contents.trim().parse::<i32>() {
+        Ok(pid) if is_running(pid) => Err(AppError::Config(format!(
+            "lichen is already running with pid {} (according to {})",
+            pid,
+            path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn is_running(pid: i32) -> bool {
+    // 0 and negative pids address process groups rather than a single process
+    if pid <= 0 {
+        return false;
+    }
+
+    // SAFETY: signal 0 only checks whether the process exists and may be signalled
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Forks into the background and detaches from the terminal, keeping the working directory so
+/// `config.toml` is still found. Must run before the async runtime starts.
+pub fn detach() -> AppResult<()> {
+    daemonize::Daemonize::new()
+        .working_directory(std::env::current_dir()?)
+        .start()
+        .map_err(|e| AppError::Io(io::Error::other(e.to_string())))
+}
+
+pub async fn write_pid_file(path: &Path) -> AppResult<()> {
+    tokio::fs::write(path, format!("{}\n", process::id())).await?;
+    Ok(())
+}
+
+pub async fn remove_pid_file(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        warn!("Failed to remove pid file {}: {}", path.display(), e);
+    }
+}
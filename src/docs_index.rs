@@ -0,0 +1,400 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::fs;
+
+use crate::error::{AppError, AppResult};
+
+/// Kinds of rustdoc items, in the order of the type codes in `search-index.js`
+const RUSTDOC_KINDS: &[&str] = &[
+    "keyword",
+    "primitive",
+    "mod",
+    "externcrate",
+    "import",
+    "struct",
+    "enum",
+    "fn",
+    "type",
+    "static",
+    "trait",
+    "impl",
+    "tymethod",
+    "method",
+    "structfield",
+    "variant",
+    "macro",
+    "associatedtype",
+    "constant",
+    "associatedconstant",
+    "union",
+    "foreigntype",
+    "existential",
+    "attr",
+    "derive",
+    "traitalias",
+    "generic",
+];
+
+static JAVADOC_ENTRY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<dt>(.*?)</dt>").expect("valid regex"));
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").expect("valid regex"));
+
+/// A documented item, with the module or package it's in as a `/`-separated `path`. Methods
+/// and fields are listed with the path of their type's module, not the type.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: String,
+    pub path: String,
+}
+
+fn invalid_index(file: &Path) -> AppError {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{} isn't in a format lichen understands", file.display()),
+    )
+    .into()
+}
+
+/// The contents of the single-quoted JS string `js` starts with (after its opening quote),
+/// unescaped. `None` if the string isn't closed.
+fn unescape_js(js: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(js.len());
+    let mut chars = js.chars();
+    loop {
+        match chars.next()? {
+            // a backslash before a line break only continues the string
+            '\\' => match chars.next()? {
+                '\n' => {}
+                escaped => unescaped.push(escaped),
+            },
+            '\'' => return Some(unescaped),
+            c => unescaped.push(c),
+        }
+    }
+}
+
+/// Items of one crate of the search index. Names, type codes and paths are parallel lists,
+/// each path applying to the items up to the next one.
+fn crate_symbols(data: &Value, symbols: &mut Vec<Symbol>) -> Option<()> {
+    let names = data.get("n")?.as_array()?;
+    let kinds: Vec<usize> = match data.get("t")? {
+        // type codes as letters from `A` on, since Rust 1.69
+        Value::String(codes) => codes
+            .bytes()
+            .map(|c| c.wrapping_sub(b'A') as usize)
+            .collect(),
+        Value::Array(codes) => codes
+            .iter()
+            .map(|c| c.as_u64().map(|c| c as usize))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    // either `[index, path]` pairs, or one path per item with "" repeating the previous one
+    let mut paths = vec![None; names.len()];
+    for (i, entry) in data
+        .get("q")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        match entry {
+            Value::Array(pair) => {
+                let index = pair.first()?.as_u64()? as usize;
+                if let Some(path) = paths.get_mut(index) {
+                    *path = Some(pair.get(1)?.as_str()?);
+                }
+            }
+            Value::String(path) if !path.is_empty() => {
+                if let Some(slot) = paths.get_mut(i) {
+                    *slot = Some(path.as_str());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut path = "";
+    for (i, name) in names.iter().enumerate() {
+        path = paths[i].unwrap_or(path);
+        let (Some(name), Some(kind)) = (
+            name.as_str(),
+            kinds.get(i).and_then(|&k| RUSTDOC_KINDS.get(k)),
+        ) else {
+            continue;
+        };
+        if name.is_empty() {
+            continue;
+        }
+        symbols.push(Symbol {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            path: path.replace("::", "/"),
+        });
+    }
+    Some(())
+}
+
+/// Reads the `JSON.parse('...')` of a rustdoc `search-index.js`, either a list of
+/// `[crate, data]` pairs or an object keyed by crate
+fn parse_search_index(js: &str) -> Option<Vec<Symbol>> {
+    let start = js.find("JSON.parse('")? + "JSON.parse('".len();
+    let index: Value = serde_json::from_str(&unescape_js(&js[start..])?).ok()?;
+
+    let mut symbols = Vec::new();
+    match &index {
+        Value::Array(crates) => {
+            for entry in crates {
+                crate_symbols(entry.as_array()?.get(1)?, &mut symbols)?;
+            }
+        }
+        Value::Object(crates) => {
+            for data in crates.values() {
+                crate_symbols(data, &mut symbols)?;
+            }
+        }
+        _ => return None,
+    }
+    Some(symbols)
+}
+
+/// Decodes the few entities javadoc writes into names and descriptions
+fn html_text(html: &str) -> String {
+    let text = HTML_TAG.replace_all(html, "");
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Kind of a javadoc index entry from the start of its description, e.g. `Static method`
+fn javadoc_kind(label: &str) -> Option<&'static str> {
+    let label = label.to_lowercase();
+    let label = label.strip_prefix("static ").unwrap_or(&label);
+    Some(match label {
+        "package" => "package",
+        "module" => "module",
+        "class" => "class",
+        "interface" => "interface",
+        "enum" | "enum class" => "enum",
+        "record" | "record class" => "record",
+        "annotation type" | "annotation interface" => "annotation",
+        "exception" | "exception class" => "exception",
+        "error" => "error",
+        "method" => "method",
+        "constructor" => "constructor",
+        "variable" | "field" => "field",
+        "enum constant" => "enum_constant",
+        "element" | "annotation type element" | "annotation interface element" => "element",
+        _ => return None,
+    })
+}
+
+/// One `<dt>` of `index-all.html`, like `foo() - Method in class com.example.Bar` once its
+/// tags are stripped. Search tags and anything unknown are left out.
+fn javadoc_symbol(entry: &str) -> Option<Symbol> {
+    let text = html_text(entry);
+    let (name, description) = text.split_once(" - ")?;
+    let (label, rest) = [" in ", " for "]
+        .iter()
+        .filter_map(|separator| description.split_once(separator))
+        .min_by_key(|(label, _)| label.len())
+        .or_else(|| description.split_once(' '))?;
+    let kind = javadoc_kind(label)?;
+
+    let qualified = rest.rsplit(' ').next().unwrap_or_default();
+    let package = match kind {
+        "package" | "module" => "",
+        // members name the type they belong to
+        "method" | "constructor" | "field" | "enum_constant" | "element" => qualified
+            .rsplit_once('.')
+            .map_or("", |(package, _)| package),
+        _ => qualified,
+    };
+    Some(Symbol {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        path: package.replace('.', "/"),
+    })
+}
+
+fn parse_javadoc_index(html: &str) -> Vec<Symbol> {
+    JAVADOC_ENTRY
+        .captures_iter(html)
+        .filter_map(|entry| javadoc_symbol(&entry[1]))
+        .collect()
+}
+
+/// rustdoc's `search-index.js`, named `search-index{suffix}.js` by old versions
+async fn find_search_index(docs_path: &Path) -> Option<PathBuf> {
+    let path = docs_path.join("search-index.js");
+    if fs::try_exists(&path).await.unwrap_or(false) {
+        return Some(path);
+    }
+    let mut entries = fs::read_dir(docs_path).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("search-index") && name.ends_with(".js") {
+            return Some(entry.path());
+        }
+    }
+    None
+}
+
+/// javadoc's `index-all.html`, or the `index-files/index-{n}.html` of `-splitindex`, in order
+async fn find_javadoc_index(docs_path: &Path) -> Vec<PathBuf> {
+    let path = docs_path.join("index-all.html");
+    if fs::try_exists(&path).await.unwrap_or(false) {
+        return vec![path];
+    }
+    let mut pages = Vec::new();
+    if let Ok(mut entries) = fs::read_dir(docs_path.join("index-files")).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let number = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("index-")?.strip_suffix(".html"))
+                .and_then(|n| n.parse::<u32>().ok());
+            if let Some(number) = number {
+                pages.push((number, entry.path()));
+            }
+        }
+    }
+    pages.sort();
+    pages.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Every symbol documented in `docs_path`, from rustdoc's search index or javadoc's index
+/// pages. `None` if the docs have neither.
+pub async fn load(docs_path: &Path) -> AppResult<Option<Vec<Symbol>>> {
+    if let Some(path) = find_search_index(docs_path).await {
+        let js = fs::read_to_string(&path).await?;
+        return parse_search_index(&js)
+            .map(Some)
+            .ok_or_else(|| invalid_index(&path));
+    }
+
+    let pages = find_javadoc_index(docs_path).await;
+    if pages.is_empty() {
+        return Ok(None);
+    }
+    let mut symbols = Vec::new();
+    for page in pages {
+        symbols.extend(parse_javadoc_index(&fs::read_to_string(&page).await?));
+    }
+    Ok(Some(symbols))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn symbol(name: &str, kind: &str, path: &str) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_search_index() {
+        let js = "var searchIndex = new Map(JSON.parse('[\\\n\
+            [\"mylib\",{\"t\":\"FNHG\",\"n\":[\"HashMap\",\"insert\",\"new\",\"Entry\"],\
+            \"q\":[[0,\"mylib::collections\"],[3,\"mylib::collections::hash_map\"]],\"d\":[\"It\\'s a map\",\"\",\"\",\"\"]}],\\\n\
+            [\"other\",{\"t\":\"A\",\"n\":[\"unsafe\"],\"q\":[[0,\"other\"]]}]\\\n\
+            ]'));\nif (typeof exports !== 'undefined') exports.searchIndex = searchIndex;";
+        assert_eq!(
+            parse_search_index(js).unwrap(),
+            [
+                symbol("HashMap", "struct", "mylib/collections"),
+                symbol("insert", "method", "mylib/collections"),
+                symbol("new", "fn", "mylib/collections"),
+                symbol("Entry", "enum", "mylib/collections/hash_map"),
+                symbol("unsafe", "keyword", "other"),
+            ]
+        );
+
+        // older rustdoc: numeric type codes, one path per item
+        let js = "var searchIndex = JSON.parse('{\\\n\"lib\":{\"t\":[5,7],\"n\":[\"Foo\",\"bar\"],\"q\":[\"lib::a\",\"\"]}\\\n}');";
+        assert_eq!(
+            parse_search_index(js).unwrap(),
+            [
+                symbol("Foo", "struct", "lib/a"),
+                symbol("bar", "fn", "lib/a")
+            ]
+        );
+
+        assert_eq!(parse_search_index("searchIndex[\"lib\"] = {};"), None);
+    }
+
+    #[test]
+    fn test_parse_javadoc_index() {
+        let html = r#"<dl class="index">
+<dt><a href="com/example/package-summary.html">com.example</a> - package com.example</dt>
+<dt><a href="com/example/Cache.html" class="type-name-link" title="class in com.example">Cache</a> - Class in <a href="com/example/package-summary.html">com.example</a></dt>
+<dd>A cache.</dd>
+<dt><a href="com/example/Cache.html#get(K)" class="member-name-link">get(K)</a> - Method in class com.example.<a href="com/example/Cache.html" title="class in com.example">Cache</a></dt>
+<dt><a href="com/example/Cache.html#%3Cinit%3E()" class="member-name-link">Cache()</a> - Constructor for class com.example.<a href="com/example/Cache.html">Cache</a></dt>
+<dt><span class="memberNameLink"><a href="com/example/Mode.html#LRU">LRU</a></span> - Enum constant in enum com.example.<a href="com/example/Mode.html">Mode</a></dt>
+<dt><a href="com/example/Cache.html#MAX" class="member-name-link">MAX</a> - Static variable in class com.example.<a href="com/example/Cache.html">Cache</a></dt>
+<dt><a href="com/example/Cache.html#eviction" class="search-tag-link">eviction</a> - Search tag in class com.example.Cache</dt>
+<dt><a href="com/example/Loader.html">Loader&lt;K&gt;</a> - Interface in <a href="com/example/package-summary.html">com.example</a></dt>
+</dl>"#;
+        assert_eq!(
+            parse_javadoc_index(html),
+            [
+                symbol("com.example", "package", ""),
+                symbol("Cache", "class", "com/example"),
+                symbol("get(K)", "method", "com/example"),
+                symbol("Cache()", "constructor", "com/example"),
+                symbol("LRU", "enum_constant", "com/example"),
+                symbol("MAX", "field", "com/example"),
+                symbol("Loader<K>", "interface", "com/example"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let docs = TempDir::new().unwrap();
+        assert_eq!(load(docs.path()).await.unwrap(), None);
+
+        // split javadoc index pages, in their numeric order
+        std::fs::create_dir(docs.path().join("index-files")).unwrap();
+        for (page, class) in [(10, "Zebra"), (2, "Bear")] {
+            std::fs::write(
+                docs.path().join(format!("index-files/index-{}.html", page)),
+                format!("<dt><a href=\"x\">{}</a> - Class in zoo</dt>", class),
+            )
+            .unwrap();
+        }
+        let names = load(docs.path())
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Bear", "Zebra"]);
+
+        // rustdoc's search index wins
+        std::fs::write(docs.path().join("search-index.js"), "searchIndex = {};").unwrap();
+        assert!(matches!(load(docs.path()).await, Err(AppError::Io(_))));
+    }
+}
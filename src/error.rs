@@ -0,0 +1,101 @@
+use std::{fmt, path::PathBuf};
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// Invalid or unreadable configuration
+    Config(String),
+    /// Failure while fetching or updating a repository
+    Git(git2::Error),
+    /// Documentation could not be built
+    Build(String),
+    /// The build command exited unsuccessfully, with the compiler errors it reported
+    BuildFailed {
+        status: String,
+        errors: Vec<String>,
+    },
+    /// The project directory lacks the file its build system starts from
+    MissingBuildFile {
+        expected: String,
+        path: PathBuf,
+    },
+    Io(std::io::Error),
+    NotFound(String),
+    /// Malformed request parameters
+    BadRequest(String),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Config(msg) => write!(f, "Configuration error: {}", msg),
+            AppError::Git(e) => write!(f, "Git error: {}", e),
+            AppError::Build(msg) => write!(f, "Build error: {}", msg),
+            AppError::BuildFailed { status, errors } => match errors.first() {
+                Some(first) => write!(f, "Build failed ({}): {}", status, first),
+                None => write!(f, "Build failed ({})", status),
+            },
+            AppError::MissingBuildFile { expected, path } => write!(
+                f,
+                "Build error: expected {} in {}",
+                expected,
+                path.display()
+            ),
+            AppError::Io(e) => write!(f, "I/O error: {}", e),
+            AppError::NotFound(what) => write!(f, "Not found: {}", what),
+            AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Git(e) => Some(e),
+            AppError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Config(_) => StatusCode::BAD_REQUEST,
+            AppError::Git(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Build(_)
+            | AppError::BuildFailed { .. }
+            | AppError::MissingBuildFile { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
+
+impl From<git2::Error> for AppError {
+    fn from(e: git2::Error) -> Self {
+        AppError::Git(e)
+    }
+}
+
+impl From<toml::de::Error> for AppError {
+    fn from(e: toml::de::Error) -> Self {
+        AppError::Config(e.to_string())
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use actix_web::HttpResponse;
+use tokio::fs;
+use tracing::warn;
+
+use crate::config::Project;
+
+/// Page doc generators ship for missing files, at the root of the docs
+const NOT_FOUND_PAGE: &str = "404.html";
+
+/// Whether each project's docs have a [`NOT_FOUND_PAGE`], looked up on the first miss after
+/// the docs last changed rather than on every miss
+#[derive(Debug, Default)]
+pub struct ErrorPages {
+    found: RwLock<HashMap<String, bool>>,
+}
+
+impl ErrorPages {
+    /// The project's own 404 page, if its docs have one it's allowed to serve
+    pub async fn not_found_page(&self, project: &Project) -> Option<PathBuf> {
+        let path = project.docs_path.join(NOT_FOUND_PAGE);
+        let cached = self
+            .found
+            .read()
+            .expect("error pages lock poisoned")
+            .get(&project.url_path)
+            .copied();
+        let found = match cached {
+            Some(found) => found,
+            None => {
+                let found = project.serve_filter.allows(Path::new(NOT_FOUND_PAGE))
+                    && fs::metadata(&path).await.is_ok_and(|m| m.is_file());
+                self.found
+                    .write()
+                    .expect("error pages lock poisoned")
+                    .insert(project.url_path.clone(), found);
+                found
+            }
+        };
+        found.then_some(path)
+    }
+
+    /// Forgets what was found for a project whose docs were replaced
+    pub fn invalidate(&self, url_path: &str) {
+        self.found
+            .write()
+            .expect("error pages lock poisoned")
+            .remove(url_path);
+    }
+
+    /// A 404 with the project's own page, or `None` to fall back to lichen's response
+    pub async fn not_found(&self, project: &Project) -> Option<HttpResponse> {
+        let path = self.not_found_page(project).await?;
+        match fs::read(&path).await {
+            Ok(page) => Some(
+                HttpResponse::NotFound()
+                    .content_type("text/html; charset=utf-8")
+                    .body(page),
+            ),
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_not_found_page_is_cached_until_invalidated() {
+        let libs = TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("404.html"), "<h1>Lost?</h1>").unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\n[[projects]]\npath = \"hidden\"\nbuild_system = \"custom\"\nserve_only = [\"*.css\"]\n",
+        )
+        .await;
+        let pages = ErrorPages::default();
+
+        assert_eq!(
+            pages.not_found_page(&projects["lib"]).await,
+            Some(docs.join("404.html"))
+        );
+        // a page the serve filter rejects isn't served as an error page either
+        std::fs::create_dir_all(libs.path().join("hidden/docs")).unwrap();
+        std::fs::write(libs.path().join("hidden/docs/404.html"), "secret").unwrap();
+        assert_eq!(pages.not_found_page(&projects["hidden"]).await, None);
+
+        std::fs::remove_file(docs.join("404.html")).unwrap();
+        assert!(pages.not_found_page(&projects["lib"]).await.is_some());
+        pages.invalidate("lib");
+        assert_eq!(pages.not_found_page(&projects["lib"]).await, None);
+    }
+}
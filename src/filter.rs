@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::error::{AppError, AppResult};
+
+/// Files never served unless a project overrides `never_serve`
+pub const DEFAULT_NEVER_SERVE: [&str; 3] = [".env", "*.key", ".git/**"];
+
+/// Directories left out of size measurements and archives unless `ignore` is overridden
+pub const DEFAULT_IGNORE: [&str; 3] = ["target/", "build/", "node_modules/"];
+
+/// Decides which files of a project's docs directory may be served
+#[derive(Debug, Clone)]
+pub struct ServeFilter {
+    /// Files outside this set are rejected; `None` allows everything
+    serve_only: Option<GlobSet>,
+    never_serve: GlobSet,
+}
+
+/// Compiles `patterns` into a set matched against paths relative to the docs directory.
+///
+/// Patterns starting with `/` are anchored at the docs directory, all others match at any
+/// depth, so `.env` also rejects `nested/.env`. `*` never crosses a `/`, use `**` for that.
+fn compile(patterns: &[String], field: &str, project: &str) -> AppResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let anchored = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None => format!("**/{}", pattern),
+        };
+        let glob = GlobBuilder::new(&anchored)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| {
+                AppError::Config(format!(
+                    "invalid {} pattern '{}' of project '{}': {}",
+                    field, pattern, project, e
+                ))
+            })?;
+        builder.add(glob);
+    }
+
+    builder
+        .build()
+        .map_err(|e| AppError::Config(format!("invalid {} of project '{}': {}", field, project, e)))
+}
+
+impl ServeFilter {
+    pub fn new(serve_only: &[String], never_serve: &[String], project: &str) -> AppResult<Self> {
+        let serve_only = if serve_only.is_empty() {
+            None
+        } else {
+            Some(compile(serve_only, "serve_only", project)?)
+        };
+
+        Ok(Self {
+            serve_only,
+            never_serve: compile(never_serve, "never_serve", project)?,
+        })
+    }
+
+    /// Returns true if the file at `path`, relative to the docs directory, may be served
+    pub fn allows(&self, path: &Path) -> bool {
+        !self.never_serve.is_match(path)
+            && self
+                .serve_only
+                .as_ref()
+                .is_none_or(|serve_only| serve_only.is_match(path))
+    }
+}
+
+/// `.gitignore`-style rules of the files left out when measuring or archiving a project's docs
+#[derive(Debug, Clone)]
+pub struct IgnoreRules(Gitignore);
+
+impl Default for IgnoreRules {
+    /// Ignores nothing
+    fn default() -> Self {
+        Self(Gitignore::empty())
+    }
+}
+
+impl IgnoreRules {
+    /// Compiles the global `ignore` rules followed by the project's own, so the project's can
+    /// override them, e.g. `!build/` to keep a directory the global rules ignore
+    pub fn new(global: &[String], project_rules: &[String], project: &str) -> AppResult<Self> {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in global.iter().chain(project_rules) {
+            builder.add_line(None, pattern).map_err(|e| {
+                AppError::Config(format!(
+                    "invalid ignore pattern '{}' of project '{}': {}",
+                    pattern, project, e
+                ))
+            })?;
+        }
+
+        builder.build().map(Self).map_err(|e| {
+            AppError::Config(format!("invalid ignore of project '{}': {}", project, e))
+        })
+    }
+
+    /// Returns true if `path`, relative to the docs directory, is ignored. Walks check
+    /// directories before descending into them, so only the path itself is matched.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.0.matched(path, is_dir).is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(serve_only: &[&str], never_serve: &[&str]) -> ServeFilter {
+        let owned = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        ServeFilter::new(&owned(serve_only), &owned(never_serve), "lib").unwrap()
+    }
+
+    fn allows(filter: &ServeFilter, path: &str) -> bool {
+        filter.allows(Path::new(path))
+    }
+
+    #[test]
+    fn test_default_never_serve() {
+        let filter = filter(&[], &DEFAULT_NEVER_SERVE);
+
+        for path in [
+            ".env",
+            "nested/dir/.env",
+            "server.key",
+            "certs/.hidden.key",
+            ".git/config",
+            ".git/refs/heads/main",
+            "vendor/lib/.git/HEAD",
+        ] {
+            assert!(!allows(&filter, path), "{} should be rejected", path);
+        }
+
+        for path in [
+            "index.html",
+            "a.env",
+            ".envrc",
+            "keys.html",
+            "server.key.html",
+            ".github/workflows/ci.yml",
+            "git/config",
+        ] {
+            assert!(allows(&filter, path), "{} should be allowed", path);
+        }
+    }
+
+    #[test]
+    fn test_serve_only() {
+        let filter = filter(&["*.html", "*.css", "/static/*.js"], &[]);
+
+        assert!(allows(&filter, "index.html"));
+        assert!(allows(&filter, "a/b/c.html"));
+        assert!(allows(&filter, "style.css"));
+        assert!(allows(&filter, "static/app.js"));
+        // `*` doesn't cross directories and anchored patterns only match at the root
+        assert!(!allows(&filter, "static/nested/app.js"));
+        assert!(!allows(&filter, "other/static/app.js"));
+        assert!(!allows(&filter, "build.sh"));
+        assert!(!allows(&filter, ".env"));
+    }
+
+    #[test]
+    fn test_never_serve_wins_over_serve_only() {
+        let filter = filter(&["*.html"], &["/private/**", "draft-*.html"]);
+
+        assert!(allows(&filter, "index.html"));
+        assert!(allows(&filter, "public/private/index.html"));
+        assert!(!allows(&filter, "private/index.html"));
+        assert!(!allows(&filter, "private/deeply/nested/index.html"));
+        assert!(!allows(&filter, "guide/draft-intro.html"));
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let owned = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let rules = IgnoreRules::new(
+            &owned(&DEFAULT_IGNORE),
+            &owned(&["*.map", "!keep.map", "/drafts", "!build/", "cache/**/*.tmp"]),
+            "lib",
+        )
+        .unwrap();
+        let ignored = |path: &str, is_dir| rules.is_ignored(Path::new(path), is_dir);
+
+        // directory-only patterns don't match files of the same name
+        assert!(ignored("target", true));
+        assert!(ignored("nested/node_modules", true));
+        assert!(!ignored("target", false));
+        // the project's negation re-includes what the global rules ignore
+        assert!(!ignored("build", true));
+        assert!(ignored("app.js.map", false));
+        assert!(ignored("nested/app.js.map", false));
+        assert!(!ignored("nested/keep.map", false));
+        // anchored patterns only match at the root
+        assert!(ignored("drafts", true));
+        assert!(!ignored("guide/drafts", true));
+        assert!(ignored("cache/a/b/file.tmp", false));
+        assert!(!ignored("other/file.tmp", false));
+
+        assert!(!IgnoreRules::default().is_ignored(Path::new("target"), true));
+        assert!(IgnoreRules::new(&[], &owned(&["a[z-a]"]), "lib").is_err());
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected() {
+        for pattern in ["[abc", "{a,b", "a[z-a]"] {
+            assert!(
+                ServeFilter::new(&[pattern.to_string()], &[], "lib").is_err(),
+                "serve_only = [{:?}] should be rejected",
+                pattern
+            );
+            assert!(
+                ServeFilter::new(&[], &[pattern.to_string()], "lib").is_err(),
+                "never_serve = [{:?}] should be rejected",
+                pattern
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+/// Lowest score of a suggestion worth showing
+const MIN_SCORE: f64 = 0.5;
+
+/// Score above which a lone match is trusted enough to redirect to
+pub const REDIRECT_SCORE: f64 = 0.85;
+
+/// Suggestions returned at most
+const MAX_MATCHES: usize = 5;
+
+/// A known name close to what was asked for
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    /// Slug the matched name leads to; aliases lead to their project
+    pub slug: &'a str,
+    pub score: f64,
+}
+
+/// Lowercases `name` and treats `_`, `.` and spaces like `-`, so `Rust_Serde` and `rust-serde`
+/// are the same name
+fn normalize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '_' | '.' | ' ' => '-',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Edit distance between `a` and `b`, in characters
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// How close `candidate` is to `query`, from 0 to 1. Equal names score 1 and a query naming one
+/// `-`-separated part of the candidate (`serde` for `rust-serde`) 0.9; anything else is scored
+/// by edit distance relative to the longer name.
+pub fn score(query: &str, candidate: &str) -> f64 {
+    let (query, candidate) = (normalize(query), normalize(candidate));
+    if query.is_empty() || candidate.is_empty() {
+        return 0.0;
+    }
+    if query == candidate {
+        return 1.0;
+    }
+    if candidate.split(['-', '/']).any(|part| part == query) {
+        return 0.9;
+    }
+
+    let longest = query.chars().count().max(candidate.chars().count());
+    1.0 - levenshtein(&query, &candidate) as f64 / longest as f64
+}
+
+/// The slugs whose names (given as `(name, slug)` pairs) are closest to `query`, best first,
+/// each slug once with the score of its best name
+pub fn best_matches<'a>(
+    query: &str,
+    candidates: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<Match<'a>> {
+    let mut matches: Vec<Match<'a>> = Vec::new();
+    for (name, slug) in candidates {
+        let score = score(query, name);
+        if score < MIN_SCORE {
+            continue;
+        }
+        match matches.iter_mut().find(|m| m.slug == slug) {
+            Some(known) => known.score = known.score.max(score),
+            None => matches.push(Match { slug, score }),
+        }
+    }
+
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.slug.cmp(b.slug)));
+    matches.truncate(MAX_MATCHES);
+    matches
+}
+
+/// The only match scoring at least [`REDIRECT_SCORE`], if there's exactly one
+pub fn unambiguous<'a, 'b>(matches: &'b [Match<'a>]) -> Option<&'b Match<'a>> {
+    let mut confident = matches.iter().filter(|m| m.score >= REDIRECT_SCORE);
+    let first = confident.next()?;
+    confident.next().is_none().then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("serde", "serde"), 0);
+        assert_eq!(levenshtein("sedre", "serde"), 2);
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("Rust_Serde", "rust-serde"), 1.0);
+        assert_eq!(score("serde", "rust-serde"), 0.9);
+        assert_eq!(score("", "rust-serde"), 0.0);
+        // one typo in a short name is still close, unrelated names aren't
+        assert!(score("tokoi", "tokio") >= MIN_SCORE);
+        assert!(score("serde", "gradle-plugin") < MIN_SCORE);
+        // a part of a word isn't a part of the name
+        assert!(score("erde", "rust-serde") < 0.9);
+    }
+
+    #[test]
+    fn test_best_matches() {
+        let candidates = [
+            ("rust-serde", "rust-serde"),
+            ("serde-old", "rust-serde"),
+            ("serde-json", "serde-json"),
+            ("tokio", "tokio"),
+        ];
+
+        let matches = best_matches("serde", candidates);
+        assert_eq!(
+            matches.iter().map(|m| m.slug).collect::<Vec<_>>(),
+            ["rust-serde", "serde-json"]
+        );
+        assert_eq!(unambiguous(&matches), None);
+
+        let matches = best_matches("rust-serd", candidates);
+        assert_eq!(unambiguous(&matches).unwrap().slug, "rust-serde");
+        assert!(best_matches("zig", candidates).is_empty());
+    }
+}
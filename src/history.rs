@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::warn;
+
+use crate::{error::AppResult, filter::IgnoreRules};
+
+const LOGS_DIR: &str = ".lichen-logs";
+const SIZE_HISTORY_FILE: &str = "size-history.jsonl";
+
+/// Size of a project's generated docs after one successful build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeRecord {
+    /// Unix timestamp (seconds) of the build
+    pub timestamp: u64,
+    pub size_bytes: u64,
+    pub commit_sha: Option<String>,
+    /// SHA-256 of the `Cargo.lock` the build used, if there was one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_lock_sha256: Option<String>,
+    /// How long the build took; missing in records written before durations were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// Most recent builds an estimate is based on, so it follows the project as it grows
+const ESTIMATE_WINDOW: usize = 20;
+/// Builds needed before an estimate can be trusted
+const MIN_CONFIDENT_SAMPLES: usize = 5;
+/// Highest standard deviation, relative to the mean, of a trusted estimate
+const MAX_CONFIDENT_VARIATION: f64 = 0.25;
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    High,
+    Low,
+}
+
+/// Expected duration of a project's next build
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RebuildEstimate {
+    /// `None` until a build with a recorded duration succeeded
+    pub estimated_seconds: Option<u64>,
+    pub confidence: Confidence,
+    pub sample_size: usize,
+}
+
+/// Estimates the next build's duration from the mean of the latest recorded ones. It's
+/// confident with enough samples that don't vary much.
+pub fn estimate_rebuild(records: &[SizeRecord]) -> RebuildEstimate {
+    let mut durations: Vec<f64> = records
+        .iter()
+        .rev()
+        .filter_map(|r| r.duration_ms)
+        .take(ESTIMATE_WINDOW)
+        .map(|ms| ms as f64 / 1000.0)
+        .collect();
+    durations.reverse();
+
+    let sample_size = durations.len();
+    if sample_size == 0 {
+        return RebuildEstimate {
+            estimated_seconds: None,
+            confidence: Confidence::Low,
+            sample_size,
+        };
+    }
+
+    let mean = durations.iter().sum::<f64>() / sample_size as f64;
+    let variance = durations.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / sample_size as f64;
+    let variation = if mean > 0.0 {
+        variance.sqrt() / mean
+    } else {
+        0.0
+    };
+
+    RebuildEstimate {
+        estimated_seconds: Some(mean.round() as u64),
+        confidence: if sample_size >= MIN_CONFIDENT_SAMPLES && variation <= MAX_CONFIDENT_VARIATION
+        {
+            Confidence::High
+        } else {
+            Confidence::Low
+        },
+        sample_size,
+    }
+}
+
+/// Directory holding everything lichen logs about a project
+pub fn project_logs_dir(libs_path: &Path, url_path: &str) -> PathBuf {
+    libs_path.join(LOGS_DIR).join(url_path)
+}
+
+fn size_history_path(libs_path: &Path, url_path: &str) -> PathBuf {
+    project_logs_dir(libs_path, url_path).join(SIZE_HISTORY_FILE)
+}
+
+/// Total size in bytes of all files below `path`, without following symlinks
+pub async fn dir_size(path: &Path) -> AppResult<u64> {
+    docs_size(path, &IgnoreRules::default()).await
+}
+
+/// Like [`dir_size`], leaving out what `ignore` matches, including whole ignored directories
+pub async fn docs_size(path: &Path, ignore: &IgnoreRules) -> AppResult<u64> {
+    let mut total = 0;
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(path.join(&dir)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let relative = dir.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if ignore.is_ignored(&relative, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(relative);
+            } else if file_type.is_file() {
+                total += entry.metadata().await?.len();
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Appends a record to the project's `size-history.jsonl`, creating it if needed
+pub async fn append_size_record(
+    libs_path: &Path,
+    url_path: &str,
+    record: &SizeRecord,
+) -> AppResult<()> {
+    let path = size_history_path(libs_path, url_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let mut line = serde_json::to_string(record).map_err(std::io::Error::from)?;
+    line.push('\n');
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// Reads a project's size history, oldest first, keeping records at or after `since`
+pub async fn load_size_history(
+    libs_path: &Path,
+    url_path: &str,
+    since: Option<u64>,
+) -> AppResult<Vec<SizeRecord>> {
+    let path = size_history_path(libs_path, url_path);
+    let contents = match fs::read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let records = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<SizeRecord>(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!("Skipping invalid line in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .filter(|record| since.is_none_or(|since| record.timestamp >= since))
+        .collect();
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(durations: &[Option<u64>]) -> Vec<SizeRecord> {
+        durations
+            .iter()
+            .enumerate()
+            .map(|(i, &duration_ms)| SizeRecord {
+                timestamp: i as u64,
+                size_bytes: 1024,
+                commit_sha: None,
+                cargo_lock_sha256: None,
+                duration_ms,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_rebuild_without_durations() {
+        assert_eq!(
+            estimate_rebuild(&records(&[None, None])),
+            RebuildEstimate {
+                estimated_seconds: None,
+                confidence: Confidence::Low,
+                sample_size: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimate_rebuild_confidence() {
+        let steady = records(&[
+            None,
+            Some(44_000),
+            Some(46_000),
+            Some(45_000),
+            Some(44_500),
+            Some(45_500),
+        ]);
+        assert_eq!(
+            estimate_rebuild(&steady),
+            RebuildEstimate {
+                estimated_seconds: Some(45),
+                confidence: Confidence::High,
+                sample_size: 5,
+            }
+        );
+
+        // too few samples
+        assert_eq!(estimate_rebuild(&steady[..4]).confidence, Confidence::Low);
+
+        // varying too much
+        let erratic = records(&[
+            Some(5_000),
+            Some(90_000),
+            Some(10_000),
+            Some(60_000),
+            Some(30_000),
+        ]);
+        assert_eq!(estimate_rebuild(&erratic).confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn test_estimate_rebuild_uses_latest_builds() {
+        let mut durations = vec![Some(600_000); 10];
+        durations.extend([Some(10_000); ESTIMATE_WINDOW]);
+        let estimate = estimate_rebuild(&records(&durations));
+        assert_eq!(estimate.estimated_seconds, Some(10));
+        assert_eq!(estimate.sample_size, ESTIMATE_WINDOW);
+    }
+
+    #[tokio::test]
+    async fn test_docs_size_skips_ignored() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for (file, size) in [
+            ("index.html", 10),
+            ("node_modules/pkg/index.js", 100),
+            ("guide/app.js.map", 1000),
+            ("guide/page.html", 5),
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x".repeat(size)).unwrap();
+        }
+
+        assert_eq!(dir_size(dir.path()).await.unwrap(), 1115);
+        let ignore = IgnoreRules::new(
+            &["node_modules/".to_string()],
+            &["*.map".to_string()],
+            "lib",
+        )
+        .unwrap();
+        assert_eq!(docs_size(dir.path(), &ignore).await.unwrap(), 15);
+    }
+}
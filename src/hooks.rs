@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::error::{AppError, AppResult};
+
+/// How long a webhook may take before it's abandoned
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An HTTP endpoint notified when a project's build completes
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Request body; `{{project}}`, `{{status}}`, `{{duration_ms}}` and `{{commit_sha}}` are
+    /// replaced with details of the build
+    #[serde(default)]
+    pub body_template: String,
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_content_type() -> String {
+    "application/json".to_string()
+}
+
+impl WebhookConfig {
+    fn http_method(&self) -> AppResult<reqwest::Method> {
+        reqwest::Method::from_bytes(self.method.to_ascii_uppercase().as_bytes()).map_err(|_| {
+            AppError::Config(format!(
+                "invalid method '{}' for build hook {}",
+                self.method, self.url
+            ))
+        })
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        self.http_method().map(|_| ())
+    }
+}
+
+/// Details of a finished build, available to `body_template`
+#[derive(Debug)]
+pub struct BuildEvent<'a> {
+    pub project: &'a str,
+    pub success: bool,
+    pub duration: Duration,
+    pub commit_sha: Option<&'a str>,
+}
+
+impl BuildEvent<'_> {
+    /// Fills in `template`; with `json`, text values are escaped to fit inside JSON strings
+    fn render(&self, template: &str, json: bool) -> String {
+        let text = |value: &str| {
+            if json {
+                let quoted = serde_json::Value::from(value).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            } else {
+                value.to_string()
+            }
+        };
+        template
+            .replace("{{project}}", &text(self.project))
+            .replace(
+                "{{status}}",
+                if self.success { "success" } else { "failure" },
+            )
+            .replace("{{duration_ms}}", &self.duration.as_millis().to_string())
+            .replace("{{commit_sha}}", &text(self.commit_sha.unwrap_or_default()))
+    }
+}
+
+/// Sends `event` to every hook in the background; failures are only logged. One-shot commands
+/// await the returned tasks before exiting, which [`HOOK_TIMEOUT`] keeps short.
+pub fn notify(hooks: &[WebhookConfig], event: &BuildEvent) -> Vec<JoinHandle<()>> {
+    let mut tasks = Vec::with_capacity(hooks.len());
+    for hook in hooks {
+        let method = match hook.http_method() {
+            Ok(method) => method,
+            Err(e) => {
+                warn!("Skipping build hook: {}", e);
+                continue;
+            }
+        };
+        let url = hook.url.clone();
+        let content_type = hook.content_type.clone();
+        let json = hook.content_type.to_ascii_lowercase().contains("json");
+        let body = event.render(&hook.body_template, json);
+
+        tasks.push(tokio::spawn(async move {
+            let result = async {
+                reqwest::Client::builder()
+                    .timeout(HOOK_TIMEOUT)
+                    .build()?
+                    .request(method, &url)
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(body)
+                    .send()
+                    .await?
+                    .error_for_status()
+            }
+            .await;
+
+            match result {
+                Ok(response) => debug!("Build hook {} answered {}", url, response.status()),
+                Err(e) => warn!("Build hook {} failed: {}", url, e),
+            }
+        }));
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let event = BuildEvent {
+            project: "team/\"quoted\"\\lib",
+            success: false,
+            duration: Duration::from_millis(1500),
+            commit_sha: None,
+        };
+        let template =
+            r#"{"text": "{{project}}: {{status}} in {{duration_ms}}ms at {{commit_sha}}"}"#;
+
+        let json = event.render(template, true);
+        assert_eq!(
+            json,
+            r#"{"text": "team/\"quoted\"\\lib: failure in 1500ms at "}"#
+        );
+        assert!(serde_json::from_str::<serde_json::Value>(&json).is_ok());
+
+        assert_eq!(event.render("{{project}}", false), "team/\"quoted\"\\lib");
+    }
+}
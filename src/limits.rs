@@ -0,0 +1,144 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bounds on the work lichen does itself while serving, like generating archives
+#[derive(Debug, Deserialize)]
+pub struct LimitsConfig {
+    /// Heavy operations running at the same time
+    #[serde(default = "default_max_heavy_operations")]
+    pub max_heavy_operations: usize,
+    /// Heavy operations waiting for a slot before new ones are turned away
+    #[serde(default = "default_max_queued_heavy_operations")]
+    pub max_queued_heavy_operations: usize,
+    /// Size of a docs archive uploaded through the API, compressed
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: u64,
+    /// Size of the files in an uploaded docs archive, once extracted
+    #[serde(default = "default_max_extracted_bytes")]
+    pub max_extracted_bytes: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_heavy_operations: default_max_heavy_operations(),
+            max_queued_heavy_operations: default_max_queued_heavy_operations(),
+            max_upload_bytes: default_max_upload_bytes(),
+            max_extracted_bytes: default_max_extracted_bytes(),
+        }
+    }
+}
+
+fn default_max_heavy_operations() -> usize {
+    2
+}
+
+fn default_max_queued_heavy_operations() -> usize {
+    4
+}
+
+fn default_max_upload_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_max_extracted_bytes() -> u64 {
+    2 * 1024 * 1024 * 1024
+}
+
+/// Counts an operation as in flight until dropped, including while it's queued
+#[derive(Debug)]
+struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A slot for one heavy operation, released when dropped
+#[derive(Debug)]
+pub struct HeavyPermit {
+    _permit: OwnedSemaphorePermit,
+    _in_flight: InFlight,
+}
+
+/// Shared by every operation that walks or reads a whole docs tree, so a few large requests
+/// can't exhaust memory or file descriptors between them
+#[derive(Debug)]
+pub struct HeavyOps {
+    limit: Arc<Semaphore>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: usize,
+}
+
+impl HeavyOps {
+    pub fn new(config: &LimitsConfig) -> Self {
+        let running = config.max_heavy_operations.max(1);
+        Self {
+            limit: Arc::new(Semaphore::new(running)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight: running + config.max_queued_heavy_operations,
+        }
+    }
+
+    /// Waits for a slot, or returns `None` right away if too many operations are already
+    /// running or queued
+    pub async fn acquire(&self) -> Option<HeavyPermit> {
+        let in_flight = InFlight(self.in_flight.clone());
+        if self.in_flight.fetch_add(1, Ordering::SeqCst) >= self.max_in_flight {
+            return None;
+        }
+
+        let permit = self.limit.clone().acquire_owned().await.ok()?;
+        Some(HeavyPermit {
+            _permit: permit,
+            _in_flight: in_flight,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rejects_above_limit() {
+        let ops = HeavyOps::new(&LimitsConfig {
+            max_heavy_operations: 1,
+            max_queued_heavy_operations: 0,
+            ..LimitsConfig::default()
+        });
+
+        let first = ops.acquire().await;
+        assert!(first.is_some());
+        assert!(ops.acquire().await.is_none());
+
+        drop(first);
+        assert!(ops.acquire().await.is_some());
+        assert_eq!(ops.in_flight.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queues_until_a_slot_frees_up() {
+        let ops = Arc::new(HeavyOps::new(&LimitsConfig {
+            max_heavy_operations: 1,
+            max_queued_heavy_operations: 1,
+            ..LimitsConfig::default()
+        }));
+
+        let first = ops.acquire().await.unwrap();
+        let queued = tokio::spawn({
+            let ops = ops.clone();
+            async move { ops.acquire().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+
+        drop(first);
+        assert!(queued.await.unwrap());
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use crate::{
+    artifact,
+    config::Project,
+    error::AppResult,
+    filter::IgnoreRules,
+    utils::{path_from_bytes, url_path},
+};
+
+/// Broken links kept per project, so a badly broken build doesn't bloat the state file
+const MAX_BROKEN_LINKS: usize = 1000;
+
+/// `href` of `<a>` tags, quoted either way
+static HREF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<a\s[^>]*?\bhref\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("valid regex")
+});
+
+/// A link from one page of the docs to a file that isn't served
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokenLink {
+    /// Page the link is on, relative to the docs directory
+    pub source: String,
+    /// File the link resolves to, relative to the docs directory
+    pub target: String,
+    /// How many times the page links there
+    pub count: usize,
+}
+
+/// Decodes `%XX` escapes, leaving malformed ones as they are. The result can be a file name
+/// that isn't UTF-8.
+fn percent_decode(text: &str) -> PathBuf {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    path_from_bytes(decoded)
+}
+
+/// The file below the docs directory an `href` on `source` points to, or `None` for links
+/// leaving the docs: other sites, other projects, `mailto:` and the like
+fn resolve(href: &str, source: &Path, url_path: &str) -> Option<PathBuf> {
+    let href = href.trim();
+    let href = href.split(['#', '?']).next().unwrap_or_default();
+    if href.is_empty() || href.starts_with("//") || href.contains(':') {
+        return None;
+    }
+
+    // root-relative links are the same origin, but only those under the slug are these docs
+    let (base, path) = match href.strip_prefix('/') {
+        Some(absolute) => (
+            PathBuf::new(),
+            absolute.strip_prefix(url_path)?.strip_prefix('/')?,
+        ),
+        None => (source.parent().unwrap_or(Path::new("")).to_path_buf(), href),
+    };
+
+    let mut resolved = base;
+    for component in percent_decode(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Finds the links of the served HTML pages of a project pointing to files inside its docs
+/// that aren't served, counting repeats on the same page once with their number
+pub async fn check(project: &Project) -> AppResult<Vec<BrokenLink>> {
+    let files = artifact::list_files(
+        &project.docs_path,
+        &project.serve_filter,
+        &IgnoreRules::default(),
+    )
+    .await?;
+    let served = files.iter().collect::<HashSet<_>>();
+    let index_file = project.index_file_name();
+
+    let mut broken = BTreeMap::new();
+    for source in &files {
+        let is_html = source
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+        if !is_html {
+            continue;
+        }
+
+        let contents = fs::read(project.docs_path.join(source)).await?;
+        let contents = String::from_utf8_lossy(&contents);
+        for captures in HREF.captures_iter(&contents) {
+            let href = captures
+                .get(1)
+                .or(captures.get(2))
+                .map_or("", |m| m.as_str());
+            let Some(target) = resolve(&html_unescape(href), source, &project.url_path) else {
+                continue;
+            };
+            // directories are served through their index file
+            if served.contains(&target) || served.contains(&target.join(index_file)) {
+                continue;
+            }
+            *broken
+                .entry((url_path(source), url_path(&target)))
+                .or_insert(0) += 1;
+        }
+    }
+
+    Ok(broken
+        .into_iter()
+        .take(MAX_BROKEN_LINKS)
+        .map(|((source, target), count)| BrokenLink {
+            source,
+            target,
+            count,
+        })
+        .collect())
+}
+
+/// Runs [`check`] for freshly built docs, logging what it found. `None` if the docs couldn't
+/// be read, which doesn't fail the build.
+pub async fn after_build(project: &Project) -> Option<Vec<BrokenLink>> {
+    match check(project).await {
+        Ok(links) => {
+            if !links.is_empty() {
+                warn!(
+                    "Found {} broken links in the docs of {}",
+                    links.iter().map(|l| l.count).sum::<usize>(),
+                    project.config.path
+                );
+            }
+            Some(links)
+        }
+        Err(e) => {
+            warn!("Failed to check links of {}: {}", project.config.path, e);
+            None
+        }
+    }
+}
+
+/// The entities generators escape URLs with
+fn html_unescape(href: &str) -> String {
+    href.replace("&amp;", "&")
+        .replace("&#38;", "&")
+        .replace("&#x2F;", "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let page = Path::new("guide/intro.html");
+        let resolve = |href| resolve(href, page, "lib");
+
+        assert_eq!(resolve("next.html#top"), Some("guide/next.html".into()));
+        assert_eq!(resolve("../index.html?q=1"), Some("index.html".into()));
+        assert_eq!(resolve("./a%20b.html"), Some("guide/a b.html".into()));
+        assert_eq!(resolve("/lib/api/"), Some("api".into()));
+        assert_eq!(resolve("sub/"), Some("guide/sub".into()));
+        for external in [
+            "#section",
+            "",
+            "https://example.com/lib/index.html",
+            "//cdn.example.com/app.js",
+            "mailto:docs@example.com",
+            "/other/index.html",
+            "/library/index.html",
+            "../../outside.html",
+        ] {
+            assert_eq!(resolve(external), None, "{}", external);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_non_utf8_name() {
+        // a Latin-1 `café.html`, linked to the way a generator would
+        assert_eq!(
+            resolve("caf%E9.html", Path::new("index.html"), "lib"),
+            Some(path_from_bytes(b"caf\xe9.html".to_vec()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let libs = tempfile::TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(
+            docs.join("index.html"),
+            r#"<a href="guide/">Guide</a> <a class="x" href='missing.html'>1</a>
+            <A HREF="missing.html#a">2</A> <a href="https://example.com/">site</a>
+            <a href="deploy.key">key</a>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            docs.join("guide/index.html"),
+            r#"<a href="../index.html">up</a> <a href="/lib/gone/">gone</a>"#,
+        )
+        .unwrap();
+        std::fs::write(docs.join("deploy.key"), "secret").unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\n",
+        )
+        .await;
+
+        let link = |source: &str, target: &str, count| BrokenLink {
+            source: source.to_string(),
+            target: target.to_string(),
+            count,
+        };
+        assert_eq!(
+            check(&projects["lib"]).await.unwrap(),
+            [
+                link("guide/index.html", "gone", 1),
+                // never_serve hides the key, so linking to it is broken too
+                link("index.html", "deploy.key", 1),
+                link("index.html", "missing.html", 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fs, path::Path, sync::Mutex};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+use crate::error::{AppError, AppResult};
+
+/// Environment variable holding `RUST_LOG`-style directives, e.g. `debug` or `git=trace`
+const LOG_ENV: &str = "LICHEN_LOG";
+
+/// Modules that may be named without the `lichen::` prefix in [`LOG_ENV`], every `mod` of
+/// `main.rs`, which a test keeps this in sync with
+const MODULES: &[&str] = &[
+    "access",
+    "api",
+    "app",
+    "artifact",
+    "badges",
+    "build_files",
+    "cargo",
+    "clean",
+    "cli",
+    "config",
+    "copy",
+    "daemon",
+    "docs_index",
+    "dotnet",
+    "error",
+    "error_pages",
+    "filter",
+    "fuzzy",
+    "git",
+    "history",
+    "hooks",
+    "limits",
+    "links",
+    "logging",
+    "maintenance",
+    "manifest",
+    "metrics",
+    "pipeline",
+    "portal",
+    "prebuild",
+    "precompress",
+    "proxy",
+    "pydoc",
+    "report",
+    "rewrite",
+    "sandbox",
+    "shutdown",
+    "snapshots",
+    "state",
+    "tls",
+    "upload",
+    "utils",
+    "versions",
+    "zig",
+];
+
+/// Level requested with `-q`, `-v` or `-vv`, if any
+pub fn cli_level(verbose: u8, quiet: bool) -> Option<LevelFilter> {
+    match (quiet, verbose) {
+        (true, _) => Some(LevelFilter::WARN),
+        (false, 0) => None,
+        (false, 1) => Some(LevelFilter::DEBUG),
+        (false, _) => Some(LevelFilter::TRACE),
+    }
+}
+
+/// `target` with the `lichen::` prefix if it starts with one of [`MODULES`], so submodules
+/// like `zig::toolchain` can be named directly too
+fn qualify(target: &str) -> String {
+    let module = target.split("::").next().unwrap_or_default();
+    if MODULES.contains(&module) {
+        format!("lichen::{}", target)
+    } else {
+        target.to_string()
+    }
+}
+
+/// Installs the global subscriber. The default level comes from the command line, then a
+/// bare level in [`LOG_ENV`], then `log_level` in the config; per-target directives from
+/// [`LOG_ENV`] always apply. Access logs go through the same filter, so `-q` silences them.
+///
+/// Logs are appended to `file` instead of stdout when one is given.
+pub fn init(cli: Option<LevelFilter>, config: Option<&str>, file: Option<&Path>) -> AppResult<()> {
+    let env = std::env::var(LOG_ENV).unwrap_or_default();
+
+    let mut env_level = None;
+    let mut targets = Vec::new();
+    for directive in env.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => targets.push(format!("{}={}", qualify(target), level)),
+            None => env_level = Some(directive.to_string()),
+        }
+    }
+
+    let level = cli
+        .map(|l| l.to_string())
+        .or(env_level)
+        .or_else(|| config.map(str::to_string))
+        .unwrap_or_else(|| LevelFilter::INFO.to_string());
+    let directives = std::iter::once(level)
+        .chain(targets)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let filter = EnvFilter::try_new(&directives)
+        .map_err(|e| AppError::Config(format!("invalid log filter '{}': {}", directives, e)))?;
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match file {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            subscriber
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+        None => subscriber.init(),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modules_match_main() {
+        let declared = include_str!("main.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("mod ")?.strip_suffix(';'))
+            .collect::<Vec<_>>();
+        assert_eq!(MODULES, declared);
+    }
+
+    #[test]
+    fn test_qualify() {
+        assert_eq!(qualify("upload"), "lichen::upload");
+        assert_eq!(qualify("zig::toolchain"), "lichen::zig::toolchain");
+        assert_eq!(qualify("lichen::git"), "lichen::git");
+        assert_eq!(qualify("actix_web"), "actix_web");
+        assert_eq!(qualify("upload_helper"), "upload_helper");
+    }
+}
@@ -1,126 +1,362 @@
+mod access;
+mod api;
+mod app;
+mod artifact;
+mod badges;
+mod build_files;
+mod cargo;
+mod clean;
+mod cli;
+mod config;
+mod copy;
+#[cfg(unix)]
+mod daemon;
+mod docs_index;
 mod dotnet;
+mod error;
+mod error_pages;
+mod filter;
+mod fuzzy;
+mod git;
+mod history;
+mod hooks;
+mod limits;
+mod links;
+mod logging;
+mod maintenance;
+mod manifest;
+mod metrics;
+mod pipeline;
+mod portal;
+mod prebuild;
+mod precompress;
+mod proxy;
+mod pydoc;
+mod report;
+mod rewrite;
+mod sandbox;
+mod shutdown;
+mod snapshots;
+mod state;
+mod tls;
+mod upload;
+mod utils;
+mod versions;
 mod zig;
 
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    process::{ExitCode, Stdio},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use actix_files::Files;
-use actix_web::{get, middleware, web, App, HttpResponse, HttpServer, Responder};
-use serde::Deserialize;
-use tokio::fs;
-use tracing::{error, info, warn, Level};
+use actix_files::NamedFile;
+use actix_web::{
+    get,
+    http::{
+        header::{self, ContentDisposition, DispositionParam, DispositionType},
+        Method,
+    },
+    middleware, web, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use serde::Serialize;
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::ChildStderr,
+    sync::RwLock,
+};
+use tracing::{debug, error, info, warn};
 
-type AppResult<T> = Result<T, Box<dyn std::error::Error>>;
+use crate::{
+    access::AccessTimes,
+    api::ProjectSummary,
+    badges::BadgeProxy,
+    cli::{BenchResult, Cli, CliCommand, DryRunPlan, Targets},
+    config::{
+        config_path, initialize_projects, load_config, BuildSystem, Config, IndexSort, Project,
+        ProjectConfig,
+    },
+    error::{AppError, AppResult},
+    error_pages::ErrorPages,
+    git::{describe_update, CommitInfo},
+    history::{append_size_record, docs_size, SizeRecord},
+    limits::HeavyOps,
+    maintenance::Maintenance,
+    metrics::ConnectionStats,
+    pipeline::{process_project, ProjectOutcome},
+    report::{CommandRun, Trigger},
+    sandbox::SandboxConfig,
+    state::{format_timestamp, now_secs, BuildState},
+    utils::{alias_location, escape_html, favicon_svg, format_size, sanitize_path, with_query},
+};
 
-#[derive(Debug, Deserialize)]
-struct Config {
-    libs_path: PathBuf,
-    #[serde(default = "default_port")]
-    port: u16,
-    #[serde(default)]
-    update_on_start: bool,
-    projects: Vec<ProjectConfig>,
+#[derive(Debug)]
+#[allow(dead_code)]
+struct AppState {
+    projects: HashMap<String, Project>,
+    base_path: PathBuf,
+    /// Written to when docs are uploaded through the API
+    build_state: RwLock<BuildState>,
+    /// What the `update_on_start` pass did to each project, by slug
+    startup: HashMap<String, ProjectOutcome>,
+    maintenance: Maintenance,
+    heavy_ops: HeavyOps,
+    /// `limits.max_upload_bytes`
+    max_upload_bytes: u64,
+    /// `limits.max_extracted_bytes`
+    max_extracted_bytes: u64,
+    badge_proxy: BadgeProxy,
+    index_sort: IndexSort,
+    redirect_unknown_slugs: bool,
+    access: AccessTimes,
+    error_pages: ErrorPages,
+    /// Connections of the public listener, for `/metrics`
+    connections: Arc<ConnectionStats>,
+    /// SVG generated from `favicon_color` at startup
+    favicon: String,
 }
 
-fn default_port() -> u16 {
-    8080
+impl AppState {
+    fn new(
+        config: &Config,
+        projects: HashMap<String, Project>,
+        build_state: BuildState,
+        startup: HashMap<String, ProjectOutcome>,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            access: AccessTimes::new(projects.keys(), &build_state),
+            projects,
+            base_path: config.libs_path.clone(),
+            build_state: RwLock::new(build_state),
+            startup,
+            maintenance: Maintenance::new(&config.maintenance),
+            heavy_ops: HeavyOps::new(&config.limits),
+            max_upload_bytes: config.limits.max_upload_bytes,
+            max_extracted_bytes: config.limits.max_extracted_bytes,
+            badge_proxy: BadgeProxy::new(&config.badge_proxy_hosts)?,
+            index_sort: config.index_sort,
+            redirect_unknown_slugs: config.redirect_unknown_slugs,
+            error_pages: ErrorPages::default(),
+            connections: Arc::default(),
+            favicon: favicon_svg(&config.favicon_color),
+        })
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct ProjectConfig {
-    path: String,
-    repo: Option<String>,
-    build_system: BuildSystem,
-    #[serde(default)]
-    build_command: Option<String>,
-}
+/// Picks an icon for the index listing based on what the project serves
+fn entry_icon(entry_file: Option<&str>) -> &'static str {
+    let extension = entry_file
+        .and_then(|f| Path::new(f).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all = "lowercase")]
-enum BuildSystem {
-    Gradle,
-    Cargo,
-    Zig,
-    DotNet,
-    Custom,
+    match extension.as_deref() {
+        None => "&#128214;",                       // open book: regular HTML docs
+        Some("pdf") => "&#128213;",                // closed book
+        Some("epub" | "mobi") => "&#128218;",      // books
+        Some("html" | "htm") => "&#128196;",       // page
+        Some("txt" | "md") => "&#128221;",         // memo
+        Some("zip" | "tar" | "gz") => "&#128230;", // package
+        Some(_) => "&#128206;",                    // paperclip
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Project {
-    config: ProjectConfig,
-    docs_path: PathBuf,
-    url_path: String,
+fn check_if_tool_exists(project: &ProjectConfig, local_wrapper: Option<PathBuf>) -> bool {
+    match project.build_system {
+        BuildSystem::Cargo => which::which("cargo").is_ok(),
+        BuildSystem::Gradle => which::which("gradle").is_ok() || local_wrapper.is_some(),
+        BuildSystem::Zig => which::which("zig").is_ok(),
+        BuildSystem::DotNet => which::which("dotnet").is_ok(),
+        BuildSystem::Pydoc => which::which(project.python()).is_ok(),
+        BuildSystem::Vuepress => which::which("npm").is_ok(),
+        BuildSystem::Docusaurus => which::which("npx").is_ok(),
+        BuildSystem::Custom | BuildSystem::None => true,
+    }
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-struct AppState {
-    projects: HashMap<String, Project>,
-    base_path: PathBuf,
+/// A single process run while building a project's docs
+#[derive(Debug, Serialize)]
+struct BuildCommand {
+    program: PathBuf,
+    args: Vec<String>,
+    /// Working directory; the environment is inherited from lichen
+    cwd: PathBuf,
 }
 
-fn sanitize_path(path: &str) -> String {
-    let mut sanitized = String::with_capacity(path.len());
-    let mut last_was_dash = false;
-
-    for c in path.chars() {
-        if c.is_ascii_alphanumeric() {
-            sanitized.push(c.to_ascii_lowercase());
-            last_was_dash = false;
-        } else if !last_was_dash {
-            sanitized.push('-');
-            last_was_dash = true;
+impl BuildCommand {
+    fn new(program: impl Into<PathBuf>, args: &[&str], cwd: &Path) -> Self {
+        Self {
+            program: program.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            cwd: cwd.to_path_buf(),
         }
     }
+}
 
-    // trim trailing dash if exists
-    if sanitized.ends_with('-') {
-        sanitized.pop();
+impl std::fmt::Display for BuildCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "(cd {} && {}",
+            self.cwd.display(),
+            self.program.display()
+        )?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        write!(f, ")")
     }
-
-    sanitized
 }
 
-async fn update_project(path: &Path, repo_url: &str) -> AppResult<()> {
-    let repo = git2::Repository::open(path).or_else(|_| git2::Repository::clone(repo_url, path))?;
+/// The commands [`build_docs`] runs for a project, in order
+async fn build_commands(project: &ProjectConfig, base_path: &Path) -> AppResult<Vec<BuildCommand>> {
+    let project_path = base_path.join(&project.path);
 
-    repo.find_remote("origin")?
-        .fetch(&["main", "master"], None, None)?;
+    let commands = match project.build_system {
+        BuildSystem::Gradle => {
+            let gradlew = project_path.join("gradlew");
+            let program = if gradlew.exists() {
+                gradlew
+            } else {
+                PathBuf::from("gradle")
+            };
+            vec![BuildCommand::new(
+                program,
+                &project.gradle_args(),
+                &project_path,
+            )]
+        }
+        BuildSystem::Cargo => vec![BuildCommand::new("cargo", &["doc"], &project_path)],
+        BuildSystem::Zig => {
+            let zig_project = zig::library::find_project(&project_path)
+                .await
+                .ok_or_else(|| AppError::Build("No main zig file found".into()))?;
+            debug!(
+                "Zig package {} {} rooted at {}",
+                zig_project.name,
+                zig_project.version.as_deref().unwrap_or("(unversioned)"),
+                zig_project.root_file.display()
+            );
 
-    let fetch_head = repo.find_reference("FETCH_HEAD")?;
-    let commit = repo.reference_to_annotated_commit(&fetch_head)?;
-    let analysis = repo.merge_analysis(&[&commit])?;
+            // without a usable zig the build fails later on, with a clearer error
+            let version = zig::toolchain::installed_version().await.ok();
+            if let (Some(installed), Some(min)) = (version, project.min_zig_version()?)
+                && *installed < min
+            {
+                return Err(AppError::Build(format!(
+                    "zig {} is installed, but {} needs at least {}",
+                    installed, project.path, min
+                )));
+            }
+            let flags = version.map(|v| v.docs_flags()).unwrap_or_default();
+            let command = |emit_docs: &str, root_file: &Path| {
+                let root_file = root_file.to_string_lossy();
+                let mut args = vec!["build-lib", emit_docs];
+                args.extend(flags);
+                args.push(&root_file);
+                BuildCommand::new("zig", &args, &project_path)
+            };
+
+            // packages exposing several modules get docs/{module}/ for each of them
+            let modules = zig::library::get_modules(&project_path).await;
+            if modules.len() > 1 {
+                modules
+                    .iter()
+                    .map(|module| {
+                        command(
+                            &format!("-femit-docs=docs/{}", module.name),
+                            &module.root_file,
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![command("-femit-docs", &zig_project.root_file)]
+            }
+        }
+        BuildSystem::Pydoc => {
+            let modules = pydoc::find_modules(&project_path).await;
+            if modules.is_empty() {
+                return Err(AppError::Build(format!(
+                    "No Python modules found in {}/",
+                    pydoc::SOURCE_DIR
+                )));
+            }
+            // run inside the sources, so the modules import without installing the package
+            let source_dir = project_path.join(pydoc::SOURCE_DIR);
+            modules
+                .iter()
+                .map(|module| {
+                    BuildCommand::new(
+                        project.python(),
+                        &["-m", "pydoc", "-w", module.as_str()],
+                        &source_dir,
+                    )
+                })
+                .collect()
+        }
+        BuildSystem::Vuepress => vec![BuildCommand::new("npm", &["run", "build"], &project_path)],
+        BuildSystem::Docusaurus => vec![BuildCommand::new(
+            "npx",
+            &["docusaurus", "build"],
+            &project_path,
+        )],
+        BuildSystem::DotNet | BuildSystem::None => Vec::new(),
+        BuildSystem::Custom => project
+            .custom_commands()
+            .into_iter()
+            .filter_map(|command| {
+                if project.spawn_shell.unwrap_or(false) {
+                    return Some(shell_command(command, &project_path));
+                }
+                let parts = command.split_whitespace().collect::<Vec<_>>();
+                let (program, args) = parts.split_first()?;
+                Some(BuildCommand::new(*program, args, &project_path))
+            })
+            .collect(),
+    };
+
+    Ok(commands)
+}
 
-    if analysis.0.is_up_to_date() {
-        info!("Repository at {} is up-to-date", path.display());
-    } else if analysis.0.is_fast_forward() {
-        let mut reference = repo.find_reference("refs/heads/main")?;
-        reference.set_target(commit.id(), "Fast-Forward")?;
-        repo.set_head(reference.name().unwrap())?;
-        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
-        info!("Fast-forwarded repository at {}", path.display());
+/// `command` run through the platform's shell, for projects with `spawn_shell`
+fn shell_command(command: &str, cwd: &Path) -> BuildCommand {
+    if cfg!(windows) {
+        BuildCommand::new("cmd.exe", &["/C", command], cwd)
     } else {
-        return Err("Non-fast-forward update required".into());
+        BuildCommand::new("/bin/sh", &["-c", command], cwd)
     }
-
-    Ok(())
 }
 
-fn check_if_tool_exists(build_system: &BuildSystem, local_wrapper: Option<PathBuf>) -> bool {
-    match build_system {
-        BuildSystem::Cargo => which::which("cargo").is_ok(),
-        BuildSystem::Gradle => which::which("gradle").is_ok() || local_wrapper.is_some(),
-        BuildSystem::Zig => which::which("zig").is_ok(),
-        BuildSystem::DotNet => which::which("dotnet").is_ok(),
-        BuildSystem::Custom => true,
+/// Copies a build's stderr to lichen's own line by line as it comes, so progress and warnings
+/// show up while the build runs, and returns all of it
+async fn tee_stderr(stderr: Option<ChildStderr>) -> std::io::Result<Vec<u8>> {
+    let Some(stderr) = stderr else {
+        return Ok(Vec::new());
+    };
+    let mut reader = BufReader::new(stderr);
+    let mut out = tokio::io::stderr();
+    let mut captured = Vec::new();
+    loop {
+        let start = captured.len();
+        if reader.read_until(b'\n', &mut captured).await? == 0 {
+            return Ok(captured);
+        }
+        out.write_all(&captured[start..]).await?;
     }
 }
 
-async fn build_docs(project: &ProjectConfig, base_path: &Path) -> AppResult<()> {
+/// Builds a project's docs, appending each build command to `runs` once it exits
+async fn build_docs(
+    project: &ProjectConfig,
+    base_path: &Path,
+    sandbox: &SandboxConfig,
+    runs: &mut Vec<CommandRun>,
+) -> AppResult<()> {
     let project_path = base_path.join(&project.path);
+    build_files::check(&project.build_system, &project_path).await?;
 
     let wrapper = if let BuildSystem::Gradle = project.build_system {
         Some(project_path.join("gradlew"))
@@ -128,118 +364,433 @@ async fn build_docs(project: &ProjectConfig, base_path: &Path) -> AppResult<()>
         None
     };
 
-    if !check_if_tool_exists(&project.build_system, wrapper) {
-        return Err("Build tool doesn't exist.".into());
+    if !check_if_tool_exists(project, wrapper) {
+        return Err(AppError::Build("Build tool doesn't exist.".into()));
     }
 
-    match project.build_system {
-        BuildSystem::Gradle => {
-            let gradlew = project_path.join("gradlew");
-            tokio::process::Command::new(if gradlew.exists() {
-                gradlew
-            } else {
-                PathBuf::from("gradle")
-            })
-            .args(&["clean", "javadoc"])
-            .current_dir(&project_path)
-            .status()
-            .await?;
-        }
-        BuildSystem::Cargo => {
-            tokio::process::Command::new("cargo")
-                .arg("doc")
-                .current_dir(&project_path)
-                .status()
-                .await?;
-        }
-        BuildSystem::Zig => {
-            let main_file = zig::library::get_root_file(&project_path)
-                .await
-                .ok_or("No main zig file found")?;
-            tokio::process::Command::new("zig")
-                .args(&["build-lib", "-femit-docs", &main_file.to_string_lossy()])
-                .current_dir(&project_path)
-                .status()
-                .await?;
+    let sandboxed = sandbox.is_active()?;
+    prebuild::run(project, &project_path, sandbox, sandboxed).await?;
+    for command in build_commands(project, base_path).await? {
+        let command = if sandboxed {
+            sandbox.wrap(command, &project_path)
+        } else {
+            command
+        };
+        debug!("Running {}", command);
+        let mut process = tokio::process::Command::new(&command.program);
+        process.args(&command.args).current_dir(&command.cwd);
+
+        let (status, errors) = if let BuildSystem::Cargo = project.build_system {
+            // stderr is also kept to extract the compiler errors of a failed build
+            let mut child = process
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            let stderr = tee_stderr(child.stderr.take()).await?;
+            let status = child.wait().await?;
+            let stderr = String::from_utf8_lossy(&stderr);
+            (
+                status,
+                cargo::diagnostics::parse_errors(&stderr, project.max_build_errors),
+            )
+        } else {
+            (process.status().await?, Vec::new())
+        };
+        runs.push(CommandRun {
+            command: command.to_string(),
+            exit_code: status.code(),
+        });
+
+        if !status.success() {
+            return Err(AppError::BuildFailed {
+                status: status.to_string(),
+                errors,
+            });
         }
-        BuildSystem::DotNet => {}
-        BuildSystem::Custom => {
-            if let Some(cmd) = &project.build_command {
-                let mut parts = cmd.split_whitespace();
-                if let Some(program) = parts.next() {
-                    tokio::process::Command::new(program)
-                        .args(parts)
-                        .current_dir(&project_path)
-                        .status()
-                        .await?;
-                }
-            }
+    }
+
+    match project.build_system {
+        BuildSystem::Zig => zig::library::write_module_index(&project_path).await?,
+        BuildSystem::Pydoc => {
+            pydoc::collect_html(&project_path, &project_path.join(pydoc::DOCS_DIR)).await?
         }
+        _ => {}
     }
 
     Ok(())
 }
 
-async fn load_config() -> AppResult<Config> {
-    let config_str = fs::read_to_string("config.toml").await?;
-    let config: Config = toml::from_str(&config_str)?;
-    Ok(config)
-}
+/// Appends the size of freshly built docs and the build's duration to the project's size history
+async fn record_docs_size(
+    libs_path: &Path,
+    project: &Project,
+    commit: Option<&CommitInfo>,
+    cargo_lock_sha256: Option<&str>,
+    duration: Duration,
+) {
+    let size_bytes = match docs_size(&project.docs_path, &project.ignore_rules).await {
+        Ok(size) => size,
+        Err(e) => {
+            warn!("Failed to measure docs of {}: {}", project.config.path, e);
+            return;
+        }
+    };
 
-async fn initialize_projects(config: &Config) -> AppResult<HashMap<String, Project>> {
-    let mut projects = HashMap::new();
+    let record = SizeRecord {
+        timestamp: now_secs(),
+        size_bytes,
+        commit_sha: commit.map(|c| c.id.clone()),
+        cargo_lock_sha256: cargo_lock_sha256.map(str::to_string),
+        duration_ms: Some(duration.as_millis() as u64),
+    };
+    if let Err(e) = append_size_record(libs_path, &project.url_path, &record).await {
+        warn!(
+            "Failed to record docs size of {}: {}",
+            project.config.path, e
+        );
+    }
+}
 
-    for project_cfg in &config.projects {
-        let url_path = sanitize_path(&project_cfg.path);
-        let project_path = config.libs_path.join(&project_cfg.path);
+/// Serves a file of a project's docs inline, like its single-file artifact, refusing anything
+/// that isn't a regular file or resolves outside `docs_path`
+async fn serve_docs_file(docs_path: &Path, file: &str) -> AppResult<NamedFile> {
+    let not_found = || AppError::NotFound(file.to_string());
 
-        let docs_path = match project_cfg.build_system {
-            BuildSystem::Gradle => project_path.join("build/docs/javadoc"),
-            BuildSystem::Cargo => project_path.join("target/doc"),
-            BuildSystem::Zig => project_path.join("docs"),
-            // TODO: determine actual folder: bin/net-x.0/.../
-            BuildSystem::DotNet => project_path.join("docs"),
-            BuildSystem::Custom => project_path.join("docs"),
-        };
+    let docs_root = fs::canonicalize(docs_path).await.map_err(|_| not_found())?;
+    let file_path = fs::canonicalize(docs_root.join(file))
+        .await
+        .map_err(|_| not_found())?;
+    let is_file = fs::metadata(&file_path).await.is_ok_and(|m| m.is_file());
+    if !file_path.starts_with(&docs_root) || !is_file {
+        return Err(not_found());
+    }
 
-        let project = Project {
-            config: project_cfg.clone(),
-            docs_path,
-            url_path: url_path.clone(),
-        };
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
 
-        projects.insert(url_path, project);
-    }
+    // NamedFile takes care of the content type, ETags and range requests
+    let file = NamedFile::open_async(&file_path)
+        .await?
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        });
 
-    Ok(projects)
+    Ok(file)
 }
 
-#[get("/")]
-async fn index(state: web::Data<Arc<AppState>>) -> impl Responder {
-    let projects = state
-        .projects
-        .values()
-        .map(|p| {
+/// The build warnings of a project, below its entry on the index
+fn warnings_line(summary: &ProjectSummary) -> String {
+    summary
+        .warnings
+        .iter()
+        .map(|warning| {
             format!(
-                "<li><a href=\"/{}/\">{}</a></li>",
-                p.url_path, p.config.path
+                "<div class=\"warning\">&#9888; {}</div>",
+                escape_html(warning)
             )
         })
+        .collect()
+}
+
+/// Links to the named snapshots of a project, below its entry on the index
+async fn snapshots_line(libs_path: &Path, url_path: &str) -> String {
+    let names = snapshots::list(libs_path, url_path).await;
+    if names.is_empty() {
+        return String::new();
+    }
+    let links = names
+        .iter()
+        .map(|name| format!("<a href=\"/{}/snapshots/{}/\">{}</a>", url_path, name, name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("<div class=\"snapshots\">Snapshots: {}</div>", links)
+}
+
+/// Color and label of the freshness badge for docs last built at `last_built`
+fn freshness_badge(last_built: Option<u64>, now: u64) -> (&'static str, String) {
+    const DAY: u64 = 24 * 60 * 60;
+
+    let Some(last_built) = last_built else {
+        return ("grey", "never built".to_string());
+    };
+
+    let age = now.saturating_sub(last_built);
+    let color = match age {
+        a if a < DAY => "green",
+        a if a <= 7 * DAY => "yellow",
+        _ => "red",
+    };
+    let label = match age {
+        a if a < 60 * 60 => "built just now".to_string(),
+        a if a < DAY => format!("built {}h ago", a / (60 * 60)),
+        a => format!("built {}d ago", a / DAY),
+    };
+
+    (color, label)
+}
+
+/// Second line of an index entry, describing where the docs were built from
+fn source_line(summary: &ProjectSummary) -> String {
+    let view_source = summary
+        .source_url
+        .as_deref()
+        .map(|url| format!(" &mdash; <a href=\"{}\">View source</a>", escape_html(url)))
+        .unwrap_or_default();
+    let release = summary
+        .release
+        .map(|release| format!(" &mdash; {}", escape_html(&release.to_string())))
+        .unwrap_or_default();
+    let Some(commit) = &summary.commit else {
+        return format!(
+            "<div class=\"source\">{}{}{}</div>",
+            escape_html(summary.source),
+            release,
+            view_source
+        );
+    };
+
+    let id = match &commit.url {
+        Some(url) => format!(
+            "<a href=\"{}\"><code>{}</code></a>",
+            escape_html(url),
+            commit.info.short_id()
+        ),
+        None => format!("<code>{}</code>", commit.info.short_id()),
+    };
+    format!(
+        "<div class=\"source\">{} {} &mdash; {}, {}{}{}</div>",
+        id,
+        escape_html(&commit.info.summary),
+        escape_html(&commit.info.author),
+        format_timestamp(commit.info.date),
+        release,
+        view_source
+    )
+}
+
+#[get("/favicon.ico")]
+async fn favicon(state: web::Data<Arc<AppState>>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .append_header(("Cache-Control", "public, max-age=86400"))
+        .body(state.favicon.clone())
+}
+
+/// Redirects to `location`, keeping the request's query string, so deep links like
+/// `/{slug}?search=Foo` still search. Where the docs start can change with the config, so the
+/// redirect is temporary: a `302` for GET and HEAD, and a `307` for other methods, which
+/// clients repeat with the same method and body instead of turning into a GET.
+fn redirect_keeping_query(req: &HttpRequest, location: &str) -> HttpResponse {
+    let mut response = if matches!(*req.method(), Method::GET | Method::HEAD) {
+        HttpResponse::Found()
+    } else {
+        HttpResponse::TemporaryRedirect()
+    };
+    response
+        .append_header((header::LOCATION, with_query(location, req.query_string())))
+        .finish()
+}
+
+/// Answers requests no route matched. When the first path segment names no project, the
+/// projects with the closest slugs or aliases are suggested, and with `redirect_unknown_slugs`
+/// a single close enough one is redirected to.
+async fn unknown_path(req: HttpRequest, state: web::Data<Arc<AppState>>) -> HttpResponse {
+    let path = req.path().trim_start_matches('/');
+    let (segment, tail) = path.split_once('/').unwrap_or((path, ""));
+
+    // the slug projects are reached by, and every name leading there
+    let mut candidates = Vec::new();
+    for project in state.projects.values() {
+        let slug = project
+            .branch
+            .as_ref()
+            .map_or(project.url_path.as_str(), |b| b.parent.as_str());
+        candidates.push((slug, slug));
+        candidates.extend(project.aliases.iter().map(|alias| (alias.as_str(), slug)));
+    }
+    if segment.is_empty() || segment == "api" || candidates.iter().any(|(name, _)| *name == segment)
+    {
+        return HttpResponse::NotFound().body(format!("Not found: /{}", path));
+    }
+
+    let matches = fuzzy::best_matches(segment, candidates);
+    if state.redirect_unknown_slugs
+        && matches!(*req.method(), Method::GET | Method::HEAD)
+        && let Some(best) = fuzzy::unambiguous(&matches)
+    {
+        return HttpResponse::Found()
+            .append_header((
+                "Location",
+                alias_location(best.slug, tail, req.query_string()),
+            ))
+            .finish();
+    }
+
+    let suggestions = if matches.is_empty() {
+        "<p>No project has a similar name.</p>".to_string()
+    } else {
+        format!(
+            "<p>Did you mean:</p><ul>{}</ul>",
+            matches
+                .iter()
+                .map(|m| format!("<li><a href=\"/{}/\">{}</a></li>", m.slug, m.slug))
+                .collect::<String>()
+        )
+    };
+    HttpResponse::NotFound()
+        .content_type("text/html")
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Not found</title>
+            <link rel="icon" type="image/svg+xml" href="/favicon.ico">
+            <style>
+                body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; }}
+                a {{ text-decoration: none; color: #0366d6; font-weight: 500; }}
+                li {{ margin: 0.3em 0; }}
+            </style>
+        </head>
+        <body>
+            <h1>No project named "{segment}"</h1>
+            {suggestions}
+            <p><a href="/">All projects</a></p>
+        </body>
+        </html>
+    "#,
+            segment = escape_html(segment),
+            suggestions = suggestions
+        ))
+}
+
+/// Whether the client prefers JSON over the HTML page, e.g. `curl -H 'Accept: application/json'`
+fn wants_json(req: &HttpRequest) -> bool {
+    <header::Accept as header::Header>::parse(req)
+        .is_ok_and(|accept| accept.preference().essence_str() == "application/json")
+}
+
+#[get("/")]
+async fn index(req: HttpRequest, state: web::Data<Arc<AppState>>) -> impl Responder {
+    let build_state = state.build_state.read().await;
+    let mut summaries = api::project_summaries(&state, &build_state);
+    api::sort_summaries(&mut summaries, state.index_sort);
+    if wants_json(&req) {
+        return HttpResponse::Ok()
+            .insert_header((header::VARY, "Accept"))
+            .json(summaries);
+    }
+
+    let now = now_secs();
+    // the branches of a project share one entry, with a line per branch, and so do the
+    // variants built with a project
+    let mut entries: Vec<(String, Vec<String>)> = Vec::with_capacity(summaries.len());
+    let mut groups = HashMap::new();
+    let mut variants = Vec::new();
+    for summary in summaries {
+        let p = &state.projects[summary.slug];
+        if let Some(variant) = &p.variant {
+            variants.push((variant, summary.url));
+            continue;
+        }
+        let (color, label) = freshness_badge(summary.last_built, now);
+        let Some(branch) = &p.branch else {
+            let project_badges = badges::project_badges(p, &state.base_path).await;
+            entries.push((
+                format!(
+                    "<span class=\"icon\">{}</span><a href=\"{}\">{}</a><span class=\"badge badge-{}\">{}</span><span class=\"badges\">{}</span>{}{}{}",
+                    entry_icon(p.config.entry_file.as_deref()),
+                    summary.url,
+                    escape_html(summary.name),
+                    color,
+                    label,
+                    badges::render(&project_badges, &state.badge_proxy),
+                    source_line(&summary),
+                    snapshots_line(&state.base_path, summary.slug).await,
+                    warnings_line(&summary)
+                ),
+                Vec::new(),
+            ));
+            groups.insert(summary.slug, entries.len() - 1);
+            continue;
+        };
+
+        let group = match groups.get(branch.parent.as_str()) {
+            Some(&group) => group,
+            None => {
+                let project_badges = badges::project_badges(p, &state.base_path).await;
+                entries.push((
+                    format!(
+                        "<span class=\"icon\">{}</span><a href=\"/{}/\">{}</a><span class=\"badges\">{}</span>",
+                        entry_icon(p.config.entry_file.as_deref()),
+                        branch.parent,
+                        escape_html(summary.name),
+                        badges::render(&project_badges, &state.badge_proxy)
+                    ),
+                    Vec::new(),
+                ));
+                groups.insert(branch.parent.as_str(), entries.len() - 1);
+                entries.len() - 1
+            }
+        };
+        entries[group].1.push(format!(
+            "<div class=\"branch\"><a href=\"{}\">{}</a><span class=\"badge badge-{}\">{}</span>{}{}{}</div>",
+            summary.url,
+            escape_html(&branch.name),
+            color,
+            label,
+            source_line(&summary),
+            snapshots_line(&state.base_path, summary.slug).await,
+            warnings_line(&summary)
+        ));
+    }
+    for (variant, url) in variants {
+        if let Some(&group) = groups.get(variant.parent.as_str()) {
+            entries[group].1.push(format!(
+                "<div class=\"branch\"><a href=\"{}\">{}</a> <code>{}</code></div>",
+                url,
+                escape_html(&variant.name),
+                escape_html(&variant.task)
+            ));
+        }
+    }
+    let projects = entries
+        .into_iter()
+        .map(|(entry, branches)| format!("<li>{}{}</li>", entry, branches.concat()))
         .collect::<Vec<_>>()
         .join("\n");
 
-    HttpResponse::Ok().content_type("text/html").body(format!(
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .insert_header((header::VARY, "Accept"))
+        .body(format!(
         r#"
         <!DOCTYPE html>
         <html>
         <head>
             <title>Documentation Server</title>
+            <link rel="icon" type="image/svg+xml" href="/favicon.ico">
             <style>
                 body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; }}
                 h1 {{ text-align: center; }}
                 ul {{ list-style: none; padding: 0; }}
                 li {{ margin: 0.5em 0; padding: 0.5em; background: #f5f5f5; border-radius: 4px; }}
                 a {{ text-decoration: none; color: #0366d6; font-weight: 500; }}
+                .icon {{ margin-right: 0.5em; }}
+                .badge {{ float: right; padding: 0.1em 0.6em; border-radius: 1em; font-size: 0.8em; color: #fff; }}
+                .badge-green {{ background: #2da44e; }}
+                .badge-yellow {{ background: #bf8700; }}
+                .badge-red {{ background: #cf222e; }}
+                .badge-grey {{ background: #8c959f; }}
+                .badges img {{ height: 1.2em; margin-left: 0.4em; vertical-align: middle; }}
+                .source {{ margin-top: 0.3em; font-size: 0.85em; color: #57606a; }}
+                .source a {{ font-weight: normal; }}
+                .warning {{ margin-top: 0.3em; font-size: 0.85em; color: #9a6700; }}
+                .branch {{ margin-top: 0.4em; padding-left: 1.5em; }}
+                .snapshots {{ margin-top: 0.3em; font-size: 0.85em; color: #57606a; }}
             </style>
         </head>
         <body>
@@ -252,90 +803,481 @@ async fn index(state: web::Data<Arc<AppState>>) -> impl Responder {
     ))
 }
 
-#[actix_web::main]
-async fn main() -> AppResult<()> {
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+/// Resolves the projects named on the command line, in order
+fn select_projects<'a>(
+    projects: &'a HashMap<String, Project>,
+    targets: Targets,
+) -> AppResult<Vec<&'a Project>> {
+    match targets {
+        Targets::All => {
+            let mut all = projects.values().collect::<Vec<_>>();
+            all.sort_by(|a, b| a.url_path.cmp(&b.url_path));
+            Ok(all)
+        }
+        Targets::Slugs(slugs) => slugs
+            .iter()
+            .map(|slug| {
+                projects
+                    .get(slug)
+                    .ok_or_else(|| AppError::Config(format!("unknown project '{}'", slug)))
+            })
+            .collect(),
+    }
+}
 
-    let config = Arc::new(load_config().await?);
-    let projects = initialize_projects(&config).await?;
-    let base_path = &config.clone().libs_path;
+/// Every command a build of `project` runs, starting with its `pre_build_script`
+async fn planned_commands(
+    project: &ProjectConfig,
+    base_path: &Path,
+) -> AppResult<Vec<BuildCommand>> {
+    let project_path = base_path.join(&project.path);
+    let mut commands = Vec::new();
+    if let Some(script) = prebuild::script_path(project, &project_path) {
+        commands.push(prebuild::command(&script, &project_path));
+    }
+    commands.extend(build_commands(project, base_path).await?);
+    Ok(commands)
+}
 
-    if config.update_on_start {
-        info!("Updating and building projects...");
-        for project in projects.values() {
-            let path_str = &project.config.path;
-            let repo_url = if let Some(url) = &project.config.repo {
-                url
-            } else {
-                warn!("Skipping {} (no repo URL)", path_str);
-                continue;
-            };
+/// Prints what `build`/`update` would do for `selected` without running anything
+async fn dry_run(
+    config: &Config,
+    selected: &[&Project],
+    update: bool,
+    json: bool,
+) -> AppResult<ExitCode> {
+    let mut plans = Vec::with_capacity(selected.len());
+    for project in selected {
+        let project_path = config.libs_path.join(&project.config.path);
+        let (git, commands) = match (update, &project.config.repo) {
+            _ if project.proxy.is_some() || project.is_static() || project.variant.is_some() => {
+                (Vec::new(), Vec::new())
+            }
+            (false, _) => (
+                Vec::new(),
+                planned_commands(&project.config, &config.libs_path).await?,
+            ),
+            (true, Some(repo_url)) => (
+                describe_update(
+                    &project_path,
+                    repo_url,
+                    &config.git.default_branches,
+                    &project.config.git.sparse_paths,
+                    project.branch.as_ref().map(|b| b.name.as_str()),
+                ),
+                planned_commands(&project.config, &config.libs_path).await?,
+            ),
+            (true, None) => (vec!["skip (no repo URL)".to_string()], Vec::new()),
+        };
+        plans.push(DryRunPlan {
+            slug: &project.url_path,
+            git,
+            commands,
+        });
+    }
 
-            info!("Updating {} from {}", path_str, repo_url);
-            let project_path = base_path.join(path_str);
-            if let Err(e) = update_project(&project_path, repo_url).await {
-                error!("Failed to update {}: {}", path_str, e);
+    cli::print_plans(&plans, json)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs the `build`/`update` subcommands, returning a failure exit code if any project failed
+async fn run_once(
+    config: &Config,
+    projects: &HashMap<String, Project>,
+    update: bool,
+    targets: Targets,
+) -> AppResult<ExitCode> {
+    let selected = select_projects(projects, targets)?;
+
+    let mut build_state = BuildState::load(&config.libs_path).await;
+    let mut outcomes = Vec::with_capacity(selected.len());
+    for project in selected {
+        let outcome = process_project(
+            project,
+            &config.libs_path,
+            &config.sandbox,
+            &config.git,
+            &mut build_state,
+            update,
+            Trigger::Cli,
+        )
+        .await;
+        outcomes.push((project.url_path.as_str(), outcome));
+    }
+    build_state.save(&config.libs_path).await?;
+
+    cli::print_summary(&outcomes);
+    // the runtime, and any request still in flight, ends with this command
+    for (_, outcome) in &mut outcomes {
+        outcome.hooks_sent().await;
+    }
+
+    if outcomes.iter().any(|(_, o)| o.is_failed()) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Runs the `bench` subcommand: builds each of `selected` `repeat` times from its working copy,
+/// without updating it or recording anything, and prints the timings slowest first. Returns a
+/// failure exit code if any build failed.
+async fn run_bench(
+    config: &Config,
+    selected: &[&Project],
+    repeat: u32,
+    json: bool,
+) -> AppResult<ExitCode> {
+    let mut results = Vec::with_capacity(selected.len());
+    for project in selected {
+        // nothing is built for these, or built along with another project
+        if project.proxy.is_some() || project.is_static() || project.variant.is_some() {
+            continue;
+        }
+
+        let mut durations = Vec::with_capacity(repeat as usize);
+        let mut error = None;
+        for run in 1..=repeat {
+            info!("Benchmarking {} ({}/{})", project.config.path, run, repeat);
+            let started = Instant::now();
+            let result = build_docs(
+                &project.config,
+                &config.libs_path,
+                &config.sandbox,
+                &mut Vec::new(),
+            )
+            .await;
+            match result {
+                Ok(()) => durations.push(started.elapsed().as_millis() as u64),
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
             }
+        }
+
+        results.push(BenchResult {
+            slug: &project.url_path,
+            build_system: project.config.build_system.name(),
+            runs: durations.len() as u32,
+            mean_ms: durations
+                .iter()
+                .sum::<u64>()
+                .checked_div(durations.len() as u64)
+                .unwrap_or_default(),
+            min_ms: durations.iter().copied().min().unwrap_or_default(),
+            max_ms: durations.iter().copied().max().unwrap_or_default(),
+            error,
+        });
+    }
 
-            info!("Building docs for {}", path_str);
-            if let Err(e) = build_docs(&project.config, &base_path).await {
-                error!("Failed to build {}: {}", path_str, e);
+    // failures last, as their times don't compare
+    results.sort_by_key(|r| (r.error.is_some(), std::cmp::Reverse(r.mean_ms)));
+    cli::print_bench(&results, json)?;
+
+    Ok(if results.iter().any(|r| r.error.is_some()) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Runs the `clean` subcommand, returning a failure exit code if anything couldn't be cleaned
+async fn run_clean(
+    config: &Config,
+    selected: &[&Project],
+    deep: bool,
+    dry_run: bool,
+    json: bool,
+) -> AppResult<ExitCode> {
+    let mut plans = Vec::with_capacity(selected.len());
+    for project in selected {
+        plans.push(clean::plan(project, &config.libs_path, deep).await?);
+    }
+
+    if dry_run {
+        cli::print_clean_plans(&plans, json)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut build_state = BuildState::load(&config.libs_path).await;
+    let mut freed = 0;
+    let mut failed = false;
+    for plan in &plans {
+        match clean::execute(plan).await {
+            Ok(bytes) => {
+                build_state.projects.remove(plan.slug);
+                println!("{}: freed {}", plan.slug, format_size(bytes));
+                freed += bytes;
+            }
+            Err(e) => {
+                println!("{}: failed: {}", plan.slug, e);
+                failed = true;
             }
         }
     }
+    build_state.save(&config.libs_path).await?;
+
+    println!("Freed {} in total", format_size(freed));
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
 
-    let state = Arc::new(AppState {
-        projects,
-        base_path: base_path.to_path_buf(),
+fn main() -> AppResult<ExitCode> {
+    let cli = cli::parse();
+
+    if cli.daemon || cli.pid_file.is_some() {
+        if !matches!(cli.command, CliCommand::Serve) {
+            return Err(AppError::Config(
+                "--daemon and --pid-file only apply when running the server".into(),
+            ));
+        }
+        detach(&cli)?;
+    }
+
+    actix_web::rt::System::new().block_on(run(cli))
+}
+
+/// Checks the pid file and daemonizes if requested, before any thread is spawned
+#[cfg(unix)]
+fn detach(cli: &Cli) -> AppResult<()> {
+    if let Some(pid_file) = &cli.pid_file {
+        daemon::check_pid_file(pid_file)?;
+    }
+    if cli.daemon {
+        daemon::detach()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn detach(_cli: &Cli) -> AppResult<()> {
+    Err(AppError::Config(
+        "--daemon and --pid-file are only supported on Unix".into(),
+    ))
+}
+
+async fn run(cli: Cli) -> AppResult<ExitCode> {
+    let config_path = config_path(cli.config.as_deref());
+
+    match cli.command {
+        CliCommand::Completions { shell } => {
+            // slugs are a nicety, completions still work without a readable config
+            let slugs: Vec<String> = load_config(&config_path, cli.config_format)
+                .await
+                .map(|c| c.projects.iter().map(|p| sanitize_path(&p.path)).collect())
+                .unwrap_or_default();
+            cli::print_completions(shell, &slugs);
+            return Ok(ExitCode::SUCCESS);
+        }
+        CliCommand::Man => {
+            cli::print_man()?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    let config = Arc::new(load_config(&config_path, cli.config_format).await?);
+    // stdout is gone once daemonized, so logs need a file then
+    let log_file = config.log_file.clone().or_else(|| {
+        cli.daemon
+            .then(|| config.libs_path.join(".lichen-logs").join("lichen.log"))
     });
+    logging::init(
+        logging::cli_level(cli.verbose, cli.quiet),
+        config.log_level.as_deref(),
+        log_file.as_deref(),
+    )?;
+    info!("Using config file {}", config_path.display());
+    let projects = initialize_projects(&config).await?;
 
-    info!("Starting server on port {}", config.port);
-    HttpServer::new(move || {
-        let state = web::Data::new(state.clone());
-
-        // create routes for each project
-        let mut app = App::new()
-            .app_data(state.clone())
-            .wrap(middleware::Logger::default())
-            .service(index);
-
-        for project in state.projects.values() {
-            let docs_path = project.docs_path.clone();
-            let route = project.url_path.clone();
-            let resource_path = format!("/{}", route);
-
-            // closure with captured variables for each project
-            let route_clone = route.clone();
-            app = app.service(web::resource(&resource_path).to(move || {
-                let route = route_clone.clone();
-                async move {
-                    HttpResponse::Found()
-                        .append_header(("Location", format!("/{}/", route)))
-                        .finish()
-                }
-            }));
-
-            // closure for the default handler
-            let route_clone2 = route.clone();
-            app = app.service(
-                Files::new(&format!("/{}", route), docs_path)
-                    .index_file("index.html")
-                    .default_handler(web::to(move || {
-                        let route = route_clone2.clone();
-                        async move {
-                            HttpResponse::Found()
-                                .append_header(("Location", format!("/{}/", route)))
-                                .finish()
-                        }
-                    })),
-            );
+    match cli.command {
+        CliCommand::Serve | CliCommand::Completions { .. } | CliCommand::Man => {}
+        CliCommand::List { json } => {
+            let selected = select_projects(&projects, Targets::All)?;
+            cli::print_projects(&selected, &config, json)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        CliCommand::Run {
+            update,
+            targets,
+            dry_run: true,
+            json,
+        } => {
+            let selected = select_projects(&projects, targets)?;
+            return dry_run(&config, &selected, update, json).await;
+        }
+        CliCommand::Run {
+            update, targets, ..
+        } => {
+            return run_once(&config, &projects, update, targets).await;
+        }
+        CliCommand::Clean {
+            targets,
+            deep,
+            dry_run,
+            json,
+        } => {
+            let selected = select_projects(&projects, targets)?;
+            return run_clean(&config, &selected, deep, dry_run, json).await;
+        }
+        CliCommand::Bench {
+            targets,
+            repeat,
+            json,
+        } => {
+            let selected = select_projects(&projects, targets)?;
+            return run_bench(&config, &selected, repeat, json).await;
+        }
+    }
+
+    let base_path = &config.clone().libs_path;
+    let mut build_state = BuildState::load(base_path).await;
+
+    let mut startup = HashMap::new();
+    if config.update_on_start {
+        info!("Updating and building projects...");
+        let mut selected = projects.values().collect::<Vec<_>>();
+        selected.sort_by(|a, b| a.url_path.cmp(&b.url_path));
+
+        let mut outcomes = Vec::with_capacity(selected.len());
+        for project in selected {
+            let outcome = process_project(
+                project,
+                base_path,
+                &config.sandbox,
+                &config.git,
+                &mut build_state,
+                true,
+                Trigger::Startup,
+            )
+            .await;
+            outcomes.push((project.url_path.as_str(), outcome));
+        }
+
+        if let Err(e) = build_state.save(base_path).await {
+            error!("Failed to save build state: {}", e);
         }
 
-        app
+        info!("Startup summary:");
+        for line in cli::summary_lines(&outcomes) {
+            info!("{}", line);
+        }
+        if cli.strict_startup && outcomes.iter().any(|(_, o)| o.is_failed()) {
+            error!("Not serving, some projects failed on startup (--strict-startup)");
+            return Ok(ExitCode::FAILURE);
+        }
+
+        startup = outcomes
+            .into_iter()
+            .map(|(slug, outcome)| (slug.to_string(), outcome))
+            .collect();
+    }
+
+    let mut projects = projects;
+    for project in projects.values_mut() {
+        if project.config.detect_docs_path
+            && let Some(detected) = build_state
+                .project(&project.url_path)
+                .and_then(|p| p.docs_path.as_ref())
+        {
+            project.docs_path = base_path.join(&project.config.path).join(detected);
+        }
+        project.resolve_index_file();
+    }
+
+    let state = Arc::new(AppState::new(&config, projects, build_state, startup)?);
+    access::spawn_persist(state.clone());
+    if let Some(hours) = config.maintenance.gc_interval_hours {
+        maintenance::spawn_periodic(state.clone(), Duration::from_secs(hours * 60 * 60));
+    }
+
+    let tls = config.tls.as_ref().map(tls::server_config).transpose()?;
+    let client_auth = config
+        .tls
+        .as_ref()
+        .is_some_and(|tls| tls.client_ca_cert.is_some());
+
+    let separate_admin = config.admin.is_some();
+    let admin_state = state.clone();
+    let shutdown_state = state.clone();
+    let connections = state.connections.clone();
+    let terminate = shutdown::Terminate::listen()?;
+
+    // actix advertises h2 ahead of http/1.1 through ALPN on TLS listeners
+    let protocols = if tls.is_some() {
+        "HTTPS, offering h2 and http/1.1"
+    } else {
+        "HTTP/1.1, and h2 to clients with prior knowledge"
+    };
+    info!("Starting server on port {} ({})", config.port, protocols);
+    let mut server = HttpServer::new(move || {
+        let logger = if client_auth {
+            tls::client_logger()
+        } else {
+            middleware::Logger::default()
+        };
+        app::public_app(state.clone(), separate_admin, logger)
     })
-    .bind(("0.0.0.0", config.port))?
-    .run()
-    .await?;
+    .keep_alive(Duration::from_secs(config.server.keep_alive_secs))
+    .client_request_timeout(Duration::from_secs(
+        config.server.client_request_timeout_secs,
+    ))
+    .on_connect(move |connection, data| metrics::on_connect(&connections, connection, data))
+    .disable_signals();
+    if let Some(max) = config.server.max_connections {
+        server = server.max_connections(max);
+    }
+    if let Some(max) = config.server.max_connection_rate {
+        server = server.max_connection_rate(max);
+    }
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23(("0.0.0.0", config.port), tls)?,
+        None => server.bind(("0.0.0.0", config.port))?,
+    };
 
-    Ok(())
+    // the admin listener shares the state, but serves nothing else
+    let admin = match &config.admin {
+        Some(admin) => {
+            info!("Starting admin server on {}:{}", admin.bind, admin.port);
+            let server = HttpServer::new(move || app::admin_app(admin_state.clone()))
+                .disable_signals()
+                .bind((admin.bind.as_str(), admin.port))?;
+            Some(server.run())
+        }
+        None => None,
+    };
+
+    // written only now, so an existing pid file means the server is accepting connections
+    #[cfg(unix)]
+    if let Some(pid_file) = &cli.pid_file {
+        daemon::write_pid_file(pid_file).await?;
+    }
+
+    let server = server.run();
+    let mut handles = vec![server.handle()];
+    handles.extend(admin.as_ref().map(|admin| admin.handle()));
+    shutdown::spawn_stop_on_signal(
+        handles,
+        terminate,
+        Duration::from_secs(config.server.shutdown_grace_period_secs),
+        shutdown_state.connections.clone(),
+    );
+
+    let result = match admin {
+        Some(admin) => tokio::try_join!(server, admin).map(|_| ()),
+        None => server.await,
+    };
+    access::persist(&shutdown_state).await;
+
+    #[cfg(unix)]
+    if let Some(pid_file) = &cli.pid_file {
+        daemon::remove_pid_file(pid_file).await;
+    }
+
+    result?;
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{process::Command, sync::Semaphore, task::JoinSet, time};
+use tracing::{info, warn};
+
+use crate::{
+    error::{AppError, AppResult},
+    history::dir_size,
+    utils::format_size,
+    AppState,
+};
+
+/// Periodic housekeeping of the projects' repositories
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Hours between `git gc` runs over all repositories, none if unset
+    #[serde(default)]
+    pub gc_interval_hours: Option<u64>,
+    /// Repositories garbage collected at the same time
+    #[serde(default = "default_max_concurrent_gc")]
+    pub max_concurrent_gc: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            gc_interval_hours: None,
+            max_concurrent_gc: default_max_concurrent_gc(),
+        }
+    }
+}
+
+fn default_max_concurrent_gc() -> usize {
+    2
+}
+
+/// Size of a repository's `.git` directory around a `git gc`
+#[derive(Debug, Serialize)]
+pub struct GcReport {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+    pub freed_bytes: u64,
+}
+
+/// Marks a repository as being garbage collected until dropped. That also happens when a gc
+/// is cancelled, e.g. along with the request of a client that went away.
+struct Running<'a> {
+    running: &'a Mutex<HashSet<PathBuf>>,
+    path: &'a Path,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(self.path);
+    }
+}
+
+/// Bounds and deduplicates garbage collections, whether periodic or requested over the API
+#[derive(Debug)]
+pub struct Maintenance {
+    running: Mutex<HashSet<PathBuf>>,
+    limit: Semaphore,
+}
+
+impl Maintenance {
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        Self {
+            running: Mutex::new(HashSet::new()),
+            limit: Semaphore::new(config.max_concurrent_gc.max(1)),
+        }
+    }
+
+    /// Garbage collects the repository at `path`, or returns `None` if that's already underway
+    pub async fn gc(&self, path: &Path) -> Option<AppResult<GcReport>> {
+        if !self.running.lock().unwrap().insert(path.to_path_buf()) {
+            return None;
+        }
+        let _running = Running {
+            running: &self.running,
+            path,
+        };
+
+        let result = match self.limit.acquire().await {
+            Ok(_permit) => gc_repository(path).await,
+            Err(e) => Err(AppError::Io(std::io::Error::other(e))),
+        };
+        Some(result)
+    }
+}
+
+/// Runs `git gc` in the repository at `path`. Stale remote refs are already pruned by every
+/// fetch; git's own lock keeps this from racing a `lichen update` in another process.
+async fn gc_repository(path: &Path) -> AppResult<GcReport> {
+    let git_dir = path.join(".git");
+    if !tokio::fs::try_exists(&git_dir).await.unwrap_or(false) {
+        return Err(AppError::NotFound(format!(
+            "git repository at {}",
+            path.display()
+        )));
+    }
+
+    let before_bytes = dir_size(&git_dir).await?;
+    let status = Command::new("git")
+        .args(["gc", "--quiet"])
+        .current_dir(path)
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(AppError::Io(std::io::Error::other(format!(
+            "git gc in {} failed ({})",
+            path.display(),
+            status
+        ))));
+    }
+    let after_bytes = dir_size(&git_dir).await?;
+
+    let freed_bytes = before_bytes.saturating_sub(after_bytes);
+    info!(
+        "Garbage collected {}: {} -> {} ({} freed)",
+        path.display(),
+        format_size(before_bytes),
+        format_size(after_bytes),
+        format_size(freed_bytes)
+    );
+    Ok(GcReport {
+        before_bytes,
+        after_bytes,
+        freed_bytes,
+    })
+}
+
+/// Garbage collects every cloned repository each `interval`, starting one interval from now
+pub fn spawn_periodic(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticks = time::interval_at(time::Instant::now() + interval, interval);
+        loop {
+            ticks.tick().await;
+
+            let mut tasks = JoinSet::new();
+            for project in state.projects.values() {
+                if project.config.repo.is_none() {
+                    continue;
+                }
+                let state = state.clone();
+                let path = state.base_path.join(&project.config.path);
+                tasks.spawn(async move {
+                    if let Some(Err(e)) = state.maintenance.gc(&path).await {
+                        warn!("Failed to garbage collect {}: {}", path.display(), e);
+                    }
+                });
+            }
+            while tasks.join_next().await.is_some() {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gc_requires_repository() {
+        let dir = TempDir::new().unwrap();
+        let maintenance = Maintenance::new(&MaintenanceConfig::default());
+
+        let result = maintenance.gc(dir.path()).await;
+        assert!(matches!(result, Some(Err(AppError::NotFound(_)))));
+        // a finished gc doesn't block the next one
+        assert!(maintenance.gc(dir.path()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_gc_skips_repository_already_collected() {
+        let dir = TempDir::new().unwrap();
+        let maintenance = Maintenance::new(&MaintenanceConfig::default());
+
+        maintenance
+            .running
+            .lock()
+            .unwrap()
+            .insert(dir.path().to_path_buf());
+        assert!(maintenance.gc(dir.path()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_gc_is_no_longer_running() {
+        let dir = TempDir::new().unwrap();
+        let maintenance = Maintenance::new(&MaintenanceConfig {
+            max_concurrent_gc: 1,
+            ..MaintenanceConfig::default()
+        });
+
+        // dropped while waiting for the only slot
+        let slot = maintenance.limit.acquire().await.unwrap();
+        let waiting = time::timeout(Duration::from_millis(10), maintenance.gc(dir.path())).await;
+        assert!(waiting.is_err());
+        drop(slot);
+
+        assert!(maintenance.running.lock().unwrap().is_empty());
+        assert!(maintenance.gc(dir.path()).await.is_some());
+    }
+}
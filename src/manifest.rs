@@ -0,0 +1,252 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncReadExt};
+use tracing::warn;
+
+use crate::{
+    artifact::list_files,
+    config::Project,
+    error::{AppError, AppResult},
+    filter::{IgnoreRules, ServeFilter},
+    history::project_logs_dir,
+    state::now_secs,
+    utils::url_path,
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Checksums of the files a project serves, as of its last successful build
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocsManifest {
+    /// Unix timestamp (seconds) the manifest was computed at
+    pub generated_at: u64,
+    pub commit_sha: Option<String>,
+    pub file_count: usize,
+    pub total_size: u64,
+    /// Hex SHA-256 of each file, keyed by its `/`-separated path inside the docs, with the
+    /// bytes of names that aren't UTF-8 percent-encoded
+    pub files: BTreeMap<String, String>,
+}
+
+fn manifest_path(libs_path: &Path, url_path: &str) -> PathBuf {
+    project_logs_dir(libs_path, url_path).join(MANIFEST_FILE)
+}
+
+/// Hashes the file at `path` in fixed-size chunks, returning its digest and size
+async fn sha256_file(path: &Path) -> AppResult<(String, u64)> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    Ok((hex, size))
+}
+
+/// SHA-256 of the `Cargo.lock` a build in `project_dir` used: the project's own, or that of the
+/// workspace it's a member of, looked for up to `libs_path`
+pub async fn cargo_lock_sha256(libs_path: &Path, project_dir: &Path) -> Option<String> {
+    for dir in project_dir.ancestors() {
+        if !dir.starts_with(libs_path) {
+            break;
+        }
+        match sha256_file(&dir.join("Cargo.lock")).await {
+            Ok((hex, _)) => return Some(hex),
+            Err(AppError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                warn!(
+                    "Failed to checksum the Cargo.lock of {}: {}",
+                    dir.display(),
+                    e
+                );
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Checksums every file below `docs_path` that `filter` allows to be served
+pub async fn compute(
+    docs_path: &Path,
+    filter: &ServeFilter,
+    commit_sha: Option<&str>,
+) -> AppResult<DocsManifest> {
+    let mut files = BTreeMap::new();
+    let mut total_size = 0;
+
+    for file in list_files(docs_path, filter, &IgnoreRules::default()).await? {
+        let (digest, size) = sha256_file(&docs_path.join(&file)).await?;
+        files.insert(url_path(&file), digest);
+        total_size += size;
+    }
+
+    Ok(DocsManifest {
+        generated_at: now_secs(),
+        commit_sha: commit_sha.map(str::to_string),
+        file_count: files.len(),
+        total_size,
+        files,
+    })
+}
+
+/// Computes the manifest of freshly built docs and stores it in the project's logs directory
+pub async fn record(
+    libs_path: &Path,
+    project: &Project,
+    commit_sha: Option<&str>,
+) -> AppResult<()> {
+    let manifest = compute(&project.docs_path, &project.serve_filter, commit_sha).await?;
+    save(libs_path, &project.url_path, &manifest).await
+}
+
+async fn save(libs_path: &Path, url_path: &str, manifest: &DocsManifest) -> AppResult<()> {
+    let path = manifest_path(libs_path, url_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let json = serde_json::to_string_pretty(manifest).map_err(std::io::Error::from)?;
+    fs::write(path, json).await?;
+    Ok(())
+}
+
+/// SHA-256 of the stored manifest of a project
+pub async fn digest(libs_path: &Path, url_path: &str) -> AppResult<String> {
+    let (hex, _) = sha256_file(&manifest_path(libs_path, url_path)).await?;
+    Ok(hex)
+}
+
+/// The stored manifest of a project, as JSON
+pub async fn load(libs_path: &Path, url_path: &str) -> AppResult<String> {
+    fs::read_to_string(manifest_path(libs_path, url_path))
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                AppError::NotFound(format!("manifest of project '{}'", url_path))
+            }
+            _ => e.into(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sha256_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file");
+
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            sha256_file(&path).await.unwrap(),
+            (
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string(),
+                0
+            )
+        );
+
+        // larger than one chunk
+        std::fs::write(&path, "a".repeat(100_000)).unwrap();
+        let (digest, size) = sha256_file(&path).await.unwrap();
+        assert_eq!(size, 100_000);
+        assert_eq!(
+            digest,
+            "6d1cf22d7cc09b085dfc25ee1a1f3ae0265804c607bc2074ad253bcc82fd81ee"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_lock_sha256() {
+        let libs = TempDir::new().unwrap();
+        let member = libs.path().join("workspace/crates/core");
+        std::fs::create_dir_all(&member).unwrap();
+        assert_eq!(cargo_lock_sha256(libs.path(), &member).await, None);
+
+        // members share the lock file of their workspace
+        std::fs::write(libs.path().join("workspace/Cargo.lock"), "").unwrap();
+        assert_eq!(
+            cargo_lock_sha256(libs.path(), &member).await.as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+
+        // nothing above the libs directory is looked at
+        let project = libs.path().join("lib");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(libs.path().join("Cargo.lock"), "").unwrap();
+        assert_eq!(cargo_lock_sha256(&project, &project).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_load() {
+        let libs = TempDir::new().unwrap();
+        assert!(matches!(
+            load(libs.path(), "lib").await,
+            Err(AppError::NotFound(_))
+        ));
+
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(docs.join("sub")).unwrap();
+        std::fs::write(docs.join("index.html"), "index").unwrap();
+        std::fs::write(docs.join("sub/page.html"), "page").unwrap();
+        std::fs::write(docs.join("deploy.key"), "secret").unwrap();
+
+        let filter = ServeFilter::new(&[], &["*.key".to_string()], "lib").unwrap();
+        let manifest = compute(&docs, &filter, Some("abc123")).await.unwrap();
+        save(libs.path(), "lib", &manifest).await.unwrap();
+
+        let manifest: DocsManifest =
+            serde_json::from_str(&load(libs.path(), "lib").await.unwrap()).unwrap();
+        assert_eq!(manifest.commit_sha.as_deref(), Some("abc123"));
+        assert_eq!(manifest.file_count, 2);
+        assert_eq!(manifest.total_size, 9);
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            ["index.html", "sub/page.html"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_compute_with_non_utf8_names() {
+        let docs = TempDir::new().unwrap();
+        // two Latin-1 names that would both turn into `caf\u{fffd}.html` if replaced
+        for name in [&b"caf\xe9.html"[..], b"caf\xe8.html"] {
+            std::fs::write(
+                docs.path()
+                    .join(crate::utils::path_from_bytes(name.to_vec())),
+                name,
+            )
+            .unwrap();
+        }
+
+        let filter = ServeFilter::new(&[], &[], "lib").unwrap();
+        let manifest = compute(docs.path(), &filter, None).await.unwrap();
+        assert_eq!(manifest.file_count, 2);
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            ["caf%E8.html", "caf%E9.html"]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    fmt::Write,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Extensions, ServiceRequest, ServiceResponse},
+    get,
+    middleware::Next,
+    web, HttpResponse,
+};
+
+use crate::{tls, AppState};
+
+/// Connections of the public listener, counted as actix opens and drops them
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    open: AtomicUsize,
+    /// Open connections with at least one request being handled
+    active: AtomicUsize,
+    total: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Open connections waiting for a request, e.g. kept alive between two
+    pub fn idle(&self) -> usize {
+        self.open
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.active())
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// The stats in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let metrics = [
+            (
+                "lichen_http_connections_active",
+                "gauge",
+                "Open connections with a request being handled",
+                self.active() as u64,
+            ),
+            (
+                "lichen_http_connections_idle",
+                "gauge",
+                "Open connections waiting for a request",
+                self.idle() as u64,
+            ),
+            (
+                "lichen_http_connections_total",
+                "counter",
+                "Connections accepted since the server started",
+                self.total(),
+            ),
+        ];
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        text
+    }
+}
+
+/// Stored with each connection's data, which actix drops once the connection is closed
+struct Connection {
+    stats: Arc<ConnectionStats>,
+    /// Requests being handled, more than one for HTTP/2
+    in_flight: Cell<usize>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.stats.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks its connection active while a request is handled
+struct Busy(Rc<Connection>);
+
+impl Busy {
+    fn new(connection: Rc<Connection>) -> Self {
+        let in_flight = connection.in_flight.get();
+        if in_flight == 0 {
+            connection.stats.active.fetch_add(1, Ordering::Relaxed);
+        }
+        connection.in_flight.set(in_flight + 1);
+        Self(connection)
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        let in_flight = self.0.in_flight.get() - 1;
+        self.0.in_flight.set(in_flight);
+        if in_flight == 0 {
+            self.0.stats.active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counts a new connection of the public listener, and remembers its client certificate
+pub fn on_connect(stats: &Arc<ConnectionStats>, connection: &dyn Any, data: &mut Extensions) {
+    tls::on_connect(connection, data);
+    stats.total.fetch_add(1, Ordering::Relaxed);
+    stats.open.fetch_add(1, Ordering::Relaxed);
+    data.insert(Rc::new(Connection {
+        stats: stats.clone(),
+        in_flight: Cell::new(0),
+    }));
+}
+
+/// Keeps the request's connection counted as active until its response is ready. Streamed
+/// bodies, like docs archives, are sent while the connection already counts as idle.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _busy = req.conn_data::<Rc<Connection>>().cloned().map(Busy::new);
+    next.call(req).await
+}
+
+#[get("/metrics")]
+pub async fn metrics(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.connections.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_stats() {
+        let stats = Arc::new(ConnectionStats::default());
+        let mut first = Extensions::new();
+        let mut second = Extensions::new();
+        on_connect(&stats, &(), &mut first);
+        on_connect(&stats, &(), &mut second);
+        assert_eq!((stats.active(), stats.idle(), stats.total()), (0, 2, 2));
+
+        // two HTTP/2 streams on one connection make it active once
+        let connection = first.get::<Rc<Connection>>().unwrap().clone();
+        let (a, b) = (Busy::new(connection.clone()), Busy::new(connection));
+        assert_eq!((stats.active(), stats.idle()), (1, 1));
+        drop(a);
+        assert_eq!(stats.active(), 1);
+        drop(b);
+        assert_eq!((stats.active(), stats.idle()), (0, 2));
+
+        drop(second);
+        assert_eq!((stats.active(), stats.idle(), stats.total()), (0, 1, 2));
+        assert!(stats.render().contains(
+            "# TYPE lichen_http_connections_idle gauge\nlichen_http_connections_idle 1\n"
+        ));
+    }
+}
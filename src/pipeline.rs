@@ -0,0 +1,425 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::Serialize;
+use tokio::{fs, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::{
+    build_docs,
+    config::Project,
+    copy,
+    error::AppError,
+    git::{head_commit, release_status, update_project, GitConfig, UpdateOptions, UpdateOutcome},
+    hooks::{self, BuildEvent},
+    links, manifest, precompress, record_docs_size,
+    report::{self, BuildReport, Trigger},
+    sandbox::SandboxConfig,
+    state::{now_secs, BuildState},
+};
+
+/// Result of a single step of [`process_project`]
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum StepStatus {
+    Ok,
+    /// Built, but the output looks wrong
+    OkWithWarnings(String),
+    /// Updated, but the repository had no new commits
+    UpToDate,
+    Skipped(&'static str),
+    Failed(String),
+}
+
+impl StepStatus {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, StepStatus::Failed(_))
+    }
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // padded so the CLI summary can align it in columns
+        let text = match self {
+            StepStatus::Ok => "ok".to_string(),
+            StepStatus::OkWithWarnings(warning) => format!("ok with warnings: {}", warning),
+            StepStatus::UpToDate => "up to date".to_string(),
+            StepStatus::Skipped(reason) => format!("skipped ({})", reason),
+            StepStatus::Failed(e) => format!("failed: {}", e),
+        };
+        f.pad(&text)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectOutcome {
+    pub update: StepStatus,
+    pub build: StepStatus,
+    /// Build hook requests still being sent
+    #[serde(skip)]
+    pub hooks: Vec<JoinHandle<()>>,
+}
+
+impl ProjectOutcome {
+    fn skipped(update: &'static str, build: &'static str) -> Self {
+        Self {
+            update: StepStatus::Skipped(update),
+            build: StepStatus::Skipped(build),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Waits until the build hooks are sent, or gave up
+    pub async fn hooks_sent(&mut self) {
+        for task in self.hooks.drain(..) {
+            let _ = task.await;
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.update.is_failed() || self.build.is_failed()
+    }
+}
+
+/// Where build tools commonly put HTML docs, relative to the project directory, most specific
+/// first
+const DOCS_PATH_CANDIDATES: &[&str] = &[
+    "build/docs/javadoc",
+    "build/dokka/html",
+    "target/doc",
+    "zig-out/docs",
+    "docs/_build/html",
+    "_build/html",
+    "build/html",
+    "site",
+    "public",
+    "html",
+    "docs",
+    "doc",
+];
+
+/// The first of [`DOCS_PATH_CANDIDATES`] with an `.html` file directly in it
+async fn detect_docs_path(project_path: &Path) -> Option<PathBuf> {
+    for candidate in DOCS_PATH_CANDIDATES {
+        let Ok(mut entries) = fs::read_dir(project_path.join(candidate)).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let is_html = Path::new(&entry.file_name())
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("html"));
+            if is_html && entry.file_type().await.is_ok_and(|t| t.is_file()) {
+                return Some(PathBuf::from(candidate));
+            }
+        }
+    }
+    None
+}
+
+/// What's wrong with the docs a build left behind in `docs_path`, if anything: a missing or
+/// empty directory, or no `served` file (the index or entry file) in it
+pub async fn output_warning(docs_path: &Path, served: &str) -> Option<String> {
+    let Ok(mut entries) = fs::read_dir(docs_path).await else {
+        return Some(format!(
+            "its docs directory {} doesn't exist",
+            docs_path.display()
+        ));
+    };
+
+    let mut count = 0;
+    while let Ok(Some(_)) = entries.next_entry().await {
+        count += 1;
+    }
+    if count == 0 {
+        return Some(format!(
+            "its docs directory {} is empty",
+            docs_path.display()
+        ));
+    }
+
+    if !fs::try_exists(docs_path.join(served))
+        .await
+        .unwrap_or(false)
+    {
+        return Some(format!(
+            "{} is missing from {} ({} entries)",
+            served,
+            docs_path.display(),
+            count
+        ));
+    }
+
+    None
+}
+
+/// Optionally updates a project from its repository, then builds its docs and records the
+/// build. Shared by the server's startup and the one-shot CLI commands.
+pub async fn process_project(
+    project: &Project,
+    base_path: &Path,
+    sandbox: &SandboxConfig,
+    git: &GitConfig,
+    build_state: &mut BuildState,
+    update: bool,
+    trigger: Trigger,
+) -> ProjectOutcome {
+    let path_str = &project.config.path;
+    let project_path = base_path.join(path_str);
+
+    if project.proxy.is_some() {
+        return ProjectOutcome::skipped("docs are proxied", "docs are proxied");
+    }
+    if project.is_static() {
+        return ProjectOutcome::skipped("static docs", "static docs");
+    }
+    if project.variant.is_some() {
+        return ProjectOutcome::skipped("built with its project", "built with its project");
+    }
+
+    let (update, commit) = if update {
+        let Some(repo_url) = &project.config.repo else {
+            warn!("Skipping {} (no repo URL)", path_str);
+            return ProjectOutcome::skipped("no repo URL", "not updated");
+        };
+
+        info!("Updating {} from {}", path_str, repo_url);
+        let options = UpdateOptions {
+            auth: project.config.auth.clone(),
+            fallback_branches: git.default_branches.clone(),
+            sparse_paths: project.config.git.sparse_paths.clone(),
+            proxy: project
+                .config
+                .git
+                .proxy
+                .clone()
+                .or_else(|| git.proxy.clone()),
+            branch: project.branch.as_ref().map(|b| b.name.clone()),
+        };
+        match update_project(&project_path, repo_url, &options).await {
+            Ok(outcome) => {
+                info!("Updated {}: {}", path_str, outcome);
+                let status = match outcome {
+                    UpdateOutcome::UpToDate { .. } => StepStatus::UpToDate,
+                    _ => StepStatus::Ok,
+                };
+                (status, Some(outcome.into_head()))
+            }
+            Err(e) => {
+                error!("Failed to update {}: {}", path_str, e);
+                // the build still runs, from whatever is checked out
+                (
+                    StepStatus::Failed(e.to_string()),
+                    head_commit(&project_path).await.ok(),
+                )
+            }
+        }
+    } else {
+        (
+            StepStatus::Skipped("not requested"),
+            head_commit(&project_path).await.ok(),
+        )
+    };
+
+    info!("Building docs for {}", path_str);
+    let started = Instant::now();
+    let started_at = now_secs();
+    let mut commands = Vec::new();
+    let mut result = build_docs(&project.config, base_path, sandbox, &mut commands).await;
+    // after the build, which writes the lock file if the repository has none
+    let cargo_lock_sha256 = manifest::cargo_lock_sha256(base_path, &project_path).await;
+
+    // served from wherever the build left its docs, from now on and after restarts
+    let mut detected = None;
+    if result.is_ok() && project.config.detect_docs_path {
+        match detect_docs_path(&project_path).await {
+            Some(relative) => {
+                info!("Found docs of {} in {}", path_str, relative.display());
+                detected = Some(Project {
+                    docs_path: project_path.join(&relative),
+                    ..project.clone()
+                });
+                build_state.record_docs_path(&project.url_path, relative);
+            }
+            None => warn!(
+                "Found no HTML docs of {} in the usual directories",
+                path_str
+            ),
+        }
+    }
+    let project = detected.as_ref().unwrap_or(project);
+
+    let mut warnings = Vec::new();
+    if result.is_ok()
+        && let Some(warning) = output_warning(
+            &project.docs_path,
+            project
+                .config
+                .entry_file
+                .as_deref()
+                .unwrap_or(&project.config.index_file),
+        )
+        .await
+    {
+        warn!("Built {}, but {}", path_str, warning);
+        if project.config.fail_on_empty_docs {
+            result = Err(AppError::BuildFailed {
+                status: "no docs produced".to_string(),
+                errors: vec![warning],
+            });
+        } else {
+            warnings.push(warning);
+        }
+    }
+    let duration = started.elapsed();
+    let hooks = hooks::notify(
+        &project.config.build_hooks,
+        &BuildEvent {
+            project: path_str,
+            success: result.is_ok(),
+            duration,
+            commit_sha: commit.as_ref().map(|c| c.id.as_str()),
+        },
+    );
+
+    let mut manifest_sha256 = None;
+    if result.is_ok() {
+        record_docs_size(
+            base_path,
+            project,
+            commit.as_ref(),
+            cargo_lock_sha256.as_deref(),
+            duration,
+        )
+        .await;
+        let commit_sha = commit.as_ref().map(|c| c.id.as_str());
+        match manifest::record(base_path, project, commit_sha).await {
+            Ok(()) => manifest_sha256 = manifest::digest(base_path, &project.url_path).await.ok(),
+            Err(e) => warn!("Failed to checksum docs of {}: {}", path_str, e),
+        }
+        if project.config.precompress {
+            precompress::after_build(base_path, project).await;
+        }
+        copy::after_build(project).await;
+    }
+    let broken_links = if result.is_ok() && project.config.check_links {
+        links::after_build(project).await
+    } else {
+        None
+    };
+
+    let report = BuildReport {
+        project: &project.url_path,
+        trigger,
+        started_at,
+        finished_at: now_secs(),
+        duration_ms: duration.as_millis() as u64,
+        success: result.is_ok(),
+        error: result.as_ref().err().map(ToString::to_string),
+        commands: &commands,
+        commit: commit.as_ref(),
+        cargo_lock_sha256: cargo_lock_sha256.as_deref(),
+        warnings: &warnings,
+        manifest_sha256,
+    };
+    if let Err(e) = report::write(&report::path(base_path, project), &report).await {
+        warn!("Failed to write the build report of {}: {}", path_str, e);
+    }
+
+    let release = if result.is_ok() && commit.is_some() {
+        release_status(&project_path).await.unwrap_or_else(|e| {
+            warn!(
+                "Failed to compare {} with its release tags: {}",
+                path_str, e
+            );
+            None
+        })
+    } else {
+        None
+    };
+
+    let build = match result {
+        Ok(()) => {
+            let status = match warnings.first() {
+                Some(warning) => StepStatus::OkWithWarnings(warning.clone()),
+                None => StepStatus::Ok,
+            };
+            build_state.record_build(&project.url_path, commit, cargo_lock_sha256, warnings);
+            build_state.record_release(&project.url_path, release);
+            if let Some(links) = broken_links {
+                build_state.record_links(&project.url_path, links);
+            }
+            status
+        }
+        Err(e) => {
+            error!("Failed to build {}: {}", path_str, e);
+            let errors = match &e {
+                AppError::BuildFailed { errors, .. } => errors.clone(),
+                _ => Vec::new(),
+            };
+            build_state.record_failure(&project.url_path, errors);
+            StepStatus::Failed(e.to_string())
+        }
+    };
+
+    ProjectOutcome {
+        update,
+        build,
+        hooks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_detect_docs_path() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(detect_docs_path(dir.path()).await, None);
+
+        // a directory without HTML files isn't a candidate
+        std::fs::create_dir_all(dir.path().join("target/doc")).unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/index.html"), "").unwrap();
+        assert_eq!(
+            detect_docs_path(dir.path()).await,
+            Some(PathBuf::from("docs"))
+        );
+
+        std::fs::write(dir.path().join("target/doc/help.html"), "").unwrap();
+        assert_eq!(
+            detect_docs_path(dir.path()).await,
+            Some(PathBuf::from("target/doc"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_warning() {
+        let dir = TempDir::new().unwrap();
+        let docs = dir.path().join("doc");
+
+        let warning = output_warning(&docs, "index.html").await.unwrap();
+        assert!(warning.ends_with("doesn't exist"), "{}", warning);
+
+        std::fs::create_dir(&docs).unwrap();
+        let warning = output_warning(&docs, "index.html").await.unwrap();
+        assert!(warning.ends_with("is empty"), "{}", warning);
+
+        std::fs::write(docs.join("style.css"), "").unwrap();
+        std::fs::create_dir(docs.join("html")).unwrap();
+        let warning = output_warning(&docs, "html/index.html").await.unwrap();
+        assert!(
+            warning.starts_with("html/index.html is missing") && warning.ends_with("(2 entries)"),
+            "{}",
+            warning
+        );
+
+        std::fs::write(docs.join("html/index.html"), "").unwrap();
+        assert_eq!(output_warning(&docs, "html/index.html").await, None);
+    }
+}
@@ -0,0 +1,284 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path, sync::Arc};
+
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    badges::cargo_package,
+    config::{BuildSystem, Project},
+    error::AppResult,
+    git::normalize_repo_url,
+    versions, zig, AppState,
+};
+
+/// Version of the [`PortalManifest`] layout, bumped whenever a field is renamed, removed or
+/// changes meaning. Adding fields doesn't bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Where the docs of every project are, for other tools linking to them. URLs are paths on
+/// this server.
+#[derive(Debug, Serialize)]
+pub struct PortalManifest {
+    pub schema_version: u32,
+    pub projects: Vec<PortalProject>,
+    /// Docs URL by normalized repository URL, see [`normalize_repo_url`]
+    pub repos: BTreeMap<String, String>,
+    /// Docs URL by crate or package name
+    pub packages: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortalProject {
+    pub slug: String,
+    pub name: String,
+    pub url: String,
+    pub repo: Option<String>,
+    pub package: Option<String>,
+    /// For projects with `branches`, default first
+    pub branches: Vec<PortalLink>,
+    /// For projects with `versions_from_dirs`, newest first
+    pub versions: Vec<PortalLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortalLink {
+    pub name: String,
+    pub url: String,
+}
+
+/// Name the project is published under: the Cargo package, or the Zig package
+async fn package_name(project: &Project, libs_path: &Path) -> Option<String> {
+    let project_dir = libs_path.join(&project.config.path);
+    match project.config.build_system {
+        BuildSystem::Cargo => cargo_package(&project_dir).await,
+        BuildSystem::Zig => zig::library::find_project(&project_dir)
+            .await
+            .map(|p| p.name),
+        _ => None,
+    }
+}
+
+/// Builds the manifest from the configured projects. The branches of a project are listed
+/// under the slug they share, with the package name read from the default one.
+pub async fn build<'a>(
+    libs_path: &Path,
+    projects: impl IntoIterator<Item = &'a Project>,
+) -> PortalManifest {
+    let mut projects = projects.into_iter().collect::<Vec<_>>();
+    projects.sort_by(|a, b| a.url_path.cmp(&b.url_path));
+
+    let mut entries: Vec<PortalProject> = Vec::new();
+    for project in projects {
+        let slug = project
+            .branch
+            .as_ref()
+            .map_or(&project.url_path, |b| &b.parent);
+        let is_main = project.branch.as_ref().is_none_or(|b| b.is_default);
+        let package = if is_main {
+            package_name(project, libs_path).await
+        } else {
+            None
+        };
+
+        let entry = match entries.last_mut() {
+            Some(entry) if &entry.slug == slug => entry,
+            _ => {
+                entries.push(PortalProject {
+                    slug: slug.clone(),
+                    name: project.name().to_string(),
+                    url: match project.branch {
+                        Some(_) => format!("/{}/", slug),
+                        None => project.index_url(),
+                    },
+                    repo: project.config.repo.as_deref().and_then(normalize_repo_url),
+                    package: None,
+                    branches: Vec::new(),
+                    versions: Vec::new(),
+                });
+                entries.last_mut().expect("just pushed")
+            }
+        };
+        if package.is_some() {
+            entry.package = package;
+        }
+
+        if let Some(branch) = &project.branch {
+            let link = PortalLink {
+                name: branch.name.clone(),
+                url: project.index_url(),
+            };
+            if branch.is_default {
+                entry.branches.insert(0, link);
+            } else {
+                entry.branches.push(link);
+            }
+        }
+        if project.config.versions_from_dirs {
+            entry.versions = versions::list(&project.docs_path)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .map(|version| PortalLink {
+                    url: format!("/{}/{}/", project.url_path, version),
+                    name: version,
+                })
+                .collect();
+        }
+    }
+
+    // several projects may be built from one repository; the first by slug wins
+    let mut repos = BTreeMap::new();
+    let mut packages = BTreeMap::new();
+    for entry in &entries {
+        if let Some(repo) = &entry.repo {
+            repos
+                .entry(repo.clone())
+                .or_insert_with(|| entry.url.clone());
+        }
+        if let Some(package) = &entry.package {
+            packages
+                .entry(package.clone())
+                .or_insert_with(|| entry.url.clone());
+        }
+    }
+
+    PortalManifest {
+        schema_version: SCHEMA_VERSION,
+        projects: entries,
+        repos,
+        packages,
+    }
+}
+
+/// Quoted hex SHA-256 of the manifest, changing whenever any of it does
+fn etag(body: &[u8]) -> String {
+    let mut etag = String::with_capacity(66);
+    etag.push('"');
+    for byte in Sha256::digest(body) {
+        let _ = write!(etag, "{:02x}", byte);
+    }
+    etag.push('"');
+    etag
+}
+
+/// Whether an `If-None-Match` header matches `etag`, weakly as RFC 9110 asks for
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Docs URLs by repository and package name, with an `ETag` so pollers can ask cheaply
+/// whether anything changed
+#[get("/api/manifest")]
+pub async fn portal_manifest(
+    state: web::Data<Arc<AppState>>,
+    req: HttpRequest,
+) -> AppResult<HttpResponse> {
+    let manifest = build(&state.base_path, state.projects.values()).await;
+    let body = serde_json::to_vec(&manifest).map_err(std::io::Error::from)?;
+    let etag = etag(&body);
+
+    if let Some(if_none_match) = req.headers().get(header::IF_NONE_MATCH)
+        && if_none_match
+            .to_str()
+            .is_ok_and(|value| matches_etag(value, &etag))
+    {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .content_type("application/json")
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_matches_etag() {
+        assert!(matches_etag("\"abc\"", "\"abc\""));
+        assert!(matches_etag("\"old\", W/\"abc\"", "\"abc\""));
+        assert!(matches_etag("*", "\"abc\""));
+        assert!(!matches_etag("\"old\"", "\"abc\""));
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        let libs = TempDir::new().unwrap();
+        std::fs::create_dir_all(libs.path().join("serde")).unwrap();
+        std::fs::write(
+            libs.path().join("serde/Cargo.toml"),
+            "[package]\nname = \"serde_core\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(libs.path().join("handbook/v1.2.0")).unwrap();
+        std::fs::create_dir_all(libs.path().join("handbook/v1.10.0")).unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            r#"[[projects]]
+path = "serde"
+build_system = "cargo"
+repo = "git@github.com:serde-rs/serde.git"
+
+[[projects]]
+path = "client"
+build_system = "custom"
+repo = "https://github.com/org/client"
+branches = ["main", "next"]
+default_branch = "main"
+
+[[projects]]
+path = "handbook"
+build_system = "none"
+versions_from_dirs = true
+"#,
+        )
+        .await;
+
+        let manifest = build(libs.path(), projects.values()).await;
+        assert_eq!(manifest.schema_version, SCHEMA_VERSION);
+        let slugs = manifest
+            .projects
+            .iter()
+            .map(|p| p.slug.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(slugs, ["client", "handbook", "serde"]);
+
+        let client = &manifest.projects[0];
+        assert_eq!(client.url, "/client/");
+        let branches = client
+            .branches
+            .iter()
+            .map(|b| (b.name.as_str(), b.url.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            branches,
+            [("main", "/client/main/"), ("next", "/client/next/")]
+        );
+
+        let versions = manifest.projects[1]
+            .versions
+            .iter()
+            .map(|v| v.url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(versions, ["/handbook/v1.10.0/", "/handbook/v1.2.0/"]);
+
+        assert_eq!(
+            manifest.repos["https://github.com/serde-rs/serde"],
+            "/serde/"
+        );
+        assert_eq!(manifest.repos["https://github.com/org/client"], "/client/");
+        assert_eq!(manifest.packages["serde_core"], "/serde/");
+    }
+}
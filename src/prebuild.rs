@@ -0,0 +1,205 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::{process::Command, time};
+use tracing::{debug, warn};
+
+use crate::{
+    config::ProjectConfig,
+    error::{AppError, AppResult},
+    sandbox::SandboxConfig,
+    BuildCommand,
+};
+
+/// Absolute path of the project's `pre_build_script`, resolved against the project directory
+pub fn script_path(project: &ProjectConfig, project_path: &Path) -> Option<PathBuf> {
+    let script = project.pre_build_script.as_ref()?;
+    let script = project_path.join(script);
+    Some(std::path::absolute(&script).unwrap_or(script))
+}
+
+/// How the script is run, before sandboxing
+pub fn command(script: &Path, project_path: &Path) -> BuildCommand {
+    BuildCommand::new("sh", &[&script.to_string_lossy()], project_path)
+}
+
+/// Runs the project's `pre_build_script` with `sh`, if it has one. The script runs in a
+/// process group of its own, which is killed as a whole if it outlives `pre_build_timeout_secs`.
+pub async fn run(
+    project: &ProjectConfig,
+    project_path: &Path,
+    sandbox: &SandboxConfig,
+    sandboxed: bool,
+) -> AppResult<()> {
+    let Some(script) = script_path(project, project_path) else {
+        return Ok(());
+    };
+    if !script.is_file() {
+        return Err(AppError::Build(format!(
+            "pre_build_script {} doesn't exist",
+            script.display()
+        )));
+    }
+
+    let command = command(&script, project_path);
+    let command = if sandboxed {
+        sandbox.wrap(command, project_path)
+    } else {
+        command
+    };
+    debug!("Running {}", command);
+
+    let absolute = std::path::absolute(project_path).unwrap_or_else(|_| project_path.to_path_buf());
+    let mut process = Command::new(&command.program);
+    process
+        .args(&command.args)
+        .current_dir(&command.cwd)
+        .env("PROJECT_PATH", absolute)
+        .env(
+            "PROJECT_NAME",
+            project.display_name.as_deref().unwrap_or(&project.path),
+        )
+        .env("BUILD_SYSTEM", project.build_system.name())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    process.process_group(0);
+
+    let mut child = process.spawn()?;
+    let timeout = Duration::from_secs(project.pre_build_timeout_secs);
+    let status = match time::timeout(timeout, child.wait()).await {
+        Ok(status) => status?,
+        Err(_) => {
+            kill_group(&mut child).await;
+            return Err(AppError::Build(format!(
+                "pre_build_script {} timed out after {}s",
+                script.display(),
+                timeout.as_secs()
+            )));
+        }
+    };
+
+    if !status.success() {
+        return Err(AppError::BuildFailed {
+            status: format!("pre_build_script {}", status),
+            errors: Vec::new(),
+        });
+    }
+    Ok(())
+}
+
+/// Kills the script and everything it spawned
+async fn kill_group(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: the child leads its own process group, so -pid addresses only the script
+        // and its descendants
+        if unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+            warn!(
+                "Failed to kill process group {}: {}",
+                pid,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    if let Err(e) = child.kill().await {
+        warn!("Failed to kill pre_build_script: {}", e);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Instant;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn project(script: &str, timeout_secs: u64) -> ProjectConfig {
+        let config = crate::config::parse_config(
+            Path::new("/"),
+            &format!(
+                "[[projects]]\npath = \"lib\"\ndisplay_name = \"Lib\"\nbuild_system = \"custom\"\npre_build_script = {:?}\npre_build_timeout_secs = {}\n",
+                script, timeout_secs
+            ),
+        );
+        config.projects.into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_script_environment() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("pre.sh"),
+            "echo \"$PROJECT_PATH $PROJECT_NAME $BUILD_SYSTEM\" > env.txt\n",
+        )
+        .unwrap();
+
+        run(
+            &project("pre.sh", 10),
+            dir.path(),
+            &SandboxConfig::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        let env = std::fs::read_to_string(dir.path().join("env.txt")).unwrap();
+        assert_eq!(
+            env.trim(),
+            format!(
+                "{} Lib custom",
+                std::path::absolute(dir.path()).unwrap().display()
+            )
+        );
+    }
+
+    /// Whether `pid` runs; killed processes nobody reaped yet are zombies, as good as gone
+    fn is_alive(pid: libc::pid_t) -> bool {
+        // SAFETY: signal 0 only checks whether the process exists
+        if unsafe { libc::kill(pid, 0) } != 0 {
+            return false;
+        }
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                let state = stat.rsplit_once(") ")?.1.chars().next()?;
+                Some(state != 'Z')
+            })
+            .unwrap_or(true)
+    }
+
+    #[tokio::test]
+    async fn test_script_failure_and_timeout() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("fail.sh"), "exit 3\n").unwrap();
+        std::fs::write(
+            dir.path().join("slow.sh"),
+            "sleep 30 &\necho $! > sleep.pid\nsleep 30\n",
+        )
+        .unwrap();
+        let sandbox = SandboxConfig::default();
+
+        let result = run(&project("fail.sh", 10), dir.path(), &sandbox, false).await;
+        assert!(matches!(result, Err(AppError::BuildFailed { .. })));
+
+        let started = Instant::now();
+        let result = run(&project("slow.sh", 1), dir.path(), &sandbox, false).await;
+        assert!(matches!(result, Err(AppError::Build(_))));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // what the script left running in the background is killed with it
+        let pid: libc::pid_t = std::fs::read_to_string(dir.path().join("sleep.pid"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while is_alive(pid) && Instant::now() < deadline {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!is_alive(pid), "background process {} survived", pid);
+
+        let result = run(&project("missing.sh", 1), dir.path(), &sandbox, false).await;
+        assert!(matches!(result, Err(AppError::Build(_))));
+    }
+}
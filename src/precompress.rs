@@ -0,0 +1,359 @@
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use actix_files::NamedFile;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, ContentDisposition, ContentEncoding, DispositionParam, DispositionType},
+        Method,
+    },
+    middleware::Next,
+    web, Responder,
+};
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder};
+use tokio::{
+    fs,
+    io::{AsyncWrite, AsyncWriteExt},
+    time,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    artifact,
+    config::Project,
+    error::AppResult,
+    filter::{IgnoreRules, ServeFilter},
+    history::project_logs_dir,
+    rewrite,
+    utils::is_contained_path,
+    AppState,
+};
+
+/// Mirror of a project's docs holding the compressed variants, inside its logs directory so
+/// they don't end up in the manifest or in archives
+const COMPRESSED_DIR: &str = "compressed";
+
+/// Files smaller than this aren't worth compressing
+const MIN_SIZE: u64 = 256;
+
+/// Text formats that compress well; everything else (images, fonts, archives) already is
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &[
+    "html", "htm", "css", "js", "mjs", "json", "svg", "xml", "txt", "md", "map", "wasm",
+];
+
+/// Encodings variants are generated for, in the order they're preferred when serving
+const ENCODINGS: &[(&str, &str, ContentEncoding)] = &[
+    ("br", "br", ContentEncoding::Brotli),
+    ("gzip", "gz", ContentEncoding::Gzip),
+];
+
+/// What [`run`] did
+#[derive(Debug, Default, PartialEq)]
+pub struct PrecompressStats {
+    pub compressed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+fn compressed_dir(libs_path: &Path, url_path: &str) -> PathBuf {
+    project_logs_dir(libs_path, url_path).join(COMPRESSED_DIR)
+}
+
+fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| COMPRESSIBLE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Generates `.br` and `.gz` variants of the project's compressible files, at most
+/// `files_per_sec` files a second. Variants already newer than their file are kept, and those
+/// of files that are gone or no longer compressible are removed.
+pub async fn run(
+    libs_path: &Path,
+    project: &Project,
+    files_per_sec: u32,
+) -> AppResult<PrecompressStats> {
+    let target = compressed_dir(libs_path, &project.url_path);
+    // ignored files are still served, so they get variants too
+    let sources = artifact::list_files(
+        &project.docs_path,
+        &project.serve_filter,
+        &IgnoreRules::default(),
+    )
+    .await?;
+    let mut stats = PrecompressStats::default();
+    let mut expected = HashSet::new();
+
+    let mut ticks = time::interval(Duration::from_secs(1) / files_per_sec.max(1));
+    for source in sources {
+        let path = project.docs_path.join(&source);
+        let metadata = fs::metadata(&path).await?;
+        if !is_compressible(&source) || metadata.len() < MIN_SIZE {
+            continue;
+        }
+
+        let mut stale = false;
+        for (_, extension, _) in ENCODINGS {
+            let variant = variant_path(&target, &source, extension);
+            let fresh = match (fs::metadata(&variant).await, metadata.modified()) {
+                (Ok(variant), Ok(modified)) => variant.modified().is_ok_and(|v| v >= modified),
+                _ => false,
+            };
+            stale |= !fresh;
+            expected.insert(variant);
+        }
+        if !stale {
+            stats.unchanged += 1;
+            continue;
+        }
+
+        ticks.tick().await;
+        let contents = fs::read(&path).await?;
+        for (_, extension, encoding) in ENCODINGS {
+            let compressed = compress(&contents, *encoding).await?;
+            write_atomically(&variant_path(&target, &source, extension), &compressed).await?;
+        }
+        stats.compressed += 1;
+    }
+
+    // variants of files a later build deleted or changed into something else
+    if fs::try_exists(&target).await? {
+        let everything = ServeFilter::new(&[], &[], &project.config.path)?;
+        for variant in artifact::list_files(&target, &everything, &IgnoreRules::default()).await? {
+            let variant = target.join(variant);
+            if !expected.contains(&variant) {
+                fs::remove_file(&variant).await?;
+                stats.removed += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+fn variant_path(target: &Path, source: &Path, extension: &str) -> PathBuf {
+    let mut name = source.as_os_str().to_os_string();
+    name.push(".");
+    name.push(extension);
+    target.join(name)
+}
+
+async fn compress(contents: &[u8], encoding: ContentEncoding) -> io::Result<Vec<u8>> {
+    async fn finish(mut encoder: impl AsyncWrite + Unpin, contents: &[u8]) -> io::Result<()> {
+        encoder.write_all(contents).await?;
+        encoder.shutdown().await
+    }
+
+    match encoding {
+        ContentEncoding::Brotli => {
+            let mut encoder = BrotliEncoder::new(Vec::new());
+            finish(&mut encoder, contents).await?;
+            Ok(encoder.into_inner())
+        }
+        _ => {
+            let mut encoder = GzipEncoder::new(Vec::new());
+            finish(&mut encoder, contents).await?;
+            Ok(encoder.into_inner())
+        }
+    }
+}
+
+async fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(format!(".tmp-{}", std::process::id()));
+    fs::write(&temp, contents).await?;
+    fs::rename(&temp, path).await
+}
+
+/// Whether an `Accept-Encoding` header value allows `encoding`
+fn accepts(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let refused = parts.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        name.eq_ignore_ascii_case(encoding) && !refused
+    })
+}
+
+/// Serves the compressed variant of a file of a project with `precompress`, when the client
+/// accepts it. Everything else, including paths the serve filter rejects, goes on to the
+/// regular file service.
+pub async fn serve_precompressed(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let variant = match req.app_data::<web::Data<Arc<AppState>>>() {
+        Some(state) if matches!(*req.method(), Method::GET | Method::HEAD) => {
+            find_variant(state, &req).await
+        }
+        _ => None,
+    };
+    let Some((source, path, encoding)) = variant else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+
+    let file = match NamedFile::open_async(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            debug!("Failed to open {}: {}", path.display(), e);
+            return next.call(req).await.map(|res| res.map_into_left_body());
+        }
+    };
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = file
+        .set_content_type(actix_files::file_extension_to_mime(extension))
+        .set_content_encoding(encoding)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        });
+
+    let (req, _) = req.into_parts();
+    let mut response = file.respond_to(&req);
+    response.headers_mut().insert(
+        header::VARY,
+        header::HeaderValue::from_static("Accept-Encoding"),
+    );
+    Ok(ServiceResponse::new(req, response).map_into_right_body())
+}
+
+/// The file a request is for, its best compressed variant and that variant's encoding
+async fn find_variant(
+    state: &AppState,
+    req: &ServiceRequest,
+) -> Option<(PathBuf, PathBuf, ContentEncoding)> {
+    let accept_encoding = req.headers().get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+    let (slug, rest) = rewrite::project_path(state, req.path())?;
+    let project = state.projects.get(slug)?;
+    // percent-encoded paths are left to the file service to decode
+    if !project.config.precompress || rest.contains('%') {
+        return None;
+    }
+    // the root of these is a page of their own
+    if rest.is_empty() && (project.config.entry_file.is_some() || project.config.versions_from_dirs)
+    {
+        return None;
+    }
+
+    let source = if rest.is_empty() || rest.ends_with('/') {
+        PathBuf::from(format!("{}{}", rest, project.index_file_name()))
+    } else {
+        PathBuf::from(rest)
+    };
+    if !is_contained_path(&source.to_string_lossy()) || !project.serve_filter.allows(&source) {
+        return None;
+    }
+
+    let target = compressed_dir(&state.base_path, &project.url_path);
+    for (name, extension, encoding) in ENCODINGS {
+        if !accepts(accept_encoding, name) {
+            continue;
+        }
+        let variant = variant_path(&target, &source, extension);
+        if fs::metadata(&variant).await.is_ok_and(|m| m.is_file()) {
+            return Some((source, variant, *encoding));
+        }
+    }
+    None
+}
+
+/// Removes all of a project's variants, for docs whose modification times can't be compared
+pub async fn discard(libs_path: &Path, url_path: &str) {
+    let target = compressed_dir(libs_path, url_path);
+    match fs::remove_dir_all(&target).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("Failed to remove {}: {}", target.display(), e)
+        }
+        _ => {}
+    }
+}
+
+/// Runs [`run`] for freshly built docs, logging what it did without failing the build
+pub async fn after_build(libs_path: &Path, project: &Project) {
+    match run(libs_path, project, project.config.precompress_files_per_sec).await {
+        Ok(stats) => debug!(
+            "Precompressed docs of {}: {} compressed, {} unchanged, {} removed",
+            project.config.path, stats.compressed, stats.unchanged, stats.removed
+        ),
+        Err(e) => warn!(
+            "Failed to precompress docs of {}: {}",
+            project.config.path, e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_accepts() {
+        assert!(accepts("gzip, deflate, br", "br"));
+        assert!(accepts("gzip;q=0.8, br;q=1.0", "gzip"));
+        assert!(!accepts("gzip;q=0, br", "gzip"));
+        assert!(!accepts("identity", "gzip"));
+    }
+
+    #[tokio::test]
+    async fn test_run_removes_stale_variants() {
+        let libs = TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(docs.join("api")).unwrap();
+        let page = "<p>documentation</p>".repeat(50);
+        std::fs::write(docs.join("index.html"), &page).unwrap();
+        std::fs::write(docs.join("api/old.html"), &page).unwrap();
+        std::fs::write(docs.join("logo.png"), &page).unwrap();
+        std::fs::write(docs.join("tiny.js"), "x").unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\nprecompress = true\n",
+        )
+        .await;
+        let project = &projects["lib"];
+
+        let stats = run(libs.path(), project, 1000).await.unwrap();
+        assert_eq!(stats.compressed, 2);
+        let target = compressed_dir(libs.path(), "lib");
+        assert!(target.join("index.html.br").is_file());
+        assert!(target.join("api/old.html.gz").is_file());
+        assert!(!target.join("logo.png.gz").exists());
+        assert!(!target.join("tiny.js.gz").exists());
+
+        std::fs::remove_file(docs.join("api/old.html")).unwrap();
+        let stats = run(libs.path(), project, 1000).await.unwrap();
+        assert_eq!(
+            stats,
+            PrecompressStats {
+                compressed: 0,
+                unchanged: 1,
+                removed: 2,
+            }
+        );
+        assert!(!target.join("api/old.html.gz").exists());
+    }
+}
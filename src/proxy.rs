@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use actix_web::{
+    http::{
+        header::{self, HeaderValue},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+
+/// Headers describing a single connection, which a proxy must not forward (RFC 9110 §7.6.1)
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Request headers upstream doesn't get: credentials meant for lichen's origin, which a
+/// third-party site mustn't see, and the host, which reqwest sets from the URL
+const REQUEST_WITHHELD: &[&str] = &["authorization", "cookie", "host"];
+/// Response headers that would set cookies on lichen's origin
+const RESPONSE_WITHHELD: &[&str] = &["set-cookie"];
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+/// Headers named by the `Connection` header `values`, which are hop-by-hop as well
+fn connection_listed<'a>(values: impl Iterator<Item = &'a [u8]>) -> Vec<String> {
+    values
+        .filter_map(|value| std::str::from_utf8(value).ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Whether a header passes through the proxy, given the ones its message's `Connection`
+/// header lists and the ones never passed in its direction
+fn is_forwarded(name: &str, listed: &[String], withheld: &[&str]) -> bool {
+    !is_hop_by_hop(name)
+        && !listed.iter().any(|l| name.eq_ignore_ascii_case(l))
+        && !withheld.iter().any(|w| name.eq_ignore_ascii_case(w))
+}
+
+/// The upstream URL for `tail` (the request path below the project's slug) and `query`
+pub fn upstream_url(base: &str, tail: &str, query: &str) -> String {
+    let mut url = format!("{}/{}", base.trim_end_matches('/'), tail);
+    if !query.is_empty() {
+        url.push('?');
+        url.push_str(query);
+    }
+    url
+}
+
+/// Forwards requests for a project's docs to an external site
+#[derive(Debug, Clone)]
+pub struct DocsProxy {
+    upstream: String,
+    client: reqwest::Client,
+}
+
+impl DocsProxy {
+    pub fn new(upstream: &str, timeout: Duration) -> AppResult<Self> {
+        let url = reqwest::Url::parse(upstream).map_err(|e| {
+            AppError::Config(format!("invalid proxy_docs_url '{}': {}", upstream, e))
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::Config(format!(
+                "proxy_docs_url '{}' must be an http(s) URL",
+                upstream
+            )));
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            // redirects are the client's business, and must keep pointing at lichen
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| AppError::Config(format!("failed to create proxy client: {}", e)))?;
+
+        Ok(Self {
+            upstream: upstream.to_string(),
+            client,
+        })
+    }
+
+    /// Sends `req` upstream and streams the answer back, as 502 if upstream can't be reached
+    /// or 504 if it doesn't answer in time
+    pub async fn forward(&self, req: &HttpRequest, body: web::Bytes, tail: &str) -> HttpResponse {
+        let url = upstream_url(&self.upstream, tail, req.query_string());
+        let Ok(method) = reqwest::Method::from_bytes(req.method().as_str().as_bytes()) else {
+            return HttpResponse::MethodNotAllowed().finish();
+        };
+
+        let mut request = self.client.request(method, &url).body(body);
+        let listed = connection_listed(
+            req.headers()
+                .get_all(header::CONNECTION)
+                .map(HeaderValue::as_bytes),
+        );
+        for (name, value) in req.headers() {
+            if is_forwarded(name.as_str(), &listed, REQUEST_WITHHELD) {
+                request = request.header(name.as_str(), value.as_bytes());
+            }
+        }
+        {
+            let info = req.connection_info();
+            request = request
+                .header("x-forwarded-host", info.host())
+                .header("x-forwarded-proto", info.scheme());
+            if let Some(peer) = info.realip_remote_addr() {
+                request = request.header("x-forwarded-for", peer);
+            }
+        }
+
+        let upstream = match request.send().await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                warn!("Proxying {} failed: {}", url, e);
+                return if e.is_timeout() {
+                    HttpResponse::GatewayTimeout().finish()
+                } else {
+                    HttpResponse::BadGateway().finish()
+                };
+            }
+        };
+
+        let status =
+            StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let mut response = HttpResponse::build(status);
+        let listed = connection_listed(
+            upstream
+                .headers()
+                .get_all(reqwest::header::CONNECTION)
+                .iter()
+                .map(reqwest::header::HeaderValue::as_bytes),
+        );
+        for (name, value) in upstream.headers() {
+            if is_forwarded(name.as_str(), &listed, RESPONSE_WITHHELD)
+                && let Ok(value) = HeaderValue::from_bytes(value.as_bytes())
+            {
+                response.append_header((name.as_str(), value));
+            }
+        }
+        response.streaming(upstream.bytes_stream())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_url() {
+        assert_eq!(
+            upstream_url("https://docs.rs/serde/latest", "serde/index.html", ""),
+            "https://docs.rs/serde/latest/serde/index.html"
+        );
+        assert_eq!(
+            upstream_url("https://example.com/docs/", "", "search=map"),
+            "https://example.com/docs/?search=map"
+        );
+    }
+
+    #[test]
+    fn test_is_hop_by_hop() {
+        assert!(is_hop_by_hop("Connection"));
+        assert!(is_hop_by_hop("transfer-encoding"));
+        assert!(!is_hop_by_hop("content-type"));
+        assert!(!is_hop_by_hop("cache-control"));
+    }
+
+    #[test]
+    fn test_is_forwarded() {
+        let listed = connection_listed(
+            [b"keep-alive, X-Session".as_slice(), b"x-trace".as_slice()].into_iter(),
+        );
+        assert_eq!(listed, ["keep-alive", "x-session", "x-trace"]);
+
+        assert!(is_forwarded("Accept", &listed, REQUEST_WITHHELD));
+        assert!(!is_forwarded("X-Session", &listed, REQUEST_WITHHELD));
+        assert!(!is_forwarded("x-trace", &listed, REQUEST_WITHHELD));
+        assert!(!is_forwarded("Cookie", &[], REQUEST_WITHHELD));
+        assert!(!is_forwarded("authorization", &[], REQUEST_WITHHELD));
+        assert!(!is_forwarded("host", &[], REQUEST_WITHHELD));
+        assert!(!is_forwarded("Set-Cookie", &[], RESPONSE_WITHHELD));
+        assert!(is_forwarded("set-cookie", &[], REQUEST_WITHHELD));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_urls() {
+        let timeout = Duration::from_secs(1);
+        assert!(DocsProxy::new("https://docs.rs/serde", timeout).is_ok());
+        assert!(DocsProxy::new("docs.rs/serde", timeout).is_err());
+        assert!(DocsProxy::new("ftp://example.com", timeout).is_err());
+    }
+}
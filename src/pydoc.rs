@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use crate::{error::AppResult, utils::escape_html};
+
+/// Directory of a project the modules are documented from, and `pydoc` runs in so they import
+pub const SOURCE_DIR: &str = "src";
+
+/// Directory of a project the pages end up in
+pub const DOCS_DIR: &str = "docs";
+
+/// Interpreter run when `python_path` isn't set
+pub const DEFAULT_PYTHON: &str = "python3";
+
+/// Dotted name of the module at `path` inside the source directory: `pkg/sub/mod.py` is
+/// `pkg.sub.mod` and `pkg/__init__.py` is `pkg`. `None` for files that aren't importable
+/// modules, like `__main__.py` or names with dashes.
+fn module_name(path: &Path) -> Option<String> {
+    let mut parts = path
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()?;
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    let importable = |part: &String| {
+        part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && part != "__main__"
+    };
+    (!parts.is_empty() && parts.iter().all(importable)).then(|| parts.join("."))
+}
+
+/// The modules of the `.py` files in the project's source directory, sorted. Hidden
+/// directories and `__pycache__` are skipped.
+pub async fn find_modules(project_path: &Path) -> Vec<String> {
+    let root = project_path.join(SOURCE_DIR);
+    let mut modules = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = fs::read_dir(root.join(&dir)).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name();
+            let relative = dir.join(&name);
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !name.to_string_lossy().starts_with('.') && name != "__pycache__" {
+                    pending.push(relative);
+                }
+            } else if relative.extension().is_some_and(|e| e == "py")
+                && let Some(module) = module_name(&relative)
+            {
+                modules.push(module);
+            }
+        }
+    }
+    modules.sort();
+    modules
+}
+
+fn module_index(modules: &[String]) -> String {
+    let links = modules
+        .iter()
+        .map(|module| {
+            let name = escape_html(module);
+            format!("<li><a href=\"{}.html\">{}</a></li>", name, name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>Modules</title>
+    <style>
+        body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; }}
+        a {{ text-decoration: none; color: #0366d6; }}
+    </style>
+</head>
+<body>
+    <h1>Modules</h1>
+    <ul>
+{links}
+    </ul>
+</body>
+</html>
+"#
+    )
+}
+
+/// Moves the pages `pydoc -w` wrote into the source directory to `docs_path`, replacing the
+/// previous ones, and writes an index page linking them
+pub async fn collect_html(project_path: &Path, docs_path: &Path) -> AppResult<()> {
+    let modules = find_modules(project_path).await;
+    if fs::try_exists(docs_path).await? {
+        fs::remove_dir_all(docs_path).await?;
+    }
+    fs::create_dir_all(docs_path).await?;
+
+    let source = project_path.join(SOURCE_DIR);
+    for module in &modules {
+        let page = format!("{}.html", module);
+        if fs::try_exists(source.join(&page)).await? {
+            fs::rename(source.join(&page), docs_path.join(&page)).await?;
+        }
+    }
+    fs::write(docs_path.join("index.html"), module_index(&modules)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name(Path::new("mod.py")).as_deref(), Some("mod"));
+        assert_eq!(
+            module_name(Path::new("pkg/sub/mod.py")).as_deref(),
+            Some("pkg.sub.mod")
+        );
+        assert_eq!(
+            module_name(Path::new("pkg/__init__.py")).as_deref(),
+            Some("pkg")
+        );
+        assert_eq!(module_name(Path::new("pkg/__main__.py")), None);
+        assert_eq!(module_name(Path::new("my-scripts/tool.py")), None);
+        assert_eq!(module_name(Path::new("__init__.py")), None);
+    }
+
+    #[tokio::test]
+    async fn test_collect_html() {
+        let project = TempDir::new().unwrap();
+        let src = project.path().join(SOURCE_DIR);
+        std::fs::create_dir_all(src.join("pkg/__pycache__")).unwrap();
+        std::fs::create_dir_all(src.join(".venv")).unwrap();
+        for file in [
+            "pkg/__init__.py",
+            "pkg/util.py",
+            "pkg/__pycache__/util.py",
+            ".venv/site.py",
+            "notes.txt",
+        ] {
+            std::fs::write(src.join(file), "").unwrap();
+        }
+        assert_eq!(find_modules(project.path()).await, ["pkg", "pkg.util"]);
+
+        std::fs::write(src.join("pkg.html"), "pkg").unwrap();
+        std::fs::write(src.join("pkg.util.html"), "util").unwrap();
+        let docs = project.path().join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("removed.html"), "old").unwrap();
+
+        collect_html(project.path(), &docs).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(docs.join("pkg.util.html")).unwrap(),
+            "util"
+        );
+        assert!(!src.join("pkg.html").exists());
+        assert!(!docs.join("removed.html").exists());
+        let index = std::fs::read_to_string(docs.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"pkg.util.html\">pkg.util</a>"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+    config::Project,
+    error::{AppError, AppResult},
+    git::CommitInfo,
+};
+
+/// Default file name of the report, next to the docs directory
+const REPORT_FILE: &str = ".lichen-report.json";
+
+/// What started a build
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// `update_on_start` when the server started
+    Startup,
+    /// `lichen run`
+    Cli,
+}
+
+/// A build command and how it exited
+#[derive(Debug, Serialize)]
+pub struct CommandRun {
+    pub command: String,
+    /// `None` if it was killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// Outcome of one build, written next to the docs for release pipelines to pick up
+#[derive(Debug, Serialize)]
+pub struct BuildReport<'a> {
+    pub project: &'a str,
+    pub trigger: Trigger,
+    /// Unix timestamps (seconds)
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Build commands in the order they ran, up to the first failing one
+    pub commands: &'a [CommandRun],
+    pub commit: Option<&'a CommitInfo>,
+    /// SHA-256 of the `Cargo.lock` the build used, if there was one
+    pub cargo_lock_sha256: Option<&'a str>,
+    pub warnings: &'a [String],
+    /// SHA-256 of the docs manifest (see `/api/projects/{project}/manifest`), for successful
+    /// builds
+    pub manifest_sha256: Option<String>,
+}
+
+/// Where the project's build report is written: `build_report_path` relative to the project
+/// directory, or `.lichen-report.json` next to the docs directory
+pub fn path(libs_path: &Path, project: &Project) -> PathBuf {
+    match &project.config.build_report_path {
+        Some(path) => libs_path.join(&project.config.path).join(path),
+        None => project
+            .docs_path
+            .parent()
+            .unwrap_or(&project.docs_path)
+            .join(REPORT_FILE),
+    }
+}
+
+/// Writes the report to a temporary file renamed over `path`, so readers never see half of it
+pub async fn write(path: &Path, report: &BuildReport<'_>) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let json = serde_json::to_string_pretty(report).map_err(std::io::Error::from)?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp-{}", file_name, std::process::id()));
+    fs::write(&temp, json).await?;
+    if let Err(e) = fs::rename(&temp, path).await {
+        let _ = fs::remove_file(&temp).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// The last report written for a project, as JSON
+pub async fn load(path: &Path, url_path: &str) -> AppResult<String> {
+    fs::read_to_string(path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            AppError::NotFound(format!("build report of project '{}'", url_path))
+        }
+        _ => AppError::Io(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_replaces_report() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("target/.lichen-report.json");
+        let commands = [CommandRun {
+            command: "cargo doc".to_string(),
+            exit_code: Some(101),
+        }];
+        let mut report = BuildReport {
+            project: "lib",
+            trigger: Trigger::Cli,
+            started_at: 100,
+            finished_at: 102,
+            duration_ms: 2000,
+            success: false,
+            error: Some("Build failed (exit status: 101)".to_string()),
+            commands: &commands,
+            commit: None,
+            cargo_lock_sha256: None,
+            warnings: &[],
+            manifest_sha256: None,
+        };
+
+        assert!(matches!(
+            load(&path, "lib").await,
+            Err(AppError::NotFound(_))
+        ));
+        write(&path, &report).await.unwrap();
+        report.success = true;
+        write(&path, &report).await.unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&load(&path, "lib").await.unwrap()).unwrap();
+        assert_eq!(json["success"], true);
+        assert_eq!(json["trigger"], "cli");
+        assert_eq!(json["commands"][0]["exit_code"], 101);
+        // only the report itself is left behind
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fmt, sync::Arc};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::Uri,
+    middleware::Next,
+    web, HttpResponse,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    error::{AppError, AppResult},
+    AppState,
+};
+
+/// What happens to a request whose path a rule matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteMode {
+    /// Serve the rewritten path in place of the requested one
+    #[default]
+    Internal,
+    /// Redirect permanently to the rewritten path
+    Redirect,
+}
+
+/// A rewrite rule as written in the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct RewriteConfig {
+    /// Regex matched against the path inside the project, e.g. `^/old/(.*)$`
+    pub from: String,
+    /// Replacement, which may refer to groups of `from` as `$1` or `${name}`
+    pub to: String,
+    #[serde(default)]
+    pub mode: RewriteMode,
+}
+
+/// A compiled [`RewriteConfig`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Rewrite {
+    #[serde(serialize_with = "serialize_regex")]
+    from: Regex,
+    to: String,
+    mode: RewriteMode,
+}
+
+fn serialize_regex<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
+
+impl fmt::Display for Rewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            RewriteMode::Internal => "internal",
+            RewriteMode::Redirect => "redirect",
+        };
+        write!(f, "{} -> {} ({})", self.from.as_str(), self.to, mode)
+    }
+}
+
+/// Compiles a project's rewrite rules, in order
+pub fn compile(rules: &[RewriteConfig], project: &str) -> AppResult<Vec<Rewrite>> {
+    rules
+        .iter()
+        .map(|rule| {
+            let from = Regex::new(&rule.from).map_err(|e| {
+                AppError::Config(format!(
+                    "invalid rewrite pattern '{}' of project '{}': {}",
+                    rule.from, project, e
+                ))
+            })?;
+            if !rule.to.starts_with('/') {
+                return Err(AppError::Config(format!(
+                    "rewrite target '{}' of project '{}' must start with '/'",
+                    rule.to, project
+                )));
+            }
+            Ok(Rewrite {
+                from,
+                to: rule.to.clone(),
+                mode: rule.mode,
+            })
+        })
+        .collect()
+}
+
+/// Rewrites `path` (inside the project, starting with `/`) with the first rule matching it.
+/// Only that rule is applied, once, so rules can't loop into each other.
+pub fn apply(rules: &[Rewrite], path: &str) -> Option<(RewriteMode, String)> {
+    let rule = rules.iter().find(|rule| rule.from.is_match(path))?;
+    Some((
+        rule.mode,
+        rule.from.replace(path, rule.to.as_str()).into_owned(),
+    ))
+}
+
+/// Splits a request path into the slug of the project it's for and the path inside it. Slugs
+/// of projects built at several branches span two segments, like `lib/main`.
+pub fn project_path<'a>(state: &AppState, path: &'a str) -> Option<(&'a str, &'a str)> {
+    let path = path.strip_prefix('/')?;
+    let (first, rest) = path.split_once('/')?;
+    if state.projects.contains_key(first) {
+        return Some((first, rest));
+    }
+    let (branch, rest) = rest.split_once('/')?;
+    Some((&path[..first.len() + 1 + branch.len()], rest))
+}
+
+/// Applies the rewrite rules of the project a request is for, before it's routed
+pub async fn rewrite_paths(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(state) = req.app_data::<web::Data<Arc<AppState>>>().cloned() else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+
+    let rewritten = project_path(&state, req.path()).and_then(|(slug, rest)| {
+        let project = state.projects.get(slug)?;
+        let (mode, path) = apply(&project.rewrites, &format!("/{}", rest))?;
+        Some((mode, format!("/{}{}", slug, path)))
+    });
+    let Some((mode, path)) = rewritten else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+
+    let target = match req.query_string() {
+        "" => path,
+        query => format!("{}?{}", path, query),
+    };
+    match mode {
+        RewriteMode::Redirect => {
+            let response = HttpResponse::MovedPermanently()
+                .append_header(("Location", target))
+                .finish();
+            Ok(req.into_response(response).map_into_right_body())
+        }
+        RewriteMode::Internal => {
+            let uri: Uri = target
+                .parse()
+                .map_err(|_| actix_web::error::ErrorBadRequest("invalid rewritten path"))?;
+            req.match_info_mut().get_mut().update(&uri);
+            req.head_mut().uri = uri;
+            next.call(req).await.map(|res| res.map_into_left_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(from: &str, to: &str, mode: RewriteMode) -> RewriteConfig {
+        RewriteConfig {
+            from: from.to_string(),
+            to: to.to_string(),
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let rules = compile(
+            &[
+                rule("^/old/(.*)$", "/apidocs/$1", RewriteMode::Internal),
+                rule("^/apidocs/(.*)$", "/v2/$1", RewriteMode::Redirect),
+                rule(
+                    "^/(?<page>[a-z]+)\\.htm$",
+                    "/${page}.html",
+                    RewriteMode::Redirect,
+                ),
+            ],
+            "lib",
+        )
+        .unwrap();
+
+        assert_eq!(
+            apply(&rules, "/old/com/example/Foo.html"),
+            Some((
+                RewriteMode::Internal,
+                "/apidocs/com/example/Foo.html".to_string()
+            ))
+        );
+        assert_eq!(
+            apply(&rules, "/apidocs/index.html"),
+            Some((RewriteMode::Redirect, "/v2/index.html".to_string()))
+        );
+        assert_eq!(
+            apply(&rules, "/overview.htm"),
+            Some((RewriteMode::Redirect, "/overview.html".to_string()))
+        );
+        assert_eq!(apply(&rules, "/index.html"), None);
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_rules() {
+        assert!(compile(&[rule("^/old/(.*$", "/$1", RewriteMode::Internal)], "lib").is_err());
+        assert!(compile(&[rule("^/old/(.*)$", "$1", RewriteMode::Internal)], "lib").is_err());
+
+        let rules = compile(&[rule("^/a$", "/b", RewriteMode::Redirect)], "lib").unwrap();
+        assert_eq!(rules[0].to_string(), "^/a$ -> /b (redirect)");
+    }
+}
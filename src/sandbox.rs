@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    error::{AppError, AppResult},
+    BuildCommand,
+};
+
+/// Runs build commands through a wrapper like `bwrap`, so they can only write to their project
+#[derive(Debug, Deserialize)]
+pub struct SandboxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Let builds reach the network, e.g. to download dependencies
+    #[serde(default)]
+    pub network: bool,
+    /// Command the build is appended to; `{{project}}` is replaced with the project directory
+    #[serde(default = "default_wrapper")]
+    pub wrapper: Vec<String>,
+    /// Arguments appended to `wrapper` when `network` is enabled
+    #[serde(default = "default_network_args")]
+    pub network_args: Vec<String>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            network: false,
+            wrapper: default_wrapper(),
+            network_args: default_network_args(),
+        }
+    }
+}
+
+fn default_wrapper() -> Vec<String> {
+    [
+        "bwrap",
+        "--ro-bind",
+        "/",
+        "/",
+        "--dev",
+        "/dev",
+        "--proc",
+        "/proc",
+        "--tmpfs",
+        "/tmp",
+        "--bind",
+        "{{project}}",
+        "{{project}}",
+        "--unshare-all",
+        "--die-with-parent",
+    ]
+    .iter()
+    .map(|a| a.to_string())
+    .collect()
+}
+
+fn default_network_args() -> Vec<String> {
+    vec!["--share-net".to_string()]
+}
+
+impl SandboxConfig {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.enabled && self.wrapper.is_empty() {
+            return Err(AppError::Config(
+                "sandbox.wrapper must name a command when the sandbox is enabled".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether builds run sandboxed. Only Linux is supported; elsewhere builds run unconfined
+    /// after a warning. A missing wrapper fails the build rather than silently skipping it.
+    pub fn is_active(&self) -> AppResult<bool> {
+        if !self.enabled {
+            return Ok(false);
+        }
+        if !cfg!(target_os = "linux") {
+            warn!("Build sandboxing is only supported on Linux, building unsandboxed");
+            return Ok(false);
+        }
+        if which::which(&self.wrapper[0]).is_err() {
+            return Err(AppError::Build(format!(
+                "sandbox wrapper '{}' not found",
+                self.wrapper[0]
+            )));
+        }
+        Ok(true)
+    }
+
+    /// `command` run through the wrapper, with `project_path` as the writable directory
+    pub fn wrap(&self, command: BuildCommand, project_path: &Path) -> BuildCommand {
+        let project_path =
+            std::path::absolute(project_path).unwrap_or_else(|_| project_path.to_path_buf());
+        let project = project_path.to_string_lossy();
+
+        let mut args: Vec<String> = self.wrapper[1..]
+            .iter()
+            .map(|arg| arg.replace("{{project}}", &project))
+            .collect();
+        if self.network {
+            args.extend(self.network_args.iter().cloned());
+        }
+        args.push(command.program.to_string_lossy().into_owned());
+        args.extend(command.args);
+
+        BuildCommand {
+            program: self.wrapper[0].clone().into(),
+            args,
+            cwd: command.cwd,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let project = Path::new("/libs/lib");
+        let command = || BuildCommand::new("cargo", &["doc", "--no-deps"], project);
+
+        let sandbox = SandboxConfig {
+            enabled: true,
+            wrapper: vec![
+                "bwrap".to_string(),
+                "--bind".to_string(),
+                "{{project}}".to_string(),
+                "{{project}}".to_string(),
+            ],
+            ..SandboxConfig::default()
+        };
+        let wrapped = sandbox.wrap(command(), project);
+        assert_eq!(wrapped.program, Path::new("bwrap"));
+        assert_eq!(
+            wrapped.args,
+            [
+                "--bind",
+                "/libs/lib",
+                "/libs/lib",
+                "cargo",
+                "doc",
+                "--no-deps"
+            ]
+        );
+        assert_eq!(wrapped.cwd, project);
+
+        let sandbox = SandboxConfig {
+            network: true,
+            ..sandbox
+        };
+        assert_eq!(
+            sandbox.wrap(command(), project).args[3..5],
+            ["--share-net", "cargo"]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(SandboxConfig::default().validate().is_ok());
+
+        let empty = SandboxConfig {
+            enabled: true,
+            wrapper: Vec::new(),
+            ..SandboxConfig::default()
+        };
+        assert!(empty.validate().is_err());
+        assert!(SandboxConfig {
+            enabled: false,
+            ..empty
+        }
+        .validate()
+        .is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::dev::ServerHandle;
+use futures_util::future::join_all;
+use tokio::time::{self, Instant};
+use tracing::info;
+
+use crate::metrics::ConnectionStats;
+
+/// How often the time left of the grace period is logged
+const COUNTDOWN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// SIGTERM, which never arrives on platforms without it
+pub struct Terminate(#[cfg(unix)] tokio::signal::unix::Signal);
+
+impl Terminate {
+    /// Starts listening, so a SIGTERM sent from now on isn't missed
+    pub fn listen() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        let terminate = Self(tokio::signal::unix::signal(
+            tokio::signal::unix::SignalKind::terminate(),
+        )?);
+        #[cfg(not(unix))]
+        let terminate = Self();
+        Ok(terminate)
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.0.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Waits out the grace period, logging the time left every [`COUNTDOWN_INTERVAL`]
+async fn count_down(grace: Duration, connections: &ConnectionStats) {
+    let deadline = Instant::now() + grace;
+    let mut ticks = time::interval_at(Instant::now() + COUNTDOWN_INTERVAL, COUNTDOWN_INTERVAL);
+    loop {
+        tokio::select! {
+            biased;
+            _ = time::sleep_until(deadline) => return,
+            _ = ticks.tick() => info!(
+                "Stopping in {:.0}s, {} connection(s) handling requests",
+                deadline.saturating_duration_since(Instant::now()).as_secs_f64(),
+                connections.active()
+            ),
+        }
+    }
+}
+
+/// Stops the servers, whose own signal handling is disabled. After a SIGTERM they keep
+/// accepting requests for `grace`, so a rolling update can move traffic to the new instance
+/// first; Ctrl-C, or another SIGTERM, stops them right away. Either way, responses in flight
+/// are finished before the servers exit.
+pub fn spawn_stop_on_signal(
+    handles: Vec<ServerHandle>,
+    mut terminate: Terminate,
+    grace: Duration,
+    connections: Arc<ConnectionStats>,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminate.recv() => {
+                info!(
+                    "Received SIGTERM, still accepting requests for {}s",
+                    grace.as_secs()
+                );
+                tokio::select! {
+                    _ = count_down(grace, &connections) => {}
+                    _ = terminate.recv() => info!("Received SIGTERM again"),
+                    _ = tokio::signal::ctrl_c() => info!("Received Ctrl-C"),
+                }
+            }
+            _ = tokio::signal::ctrl_c() => info!("Received Ctrl-C"),
+        }
+
+        info!("Stopping, finishing the requests in flight");
+        join_all(handles.iter().map(|handle| handle.stop(true))).await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_count_down() {
+        let connections = ConnectionStats::default();
+        let started = Instant::now();
+        count_down(Duration::from_millis(50), &connections).await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(50));
+        // the first log line is only due after 5s
+        assert!(elapsed < COUNTDOWN_INTERVAL);
+
+        count_down(Duration::ZERO, &connections).await;
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use actix_files::NamedFile;
+use actix_web::{delete, http::header, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    api, artifact,
+    config::Project,
+    error::{AppError, AppResult},
+    filter::IgnoreRules,
+    history::project_logs_dir,
+    serve_docs_file,
+    utils::normalize_relative,
+    AppState,
+};
+
+/// Directory of a project's logs holding its snapshots, one subdirectory each
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Longest snapshot name accepted
+const MAX_NAME_LEN: usize = 64;
+
+/// Tells apart the staging directories of snapshots being created at the same time
+static STAGING: AtomicU64 = AtomicU64::new(0);
+
+/// Where a project's snapshots are kept, out of reach of builds and `clean`
+pub fn dir(libs_path: &Path, url_path: &str) -> PathBuf {
+    project_logs_dir(libs_path, url_path).join(SNAPSHOTS_DIR)
+}
+
+/// Snapshot names are slug-like, with dots allowed for version numbers: ASCII letters, digits,
+/// `-`, `_` and `.`, starting with a letter or digit
+pub fn validate_name(name: &str) -> AppResult<()> {
+    let valid = name.len() <= MAX_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "invalid snapshot name '{}': use up to {} letters, digits, '-', '_' and '.', starting with a letter or digit",
+            name, MAX_NAME_LEN
+        )))
+    }
+}
+
+/// Names of a project's snapshots, sorted
+pub async fn list(libs_path: &Path, url_path: &str) -> Vec<String> {
+    let Ok(mut entries) = fs::read_dir(dir(libs_path, url_path)).await else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(name) = entry.file_name().into_string()
+            && validate_name(&name).is_ok()
+            && entry.file_type().await.is_ok_and(|t| t.is_dir())
+        {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names
+}
+
+/// Copies the served files of the project's live docs into the snapshot `name`, which must not
+/// exist yet. Files are copied rather than hard-linked, so builds rewriting them in place
+/// can't change the snapshot. Returns the number of files copied.
+async fn create(libs_path: &Path, project: &Project, name: &str) -> AppResult<usize> {
+    let target = dir(libs_path, &project.url_path).join(name);
+    let staging = target.with_file_name(format!(
+        ".{}.tmp-{}-{}",
+        name,
+        std::process::id(),
+        STAGING.fetch_add(1, Ordering::Relaxed)
+    ));
+    let files = artifact::list_files(
+        &project.docs_path,
+        &project.serve_filter,
+        &IgnoreRules::default(),
+    )
+    .await?;
+
+    let copied = async {
+        for file in &files {
+            let destination = staging.join(file);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::copy(project.docs_path.join(file), destination).await?;
+        }
+        fs::create_dir_all(&staging).await?;
+        // renaming onto an existing snapshot fails, so concurrent requests can't both win
+        fs::rename(&staging, &target).await
+    }
+    .await;
+
+    if let Err(e) = copied {
+        if let Err(e) = fs::remove_dir_all(&staging).await
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+        return Err(e.into());
+    }
+    Ok(files.len())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotRequest {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotCreated {
+    name: String,
+    files: usize,
+    url: String,
+}
+
+/// Pins the project's current docs as a named snapshot, kept until deleted through the API
+#[post("/api/projects/{project:[^/]+(?:/[^/]+)?}/snapshots")]
+pub async fn create_snapshot(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+    body: web::Json<SnapshotRequest>,
+) -> AppResult<HttpResponse> {
+    let project = api::local_project(&state, &slug)?;
+    let name = &body.name;
+    validate_name(name)?;
+
+    let snapshots = dir(&state.base_path, &project.url_path);
+    if fs::try_exists(snapshots.join(name)).await? {
+        return Ok(HttpResponse::Conflict().body(format!("snapshot '{}' already exists", name)));
+    }
+    fs::create_dir_all(&snapshots).await?;
+
+    let Some(_permit) = state.heavy_ops.acquire().await else {
+        return Ok(HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, "30"))
+            .body("too many archives being processed, try again later"));
+    };
+    let files = match create(&state.base_path, project, name).await {
+        Ok(files) => files,
+        Err(_) if fs::try_exists(snapshots.join(name)).await.unwrap_or(false) => {
+            return Ok(HttpResponse::Conflict().body(format!("snapshot '{}' already exists", name)));
+        }
+        Err(e) => return Err(e),
+    };
+    info!(
+        "Created snapshot {} of {}: {} files",
+        name, project.config.path, files
+    );
+
+    Ok(HttpResponse::Created().json(SnapshotCreated {
+        name: name.clone(),
+        files,
+        url: format!("/{}/snapshots/{}/", project.url_path, name),
+    }))
+}
+
+/// Deletes one of the project's snapshots
+#[delete("/api/projects/{project:[^/]+(?:/[^/]+)?}/snapshots/{name}")]
+pub async fn delete_snapshot(
+    state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, String)>,
+) -> AppResult<HttpResponse> {
+    let (slug, name) = path.into_inner();
+    let project = api::local_project(&state, &slug)?;
+    validate_name(&name)?;
+
+    let snapshot = dir(&state.base_path, &project.url_path).join(&name);
+    match fs::remove_dir_all(&snapshot).await {
+        Ok(()) => {
+            info!("Deleted snapshot {} of {}", name, project.config.path);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(AppError::NotFound(format!(
+            "snapshot '{}' of project '{}'",
+            name, slug
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Serves a file of a snapshot at `/{slug}/snapshots/{name}/{tail}`, like the live docs
+pub async fn serve(
+    libs_path: &Path,
+    project: &Project,
+    name: &str,
+    tail: &str,
+) -> AppResult<NamedFile> {
+    let not_found = || AppError::NotFound(format!("snapshot '{}'", name));
+    if validate_name(name).is_err() {
+        return Err(not_found());
+    }
+
+    let file = if tail.is_empty() || tail.ends_with('/') {
+        format!("{}{}", tail, project.index_file_name())
+    } else {
+        tail.to_string()
+    };
+    // `./` and `//` would slip past anchored filter patterns
+    let file = normalize_relative(&file);
+    if !project.serve_filter.allows(&file) {
+        return Err(not_found());
+    }
+    serve_docs_file(
+        &dir(libs_path, &project.url_path).join(name),
+        &file.to_string_lossy(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["v3.2.0", "release-2024_01", "1.0"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in [
+            "",
+            ".hidden",
+            "../up",
+            "a/b",
+            "-flag",
+            "café",
+            &"a".repeat(65),
+        ] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list() {
+        let libs = TempDir::new().unwrap();
+        let docs = libs.path().join("lib/docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(docs.join("index.html"), "v3").unwrap();
+        std::fs::write(docs.join("guide/intro.html"), "intro").unwrap();
+        std::fs::write(docs.join("deploy.key"), "secret").unwrap();
+
+        let projects = crate::config::test_projects(
+            libs.path(),
+            "[[projects]]\npath = \"lib\"\nbuild_system = \"custom\"\n",
+        )
+        .await;
+        let project = &projects["lib"];
+
+        assert!(list(libs.path(), "lib").await.is_empty());
+        std::fs::create_dir_all(dir(libs.path(), "lib")).unwrap();
+        assert_eq!(create(libs.path(), project, "v3.2.0").await.unwrap(), 2);
+        assert!(create(libs.path(), project, "v3.2.0").await.is_err());
+
+        // the live docs change, the snapshot doesn't
+        std::fs::write(docs.join("index.html"), "v4").unwrap();
+        let snapshot = dir(libs.path(), "lib").join("v3.2.0");
+        assert_eq!(
+            std::fs::read_to_string(snapshot.join("index.html")).unwrap(),
+            "v3"
+        );
+        assert!(snapshot.join("guide/intro.html").is_file());
+        // files that aren't served aren't kept either
+        assert!(!snapshot.join("deploy.key").exists());
+        assert_eq!(list(libs.path(), "lib").await, ["v3.2.0"]);
+    }
+}
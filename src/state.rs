@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use crate::{
+    error::{AppError, AppResult},
+    git::{CommitInfo, ReleaseStatus},
+    links::BrokenLink,
+};
+
+const STATE_FILE: &str = ".lichen-state.toml";
+
+/// Build information persisted across restarts, keyed by project URL path
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildState {
+    #[serde(default)]
+    pub projects: HashMap<String, ProjectState>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProjectState {
+    /// Unix timestamp (seconds) of the last successful build
+    pub last_built: Option<u64>,
+    /// Compiler errors reported by the last build, empty if it succeeded
+    #[serde(default)]
+    pub last_errors: Vec<String>,
+    /// Commit the current docs were built from, if the project is tracked in git
+    pub commit: Option<CommitInfo>,
+    /// SHA-256 of the `Cargo.lock` the current docs were built with, if there was one. Builds
+    /// with the same hash resolved the same dependencies.
+    #[serde(default)]
+    pub cargo_lock_sha256: Option<String>,
+    /// How that commit compares with the newest release tag, if the repository has one
+    #[serde(default)]
+    pub release: Option<ReleaseStatus>,
+    /// Problems with the output of the last successful build, e.g. a missing index file
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Where the last successful build left its docs, relative to the project directory, for
+    /// projects with `detect_docs_path`
+    #[serde(default)]
+    pub docs_path: Option<PathBuf>,
+    /// Links to missing files found in the last successful build, for projects with
+    /// `check_links`
+    #[serde(default)]
+    pub broken_links: Vec<BrokenLink>,
+    /// Unix timestamp (seconds) of the last request for the project's docs
+    #[serde(default)]
+    pub last_accessed: Option<u64>,
+}
+
+impl BuildState {
+    fn file_path(libs_path: &Path) -> PathBuf {
+        libs_path.join(STATE_FILE)
+    }
+
+    /// Loads the state file, falling back to an empty state if it's missing or unreadable
+    pub async fn load(libs_path: &Path) -> Self {
+        let path = Self::file_path(libs_path);
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            warn!("Ignoring invalid state file {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub async fn save(&self, libs_path: &Path) -> AppResult<()> {
+        let contents = toml::to_string(self).map_err(|e| AppError::Config(e.to_string()))?;
+        fs::write(Self::file_path(libs_path), contents).await?;
+        Ok(())
+    }
+
+    pub fn project(&self, url_path: &str) -> Option<&ProjectState> {
+        self.projects.get(url_path)
+    }
+
+    pub fn record_build(
+        &mut self,
+        url_path: &str,
+        commit: Option<CommitInfo>,
+        cargo_lock_sha256: Option<String>,
+        warnings: Vec<String>,
+    ) {
+        let project = self.projects.entry(url_path.to_string()).or_default();
+        project.last_built = Some(now_secs());
+        project.last_errors.clear();
+        project.commit = commit;
+        project.cargo_lock_sha256 = cargo_lock_sha256;
+        project.warnings = warnings;
+    }
+
+    /// Remembers the docs directory found after a build with `detect_docs_path`
+    pub fn record_docs_path(&mut self, url_path: &str, docs_path: PathBuf) {
+        self.projects
+            .entry(url_path.to_string())
+            .or_default()
+            .docs_path = Some(docs_path);
+    }
+
+    /// Remembers how the commit of a successful build compares with the newest release tag
+    pub fn record_release(&mut self, url_path: &str, release: Option<ReleaseStatus>) {
+        self.projects
+            .entry(url_path.to_string())
+            .or_default()
+            .release = release;
+    }
+
+    /// Remembers the broken links found after a build with `check_links`
+    pub fn record_links(&mut self, url_path: &str, links: Vec<BrokenLink>) {
+        self.projects
+            .entry(url_path.to_string())
+            .or_default()
+            .broken_links = links;
+    }
+
+    /// Remembers when the project's docs were last requested
+    pub fn record_access(&mut self, url_path: &str, timestamp: u64) {
+        self.projects
+            .entry(url_path.to_string())
+            .or_default()
+            .last_accessed = Some(timestamp);
+    }
+
+    /// Records a failed build, keeping the docs (and commit) of the last successful one
+    pub fn record_failure(&mut self, url_path: &str, errors: Vec<String>) {
+        self.projects
+            .entry(url_path.to_string())
+            .or_default()
+            .last_errors = errors;
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM UTC`
+pub fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60
+    )
+}
+
+/// Converts days since the Unix epoch into a (year, month, day) date, see
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parses a `YYYY-MM-DD` date into the Unix timestamp of its midnight (UTC)
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86_400)
+}
+
+/// Inverse of [`civil_from_days`], see
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -0,0 +1,147 @@
+use std::{
+    any::Any,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{
+    dev::{Extensions, ServiceRequest},
+    middleware::Logger,
+    rt::net::TcpStream,
+};
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use serde::Deserialize;
+
+use crate::error::{AppError, AppResult};
+
+/// Serving over HTTPS, optionally only to clients with a certificate signed by `client_ca_cert`
+#[derive(Debug, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain of the server
+    pub cert: PathBuf,
+    /// PEM private key of `cert`
+    pub key: PathBuf,
+    /// PEM certificates of the CAs client certificates must be signed by, none required if unset
+    #[serde(default)]
+    pub client_ca_cert: Option<PathBuf>,
+}
+
+/// Common name of the certificate a client authenticated with, stored per connection
+#[derive(Debug, Clone)]
+pub struct ClientIdentity(pub String);
+
+/// actix's default access log format, plus the client certificate's common name
+const CLIENT_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T client=%{client}xi"#;
+
+fn read_certs(path: &Path) -> AppResult<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| AppError::Config(format!("invalid certificate {}: {}", path.display(), e)))?;
+    if certs.is_empty() {
+        return Err(AppError::Config(format!(
+            "no certificate in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Loads the certificates and keys of `config` into a rustls server configuration
+pub fn server_config(config: &TlsConfig) -> AppResult<ServerConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let certs = read_certs(&config.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key).map_err(|e| {
+        AppError::Config(format!(
+            "invalid private key {}: {}",
+            config.key.display(),
+            e
+        ))
+    })?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| AppError::Config(format!("unsupported TLS configuration: {}", e)))?;
+    let builder = match &config.client_ca_cert {
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca)? {
+                roots.add(cert).map_err(|e| {
+                    AppError::Config(format!("invalid CA certificate {}: {}", ca.display(), e))
+                })?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| AppError::Config(format!("invalid client_ca_cert: {}", e)))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    builder
+        .with_single_cert(certs, key)
+        .map_err(|e| AppError::Config(format!("invalid TLS certificate or key: {}", e)))
+}
+
+/// `CN` of the subject of a DER certificate
+fn common_name(der: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(str::to_string)
+}
+
+/// Remembers the common name of a connection's client certificate, see [`ClientIdentity`]
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let (_, session) = stream.get_ref();
+    if let Some(name) = session
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .and_then(|cert| common_name(cert.as_ref()))
+    {
+        data.insert(ClientIdentity(name));
+    }
+}
+
+/// Access log naming each request's client certificate, `-` for clients without one
+pub fn client_logger() -> Logger {
+    Logger::new(CLIENT_LOG_FORMAT).custom_request_replace("client", |req: &ServiceRequest| {
+        req.conn_data::<ClientIdentity>()
+            .map_or_else(|| "-".to_string(), |client| client.0.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_server_config_rejects_bad_files() {
+        let dir = TempDir::new().unwrap();
+        let garbage = dir.path().join("garbage.pem");
+        std::fs::write(&garbage, "not a certificate").unwrap();
+
+        for (cert, key) in [
+            (dir.path().join("missing.pem"), garbage.clone()),
+            (garbage.clone(), garbage.clone()),
+        ] {
+            let config = TlsConfig {
+                cert,
+                key,
+                client_ca_cert: None,
+            };
+            assert!(matches!(server_config(&config), Err(AppError::Config(_))));
+        }
+        assert_eq!(common_name(b"not DER"), None);
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use actix_multipart::Multipart;
+use actix_web::{http::header, put, web, HttpResponse};
+use async_compression::tokio::bufread::GzipDecoder;
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use tokio::{fs, io::AsyncRead};
+use tokio_tar::Archive;
+use tokio_util::io::StreamReader;
+use tracing::{info, warn};
+
+use crate::{
+    api, copy,
+    error::{AppError, AppResult},
+    links, manifest,
+    pipeline::output_warning,
+    precompress, record_docs_size, AppState,
+};
+
+/// Multipart field holding the `tar.gz` archive
+const DOCS_FIELD: &str = "docs";
+
+/// Tells apart the staging directories of uploads running at the same time
+static UPLOADS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize)]
+pub struct UploadReport {
+    /// Regular files extracted from the archive
+    pub files: usize,
+    /// Problems with the uploaded docs, as for a build
+    pub warnings: Vec<String>,
+}
+
+/// Replaces a project's docs with a pre-built `tar.gz`, sent as the `docs` field of a
+/// `multipart/form-data` body. The archive is extracted next to `docs_path` and swapped in
+/// once complete, so the old docs are served until then.
+#[put("/api/projects/{project:[^/]+(?:/[^/]+)?}/docs")]
+pub async fn upload_docs(
+    state: web::Data<Arc<AppState>>,
+    slug: web::Path<String>,
+    mut payload: Multipart,
+) -> AppResult<HttpResponse> {
+    let project = api::local_project(&state, &slug)?;
+
+    let Some(_permit) = state.heavy_ops.acquire().await else {
+        return Ok(HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, "30"))
+            .body("too many archives being processed, try again later"));
+    };
+
+    let field = loop {
+        match payload
+            .try_next()
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?
+        {
+            Some(field) if field.name() == Some(DOCS_FIELD) => break field,
+            Some(_) => continue,
+            None => {
+                return Err(AppError::BadRequest(format!(
+                    "no '{}' field in the upload",
+                    DOCS_FIELD
+                )));
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let max_bytes = state.max_upload_bytes;
+    let received = Arc::new(AtomicU64::new(0));
+    let chunks = field.map({
+        let received = received.clone();
+        move |chunk| {
+            let chunk = chunk.map_err(|e| io::Error::other(e.to_string()))?;
+            let total =
+                received.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+            if total > max_bytes {
+                return Err(io::Error::other("upload too large"));
+            }
+            Ok(chunk)
+        }
+    });
+
+    let docs_path = &project.docs_path;
+    if let Some(parent) = docs_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let staging = sibling(docs_path, "upload");
+    fs::create_dir(&staging).await?;
+
+    let max_extracted = state.max_extracted_bytes;
+    let reader = StreamReader::new(Box::pin(chunks));
+    let files = match extract(reader, &staging, max_extracted).await {
+        Ok(files) => files,
+        Err(e) => {
+            remove_dir(&staging).await;
+            if received.load(Ordering::Relaxed) > max_bytes {
+                return Ok(HttpResponse::PayloadTooLarge()
+                    .body(format!("docs archives are limited to {} bytes", max_bytes)));
+            }
+            if e.kind() == io::ErrorKind::FileTooLarge {
+                return Ok(HttpResponse::PayloadTooLarge().body(format!(
+                    "docs archives are limited to {} bytes once extracted",
+                    max_extracted
+                )));
+            }
+            return Err(AppError::BadRequest(format!("invalid docs archive: {}", e)));
+        }
+    };
+
+    let served = project
+        .config
+        .entry_file
+        .as_deref()
+        .unwrap_or(&project.config.index_file);
+    if project.config.fail_on_empty_docs
+        && let Some(warning) = output_warning(&staging, served).await
+    {
+        remove_dir(&staging).await;
+        return Err(AppError::BadRequest(format!(
+            "uploaded docs rejected, {}",
+            warning
+        )));
+    }
+    if let Err(e) = swap_into_place(&staging, docs_path).await {
+        remove_dir(&staging).await;
+        return Err(e.into());
+    }
+    state.error_pages.invalidate(&project.url_path);
+    let warnings = output_warning(docs_path, served)
+        .await
+        .into_iter()
+        .collect::<Vec<_>>();
+    info!("Uploaded docs of {}: {} files", project.config.path, files);
+
+    record_docs_size(&state.base_path, project, None, None, started.elapsed()).await;
+    if let Err(e) = manifest::record(&state.base_path, project, None).await {
+        warn!("Failed to checksum docs of {}: {}", project.config.path, e);
+    }
+    if project.config.precompress {
+        // archives keep their files' modification times, which can't be trusted to be newer
+        precompress::discard(&state.base_path, &project.url_path).await;
+        precompress::after_build(&state.base_path, project).await;
+    }
+    let broken_links = if project.config.check_links {
+        links::after_build(project).await
+    } else {
+        None
+    };
+    copy::after_build(project).await;
+    let mut build_state = state.build_state.write().await;
+    build_state.record_build(&project.url_path, None, None, warnings.clone());
+    build_state.record_release(&project.url_path, None);
+    if let Some(links) = broken_links {
+        build_state.record_links(&project.url_path, links);
+    }
+    build_state.save(&state.base_path).await?;
+
+    Ok(HttpResponse::Ok().json(UploadReport { files, warnings }))
+}
+
+/// Unpacks a `tar.gz` into `dir`, refusing links, paths leading out of it and archives whose
+/// files add up to more than `max_bytes`, which a small gzip stream can easily expand to.
+/// Returns the number of regular files extracted.
+async fn extract(reader: impl AsyncRead + Unpin, dir: &Path, max_bytes: u64) -> io::Result<usize> {
+    let mut archive = Archive::new(GzipDecoder::new(tokio::io::BufReader::new(reader)));
+    let mut entries = archive.entries()?;
+    let mut files = 0;
+    let mut extracted = 0u64;
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let kind = entry.header().entry_type();
+        if kind.is_symlink() || kind.is_hard_link() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is a link", path.display()),
+            ));
+        }
+        // entries are read up to the size in their header, so that's all they can write
+        extracted = extracted.saturating_add(entry.header().size()?);
+        if extracted > max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("{} bytes extracted by {}", extracted, path.display()),
+            ));
+        }
+        if !entry.unpack_in(dir).await? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is outside the archive", path.display()),
+            ));
+        }
+        if kind.is_file() {
+            files += 1;
+        }
+    }
+    Ok(files)
+}
+
+/// Hidden directory next to `docs_path`, unique to this upload or copy
+pub fn sibling(docs_path: &Path, purpose: &str) -> PathBuf {
+    let name = docs_path.file_name().unwrap_or_default().to_string_lossy();
+    docs_path.with_file_name(format!(
+        ".{}.{}-{}-{}",
+        name,
+        purpose,
+        std::process::id(),
+        UPLOADS.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Moves `staging` to `docs_path`, putting the previous docs back if that fails
+pub async fn swap_into_place(staging: &Path, docs_path: &Path) -> io::Result<()> {
+    let previous = sibling(docs_path, "previous");
+    let replaced = match fs::rename(docs_path, &previous).await {
+        Ok(()) => true,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+
+    if let Err(e) = fs::rename(staging, docs_path).await {
+        if replaced && let Err(e) = fs::rename(&previous, docs_path).await {
+            warn!("Failed to restore {}: {}", docs_path.display(), e);
+        }
+        return Err(e);
+    }
+    if replaced {
+        remove_dir(&previous).await;
+    }
+    Ok(())
+}
+
+async fn remove_dir(path: &Path) {
+    if let Err(e) = fs::remove_dir_all(path).await {
+        warn!("Failed to remove {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_compression::tokio::write::GzipEncoder;
+    use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
+    use tokio_tar::{Builder, EntryType, Header};
+
+    use super::*;
+
+    fn header(path: &str, kind: EntryType, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_path(path).unwrap();
+        header.set_entry_type(kind);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_cksum();
+        header
+    }
+
+    async fn tar_gz(entries: &[(Header, &str)]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (header, contents) in entries {
+            builder.append(header, contents.as_bytes()).await.unwrap();
+        }
+        let tar = builder.into_inner().await.unwrap();
+
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(&tar).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_extract_and_swap() {
+        let dir = TempDir::new().unwrap();
+        let docs = dir.path().join("doc");
+        std::fs::create_dir(&docs).unwrap();
+        std::fs::write(docs.join("stale.html"), "old").unwrap();
+
+        let archive = tar_gz(&[
+            (header("index.html", EntryType::Regular, 3), "new"),
+            (header("lib/page.html", EntryType::Regular, 4), "page"),
+        ])
+        .await;
+        let staging = sibling(&docs, "upload");
+        std::fs::create_dir(&staging).unwrap();
+        assert_eq!(extract(archive.as_slice(), &staging, 7).await.unwrap(), 2);
+        swap_into_place(&staging, &docs).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(docs.join("index.html")).unwrap(),
+            "new"
+        );
+        assert!(docs.join("lib/page.html").exists());
+        assert!(!docs.join("stale.html").exists());
+        // neither the staging directory nor the previous docs are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_extract_rejects_links() {
+        let dir = TempDir::new().unwrap();
+        let mut link = header("index.html", EntryType::Symlink, 0);
+        link.set_link_name("/etc/passwd").unwrap();
+        link.set_cksum();
+        let archive = tar_gz(&[(link, "")]).await;
+
+        assert!(extract(archive.as_slice(), dir.path(), u64::MAX)
+            .await
+            .is_err());
+        assert!(!dir.path().join("index.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_extract_limits_extracted_size() {
+        let dir = TempDir::new().unwrap();
+        let zeros = "0".repeat(4096);
+        let archive = tar_gz(&[
+            (header("a.html", EntryType::Regular, 4096), &zeros),
+            (header("b.html", EntryType::Regular, 4096), &zeros),
+        ])
+        .await;
+        assert!(archive.len() < 4096);
+
+        let e = extract(archive.as_slice(), dir.path(), 6000)
+            .await
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::FileTooLarge);
+        assert!(!dir.path().join("b.html").exists());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+pub fn sanitize_path(path: &str) -> String {
+    let mut sanitized = String::with_capacity(path.len());
+    let mut last_was_dash = false;
+
+    for c in path.chars() {
+        if c.is_ascii_alphanumeric() {
+            sanitized.push(c.to_ascii_lowercase());
+            last_was_dash = false;
+        } else if !last_was_dash {
+            sanitized.push('-');
+            last_was_dash = true;
+        }
+    }
+
+    // trim trailing dash if exists
+    if sanitized.ends_with('-') {
+        sanitized.pop();
+    }
+
+    sanitized
+}
+
+/// Location an alias request for `/{alias}/{tail}?{query}` is redirected to
+pub fn alias_location(slug: &str, tail: &str, query: &str) -> String {
+    with_query(&format!("/{}/{}", slug, tail), query)
+}
+
+/// `location` with the raw (still percent-encoded) query string of a request appended
+pub fn with_query(location: &str, query: &str) -> String {
+    if query.is_empty() {
+        location.to_string()
+    } else {
+        format!("{}?{}", location, query)
+    }
+}
+
+/// Returns true if `path` is relative and never escapes the directory it's joined onto
+pub fn is_contained_path(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// The `Normal` components of a relative path, so `./a//b` becomes `a/b`. Filters match paths
+/// as written, so requested paths go through this before being checked against them.
+pub fn normalize_relative(path: &str) -> PathBuf {
+    Path::new(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Escapes text coming from outside the config (e.g. commit messages) for use in HTML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A filled circle in `color` (any CSS color), served as the favicon
+pub fn favicon_svg(color: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16"><circle cx="8" cy="8" r="7" fill="{}"/></svg>"#,
+        escape_html(color)
+    )
+}
+
+#[cfg(unix)]
+fn os_bytes(name: &OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(name.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn os_bytes(name: &OsStr) -> Cow<'_, [u8]> {
+    match name.to_string_lossy() {
+        Cow::Borrowed(name) => Cow::Borrowed(name.as_bytes()),
+        Cow::Owned(name) => Cow::Owned(name.into_bytes()),
+    }
+}
+
+/// `path` with `/` separators, as in URLs. Bytes of file names that aren't UTF-8, like a
+/// Latin-1 name on Unix, are percent-encoded rather than replaced, so such files keep names
+/// of their own. So is `%` itself, or `caf%E9.html` would share its name with the Latin-1
+/// `caf\xE9.html`.
+pub fn url_path(path: &Path) -> String {
+    let mut url = String::with_capacity(path.as_os_str().len());
+    for (i, component) in path.components().enumerate() {
+        if i > 0 {
+            url.push('/');
+        }
+        for chunk in os_bytes(component.as_os_str()).utf8_chunks() {
+            url.push_str(&chunk.valid().replace('%', "%25"));
+            for byte in chunk.invalid() {
+                let _ = write!(url, "%{:02X}", byte);
+            }
+        }
+    }
+    url
+}
+
+/// Path made of raw bytes, e.g. a decoded URL. Outside Unix, bytes that aren't UTF-8 are
+/// replaced.
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Formats a byte count for humans, e.g. `1.5 MiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_path() {
+        assert_eq!(sanitize_path("my-project"), "my-project");
+        assert_eq!(sanitize_path("MyProject"), "myproject");
+        assert_eq!(sanitize_path("my_awesome lib"), "my-awesome-lib");
+        assert_eq!(sanitize_path("libs/nested/project"), "libs-nested-project");
+    }
+
+    #[test]
+    fn test_sanitize_path_collapses_and_trims_separators() {
+        assert_eq!(sanitize_path("a__b--c"), "a-b-c");
+        assert_eq!(sanitize_path("project/"), "project");
+        assert_eq!(sanitize_path("project!!!"), "project");
+        // leading separators are kept, only the trailing one is trimmed
+        assert_eq!(sanitize_path("_private"), "-private");
+    }
+
+    #[test]
+    fn test_sanitize_path_edge_cases() {
+        assert_eq!(sanitize_path(""), "");
+        assert_eq!(sanitize_path("---"), "");
+        assert_eq!(sanitize_path("v2.0.1"), "v2-0-1");
+        assert_eq!(sanitize_path("caf\u{e9}"), "caf");
+        assert_eq!(sanitize_path("\u{e9}t\u{e9}"), "-t");
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(
+            url_path(Path::new("a/b/caf\u{e9}.html")),
+            "a/b/caf\u{e9}.html"
+        );
+        assert_eq!(url_path(Path::new("")), "");
+        assert_eq!(url_path(Path::new("100%.html")), "100%25.html");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_url_path_of_non_utf8_name() {
+        let latin1 = path_from_bytes(b"api/caf\xe9.html".to_vec());
+        assert_eq!(url_path(&latin1), "api/caf%E9.html");
+        // a valid name that merely looks encoded stays apart
+        assert_eq!(url_path(Path::new("api/caf%E9.html")), "api/caf%25E9.html");
+    }
+
+    #[test]
+    fn test_with_query() {
+        assert_eq!(with_query("/lib/", ""), "/lib/");
+        assert_eq!(
+            with_query("/lib/", "search=Foo%20Bar&x=1"),
+            "/lib/?search=Foo%20Bar&x=1"
+        );
+    }
+
+    #[test]
+    fn test_alias_location() {
+        assert_eq!(alias_location("new", "", ""), "/new/");
+        assert_eq!(
+            alias_location("new", "struct.Foo.html", ""),
+            "/new/struct.Foo.html"
+        );
+        assert_eq!(
+            alias_location("new", "a/b/index.html", "search=foo&x=1"),
+            "/new/a/b/index.html?search=foo&x=1"
+        );
+    }
+
+    #[test]
+    fn test_is_contained_path() {
+        assert!(is_contained_path("index.html"));
+        assert!(is_contained_path("html/index.html"));
+        assert!(is_contained_path("./index.html"));
+        assert!(!is_contained_path(""));
+        assert!(!is_contained_path("../index.html"));
+        assert!(!is_contained_path("html/../../index.html"));
+        assert!(!is_contained_path("/etc/passwd"));
+    }
+
+    #[test]
+    fn test_normalize_relative() {
+        assert_eq!(normalize_relative("./a//b/"), Path::new("a/b"));
+        assert_eq!(normalize_relative("a/./b.html"), Path::new("a/b.html"));
+        assert_eq!(normalize_relative("."), Path::new(""));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(
+            format_size(3 * 1024 * 1024 * 1024 * 1024 * 1024),
+            "3072.0 TiB"
+        );
+    }
+
+    #[test]
+    fn test_favicon_svg() {
+        assert!(favicon_svg("#0366d6").contains(r##"fill="#0366d6""##));
+        assert!(favicon_svg("rgb(1, 2, 3)").contains(r#"fill="rgb(1, 2, 3)""#));
+        // a color can't break out of the attribute
+        assert!(
+            favicon_svg(r#"red"/><script/>"#).contains(r#"fill="red&quot;/&gt;&lt;script/&gt;""#)
+        );
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("plain"), "plain");
+    }
+}
@@ -0,0 +1,232 @@
+use std::{cmp::Ordering, path::Path};
+
+use actix_web::HttpResponse;
+use tokio::fs;
+
+use crate::{
+    error::{AppError, AppResult},
+    utils::escape_html,
+};
+
+/// A directory name like `1.2`, `v2.0.1` or `3.0.0-rc1`
+#[derive(Debug)]
+struct Version {
+    numbers: Vec<u64>,
+    pre_release: Option<String>,
+}
+
+impl Version {
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.strip_prefix('v').unwrap_or(name);
+        let (release, pre_release) = match name.split_once(['-', '+']) {
+            Some((release, suffix)) => {
+                let valid = !suffix.is_empty()
+                    && suffix
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+                if !valid {
+                    return None;
+                }
+                (release, Some(suffix.to_string()))
+            }
+            None => (name, None),
+        };
+
+        let numbers = release
+            .split('.')
+            .map(|part| {
+                (!part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+                    .then(|| part.parse().ok())
+                    .flatten()
+            })
+            .collect::<Option<Vec<u64>>>()?;
+        (numbers.len() <= 4).then_some(Self {
+            numbers,
+            pre_release,
+        })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        let number = |v: &Self, i: usize| v.numbers.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| number(self, i).cmp(&number(other, i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+            // releases come after their pre-releases
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Subdirectories of `docs_path` named like versions, newest first. Read on every call, so
+/// versions added on disk show up without a restart.
+pub async fn list(docs_path: &Path) -> AppResult<Vec<String>> {
+    let mut entries = fs::read_dir(docs_path).await?;
+    let mut versions = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if entry.file_type().await?.is_dir()
+            && let Some(version) = Version::parse(&name)
+        {
+            versions.push((version, name));
+        }
+    }
+
+    versions.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    Ok(versions.into_iter().map(|(_, name)| name).collect())
+}
+
+/// The newest release among `versions` (as returned by [`list`]), or the newest pre-release
+/// if there are only pre-releases
+fn latest(versions: &[String]) -> Option<&String> {
+    versions
+        .iter()
+        .find(|v| Version::parse(v).is_some_and(|v| v.pre_release.is_none()))
+        .or(versions.first())
+}
+
+/// Version picker served at `/{slug}/` for projects with `versions_from_dirs`
+pub async fn index_page(name: &str, url_path: &str, docs_path: &Path) -> AppResult<HttpResponse> {
+    let versions = list(docs_path)
+        .await
+        .map_err(|_| AppError::NotFound(format!("docs of project '{}'", url_path)))?;
+
+    let latest = latest(&versions);
+    let entries = versions
+        .iter()
+        .map(|version| {
+            format!(
+                "<li><a href=\"/{}/{}/\">{}</a>{}</li>",
+                url_path,
+                escape_html(version),
+                escape_html(version),
+                if Some(version) == latest {
+                    " (latest)"
+                } else {
+                    ""
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(format!(
+        r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>{name} versions</title>
+            <link rel="icon" type="image/svg+xml" href="/favicon.ico">
+            <style>
+                body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; }}
+                a {{ text-decoration: none; color: #0366d6; font-weight: 500; }}
+                li {{ margin: 0.3em 0; }}
+            </style>
+        </head>
+        <body>
+            <h1>{name}</h1>
+            <ul>{entries}</ul>
+        </body>
+        </html>
+    "#,
+        name = escape_html(name),
+        entries = entries
+    )))
+}
+
+/// Redirects `/{slug}/latest/{tail}` to the same page of the newest version
+pub async fn latest_redirect(
+    url_path: &str,
+    docs_path: &Path,
+    tail: &str,
+    query: &str,
+) -> AppResult<HttpResponse> {
+    let versions = list(docs_path).await.unwrap_or_default();
+    let latest = latest(&versions)
+        .ok_or_else(|| AppError::NotFound(format!("versions of project '{}'", url_path)))?;
+
+    let mut location = format!("/{}/{}/{}", url_path, latest, tail);
+    if !query.is_empty() {
+        location.push('?');
+        location.push_str(query);
+    }
+    Ok(HttpResponse::Found()
+        .append_header(("Location", location))
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert!(Version::parse("1.2").is_some());
+        assert!(Version::parse("v2.0.1").is_some());
+        assert!(Version::parse("3.0.0-rc1").is_some());
+        for name in [
+            "latest",
+            "1..2",
+            "1.2.3.4.5",
+            "v",
+            "1.x",
+            "1.0-",
+            "resources",
+        ] {
+            assert_eq!(Version::parse(name), None, "{}", name);
+        }
+
+        let v = |name| Version::parse(name).unwrap();
+        assert!(v("1.10") > v("1.9"));
+        assert_eq!(v("2.0"), v("v2.0.0"));
+        assert!(v("3.0.0") > v("3.0.0-rc1"));
+        assert!(v("3.0.0-rc2") > v("3.0.0-rc1"));
+    }
+
+    #[tokio::test]
+    async fn test_list_newest_first() {
+        let dir = TempDir::new().unwrap();
+        for name in ["1.9", "1.10", "2.0.0-beta", "v0.3", "resources"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        std::fs::write(dir.path().join("3.0"), "not a directory").unwrap();
+
+        let versions = list(dir.path()).await.unwrap();
+        assert_eq!(versions, ["2.0.0-beta", "1.10", "1.9", "v0.3"]);
+        assert_eq!(latest(&versions).unwrap(), "1.10");
+        assert_eq!(latest(&versions[..1]).unwrap(), "2.0.0-beta");
+
+        let response = latest_redirect("lib", dir.path(), "index.html", "q=1")
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get("Location").unwrap(),
+            "/lib/1.10/index.html?q=1"
+        );
+    }
+}
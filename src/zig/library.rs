@@ -1,35 +1,421 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use tokio::fs;
 
-/// Gets the main file based on the following possible places:
+use crate::{
+    error::AppResult,
+    utils::{escape_html, is_contained_path},
+    zig::zon::{parse_manifest, public_modules, root_source_hints, Manifest},
+};
+
+/// A Zig package, as far as building its docs is concerned
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZigProject {
+    /// `.name` from `build.zig.zon`, or the project directory's name
+    pub name: String,
+    /// Root source file of the library module
+    pub root_file: PathBuf,
+    /// `.version` from `build.zig.zon`
+    pub version: Option<String>,
+}
+
+/// Reads the package's `build.zig.zon` and `build.zig` to find its name and root file.
 ///
-/// 1. The root file is `root.zig`
-/// 2. The root file is the same as the project name
-/// 3. There is likely one file and it's named some other way
-pub async fn get_root_file(project_path: &Path) -> Option<PathBuf> {
+/// The first `root_source_file` in `build.zig` that exists wins; without one, the root file
+/// is guessed by [`get_root_file`].
+pub async fn find_project(project_path: &Path) -> Option<ZigProject> {
+    let manifest = match fs::read_to_string(project_path.join("build.zig.zon")).await {
+        Ok(source) => parse_manifest(&source),
+        Err(_) => Manifest::default(),
+    };
+
+    let root_file = match build_root_file(project_path).await {
+        Some(root_file) => root_file,
+        None => get_root_file(project_path, manifest.name.as_deref()).await?,
+    };
+
+    let name = manifest
+        .name
+        .or_else(|| {
+            project_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "root".to_string());
+
+    Some(ZigProject {
+        name,
+        root_file,
+        version: manifest.version,
+    })
+}
+
+/// A module exposed by a Zig package, documented on its own when there are several
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZigModule {
+    pub name: String,
+    pub root_file: PathBuf,
+}
+
+/// True if `name` can be used as a single directory name inside the docs directory
+fn is_dir_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Modules exposed through `b.addModule` in `build.zig` whose root file exists, skipping any
+/// whose name can't be a directory of its own
+pub async fn get_modules(project_path: &Path) -> Vec<ZigModule> {
+    let Ok(source) = fs::read_to_string(project_path.join("build.zig")).await else {
+        return Vec::new();
+    };
+
+    public_modules(&source)
+        .into_iter()
+        .filter(|(name, root)| is_dir_name(name) && is_contained_path(root))
+        .map(|(name, root)| ZigModule {
+            root_file: project_path.join(root),
+            name,
+        })
+        .filter(|module| module.root_file.is_file())
+        .collect()
+}
+
+/// Landing page linking the docs of each module in `docs/{module}/`
+fn module_index(package: &str, modules: &[ZigModule]) -> String {
+    let links = modules
+        .iter()
+        .map(|module| {
+            let name = escape_html(&module.name);
+            format!("<li><a href=\"{}/index.html\">{}</a></li>", name, name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>{package}</title>
+    <style>
+        body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; }}
+        a {{ text-decoration: none; color: #0366d6; }}
+    </style>
+</head>
+<body>
+    <h1>{package}</h1>
+    <ul>
+{links}
+    </ul>
+</body>
+</html>
+"#,
+        package = escape_html(package),
+        links = links
+    )
+}
+
+/// Writes `docs/index.html` linking each module's docs, for packages with several modules.
+/// Single-module packages keep their docs directly in `docs/`.
+pub async fn write_module_index(project_path: &Path) -> AppResult<()> {
+    let modules = get_modules(project_path).await;
+    if modules.len() < 2 {
+        return Ok(());
+    }
+
+    let package = find_project(project_path)
+        .await
+        .map(|project| project.name)
+        .unwrap_or_default();
+    fs::write(
+        project_path.join("docs").join("index.html"),
+        module_index(&package, &modules),
+    )
+    .await?;
+    Ok(())
+}
+
+/// The first `root_source_file` declared in `build.zig` that exists inside the project
+async fn build_root_file(project_path: &Path) -> Option<PathBuf> {
+    let source = fs::read_to_string(project_path.join("build.zig"))
+        .await
+        .ok()?;
+    root_source_hints(&source)
+        .into_iter()
+        .filter(|hint| is_contained_path(hint))
+        .map(|hint| project_path.join(hint))
+        .find(|path| path.is_file())
+}
+
+/// Entries of `dir` accepted by `keep`, sorted by path so the result doesn't depend on the
+/// order the filesystem lists them in
+async fn sorted_entries(dir: &Path, keep: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Ok(mut entries) = fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if keep(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths
+}
+
+fn is_zig_source(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().and_then(|e| e.to_str()) == Some("zig")
+        && path.file_name().and_then(|n| n.to_str()) != Some("build.zig")
+}
+
+/// `main.zig` if it's among `candidates`, otherwise the largest file, as a library's root
+/// usually holds more than its helpers. Equally large files go to the first one.
+async fn pick_candidate(candidates: Vec<PathBuf>) -> Option<PathBuf> {
+    if let Some(main) = candidates
+        .iter()
+        .find(|path| path.file_name().and_then(|n| n.to_str()) == Some("main.zig"))
+    {
+        return Some(main.clone());
+    }
+
+    let mut largest: Option<(u64, PathBuf)> = None;
+    for path in candidates {
+        let size = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+        if largest.as_ref().is_none_or(|(max, _)| size > *max) {
+            largest = Some((size, path));
+        }
+    }
+    largest.map(|(_, path)| path)
+}
+
+/// Gets the main file, trying these places in order:
+///
+/// 1. `src/root.zig`
+/// 2. `src/{package_name}.zig`, then `src/{project_dir_name}.zig`
+/// 3. `{package_name}.zig`, then `{project_dir_name}.zig` at the project root
+/// 4. Any other `.zig` file in `src/`, then in the directories directly inside `src/`, then
+///    at the project root (never `build.zig`). Within each of these, `main.zig` wins,
+///    otherwise the largest file, then the lexicographically smallest path.
+pub async fn get_root_file(project_path: &Path, package_name: Option<&str>) -> Option<PathBuf> {
+    let src = project_path.join("src");
+
     // root.zig
-    let root_zig = project_path.join("src").join("root.zig");
-    if root_zig.exists() {
+    let root_zig = src.join("root.zig");
+    if root_zig.is_file() {
         return Some(root_zig);
     }
 
-    // {project_dir_name}.zig
-    if let Some(dir_name) = project_path.file_name().and_then(|os| os.to_str()) {
-        let candidate = project_path.join("src").join(format!("{}.zig", dir_name));
-        if candidate.exists() {
-            return Some(candidate);
+    // {package_name}.zig, {project_dir_name}.zig, in src/ first
+    let dir_name = project_path.file_name().and_then(|os| os.to_str());
+    for dir in [src.as_path(), project_path] {
+        for name in package_name.into_iter().chain(dir_name) {
+            let candidate = dir.join(format!("{}.zig", name));
+            if is_zig_source(&candidate) {
+                return Some(candidate);
+            }
         }
     }
 
-    if let Ok(mut entries) = fs::read_dir(project_path.join("src")).await {
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            if path.extension().and_then(|e| e.to_str()) == Some("zig") {
-                return Some(path);
-            }
+    if let Some(path) = pick_candidate(sorted_entries(&src, is_zig_source).await).await {
+        return Some(path);
+    }
+
+    let mut nested = Vec::new();
+    for dir in sorted_entries(&src, Path::is_dir).await {
+        nested.extend(sorted_entries(&dir, is_zig_source).await);
+    }
+    if let Some(path) = pick_candidate(nested).await {
+        return Some(path);
+    }
+
+    // If we reach here, no `.zig` file was found anywhere else.
+    pick_candidate(sorted_entries(project_path, is_zig_source).await).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_project_from_fixture() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/zig-project");
+
+        let project = find_project(&fixture).await.unwrap();
+        assert_eq!(project.name, "zig_fixture");
+        assert_eq!(project.version.as_deref(), Some("0.3.1"));
+        // build.zig wins over the root.zig heuristic
+        assert_eq!(project.root_file, fixture.join("src/fixture.zig"));
+    }
+
+    #[tokio::test]
+    async fn test_find_project_without_build_files() {
+        let dir = TempDir::new().unwrap();
+        let project_path = dir.path().join("bare");
+        std::fs::create_dir_all(project_path.join("src")).unwrap();
+        std::fs::write(project_path.join("src/bare.zig"), "").unwrap();
+
+        let project = find_project(&project_path).await.unwrap();
+        assert_eq!(project.name, "bare");
+        assert_eq!(project.version, None);
+        assert_eq!(project.root_file, project_path.join("src/bare.zig"));
+    }
+
+    #[tokio::test]
+    async fn test_find_project_uses_package_name() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("build.zig.zon"), ".{ .name = .pkg }").unwrap();
+        // build.zig pointing at a missing file falls back to the heuristics
+        std::fs::write(
+            dir.path().join("build.zig"),
+            ".root_source_file = b.path(\"src/missing.zig\"),",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/pkg.zig"), "").unwrap();
+
+        let project = find_project(dir.path()).await.unwrap();
+        assert_eq!(project.name, "pkg");
+        assert_eq!(project.root_file, dir.path().join("src/pkg.zig"));
+    }
+
+    #[tokio::test]
+    async fn test_get_modules_from_fixture() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/zig-monorepo");
+
+        let modules = get_modules(&fixture).await;
+        let names = modules.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["core", "net", "fmt"]);
+        assert_eq!(modules[1].root_file, fixture.join("net/net.zig"));
+
+        let index = module_index("monorepo", &modules);
+        assert!(index.contains("<title>monorepo</title>"));
+        assert!(index.contains(r#"<a href="net/index.html">net</a>"#));
+    }
+
+    #[tokio::test]
+    async fn test_get_modules_single_module_project() {
+        // the fixture only uses createModule, so it keeps the simple layout
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/zig-project");
+        assert!(get_modules(&fixture).await.is_empty());
+    }
+
+    #[test]
+    fn test_is_dir_name() {
+        assert!(is_dir_name("core"));
+        assert!(is_dir_name("my-module"));
+        assert!(!is_dir_name(""));
+        assert!(!is_dir_name(".."));
+        assert!(!is_dir_name("a/b"));
+        assert!(!is_dir_name("/abs"));
+    }
+
+    /// Creates `files` (empty) inside a fresh project directory named `lib`
+    fn project_with(files: &[&str]) -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let project_path = dir.path().join("lib");
+        for file in files {
+            let path = project_path.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        (dir, project_path)
+    }
+
+    #[tokio::test]
+    async fn test_get_root_file_precedence() {
+        let cases: &[(&[&str], Option<&str>, &str)] = &[
+            // 1. src/root.zig beats everything
+            (
+                &["src/root.zig", "src/pkg.zig", "src/main.zig"],
+                Some("pkg"),
+                "src/root.zig",
+            ),
+            // 2. named after the package, then the directory
+            (
+                &["src/pkg.zig", "src/lib.zig", "src/main.zig"],
+                Some("pkg"),
+                "src/pkg.zig",
+            ),
+            (&["src/lib.zig", "src/main.zig"], Some("pkg"), "src/lib.zig"),
+            (&["src/lib.zig", "src/main.zig"], None, "src/lib.zig"),
+            // 3. named after the package or directory, at the project root
+            (&["lib.zig", "src/a.zig", "build.zig"], None, "lib.zig"),
+            // 4. main.zig, then the largest file, then the smallest path, in src/
+            (
+                &["src/zeta.zig", "src/main.zig", "src/alpha.zig"],
+                None,
+                "src/main.zig",
+            ),
+            (
+                &["src/zeta.zig", "src/beta.zig", "src/alpha.zig"],
+                None,
+                "src/alpha.zig",
+            ),
+            // ... then one level below src/
+            (
+                &["src/b/z.zig", "src/a/y.zig", "src/a/deeper/x.zig"],
+                None,
+                "src/a/y.zig",
+            ),
+            (&["src/b/main.zig", "src/a/y.zig"], None, "src/b/main.zig"),
+            // ... then at the project root, skipping build.zig
+            (
+                &["build.zig", "other.zig", "another.zig"],
+                None,
+                "another.zig",
+            ),
+        ];
+
+        for (files, package_name, expected) in cases {
+            let (_dir, project_path) = project_with(files);
+            assert_eq!(
+                get_root_file(&project_path, *package_name).await,
+                Some(project_path.join(expected)),
+                "{:?}",
+                files
+            );
         }
     }
 
-    // If we reach here, no `.zig` file was found.
-    None
+    #[tokio::test]
+    async fn test_get_root_file_prefers_largest() {
+        let (_dir, project_path) =
+            project_with(&["src/alpha.zig", "src/beta.zig", "src/gamma.zig"]);
+        std::fs::write(project_path.join("src/beta.zig"), "pub const a = 1;\n").unwrap();
+        std::fs::write(
+            project_path.join("src/gamma.zig"),
+            "pub const b = 2;\npub const c = 3;\n",
+        )
+        .unwrap();
+        assert_eq!(
+            get_root_file(&project_path, None).await,
+            Some(project_path.join("src/gamma.zig"))
+        );
+
+        // main.zig still wins over larger files
+        std::fs::write(project_path.join("src/main.zig"), "").unwrap();
+        assert_eq!(
+            get_root_file(&project_path, None).await,
+            Some(project_path.join("src/main.zig"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_root_file_without_sources() {
+        let (_dir, project_path) = project_with(&["build.zig", "src/a/deeper/x.zig", "README.md"]);
+        assert_eq!(get_root_file(&project_path, None).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_find_project_without_sources() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("build.zig.zon"), ".{ .name = .pkg }").unwrap();
+
+        assert_eq!(find_project(dir.path()).await, None);
+    }
 }
@@ -1 +1,3 @@
 pub mod library;
+pub mod toolchain;
+pub mod zon;
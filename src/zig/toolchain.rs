@@ -0,0 +1,186 @@
+use std::{cmp::Ordering, fmt};
+
+use tokio::{process::Command, sync::OnceCell};
+use tracing::info;
+
+use crate::error::{AppError, AppResult};
+
+/// A Zig release or development build, as printed by `zig version`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZigVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// Pre-release suffix, e.g. `dev.1234+abcdef` of a nightly build
+    pub pre: Option<String>,
+}
+
+impl ZigVersion {
+    /// Parses `0.14.0` or `0.15.0-dev.1234+abcdef`
+    pub fn parse(version: &str) -> Option<Self> {
+        let (release, pre) = match version.trim().split_once('-') {
+            Some((release, pre)) => (release, Some(pre.to_string())),
+            None => (version.trim(), None),
+        };
+
+        let mut parts = release.splitn(3, '.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+
+        Some(Self {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+
+    /// Flags added to `zig build-lib -femit-docs`. Since 0.12 autodoc is a standalone web app
+    /// built from the sources, so the library itself doesn't need to be compiled.
+    pub fn docs_flags(&self) -> &'static [&'static str] {
+        if (self.major, self.minor) >= (0, 12) {
+            &["-fno-emit-bin"]
+        } else {
+            &[]
+        }
+    }
+}
+
+impl Ord for ZigVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // a development build comes before the release it leads up to
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => cmp_pre(a, b),
+            })
+    }
+}
+
+impl PartialOrd for ZigVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Identifiers of a pre-release suffix, with their value if they're numbers
+fn pre_identifiers(pre: &str) -> Vec<(Option<u64>, &str)> {
+    let pre = pre.split_once('+').map_or(pre, |(pre, _)| pre);
+    pre.split('.').map(|id| (id.parse().ok(), id)).collect()
+}
+
+/// Compares pre-release suffixes the way semver does: dot-separated identifier by identifier,
+/// numerically when both are numbers, so `dev.999` comes before `dev.1000`. Numbers come
+/// before words, and a suffix before the longer ones it starts. Build metadata after `+` only
+/// breaks ties.
+fn cmp_pre(a: &str, b: &str) -> Ordering {
+    let (a_ids, b_ids) = (pre_identifiers(a), pre_identifiers(b));
+    for (a_id, b_id) in a_ids.iter().zip(&b_ids) {
+        let ordering = match (a_id, b_id) {
+            ((Some(a), _), (Some(b), _)) => a.cmp(b),
+            ((Some(_), _), (None, _)) => Ordering::Less,
+            ((None, _), (Some(_), _)) => Ordering::Greater,
+            ((None, a), (None, b)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_ids.len().cmp(&b_ids.len()).then_with(|| a.cmp(b))
+}
+
+impl fmt::Display for ZigVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+static INSTALLED: OnceCell<ZigVersion> = OnceCell::const_new();
+
+/// Version of the `zig` on `PATH`, detected on first use and cached for the process lifetime
+pub async fn installed_version() -> AppResult<&'static ZigVersion> {
+    INSTALLED
+        .get_or_try_init(|| async {
+            let output = Command::new("zig").arg("version").output().await?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let version = ZigVersion::parse(&stdout).ok_or_else(|| {
+                AppError::Build(format!(
+                    "unrecognized `zig version` output '{}'",
+                    stdout.trim()
+                ))
+            })?;
+            info!("Detected zig {}", version);
+            Ok::<_, AppError>(version)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> ZigVersion {
+        ZigVersion::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            version("0.14.0\n"),
+            ZigVersion {
+                major: 0,
+                minor: 14,
+                patch: 0,
+                pre: None
+            }
+        );
+        assert_eq!(
+            version("0.15.0-dev.1234+abcdef").pre.as_deref(),
+            Some("dev.1234+abcdef")
+        );
+        assert_eq!(version("0.13").patch, 0);
+        assert_eq!(version("0.15.0-dev.1+a").to_string(), "0.15.0-dev.1+a");
+
+        for invalid in ["", "zig", "0", "a.b.c", "0.14.x"] {
+            assert_eq!(ZigVersion::parse(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(version("0.11.0") < version("0.12.0"));
+        assert!(version("0.12.1") > version("0.12.0"));
+        assert!(version("1.0.0") > version("0.99.99"));
+        assert!(version("0.15.0-dev.1+a") < version("0.15.0"));
+        assert!(version("0.15.0-dev.1+a") > version("0.14.1"));
+        assert!(version("0.14.0") >= version("0.14"));
+    }
+
+    #[test]
+    fn test_ordering_of_nightlies() {
+        assert!(version("0.15.0-dev.999+aaaa") < version("0.15.0-dev.1000+0000"));
+        assert!(version("0.15.0-dev.2+b") > version("0.15.0-dev.2+a"));
+        assert!(version("0.15.0-dev") < version("0.15.0-dev.1"));
+        assert!(version("0.15.0-dev.9") < version("0.15.0-rc.1"));
+        assert!(version("0.15.0-1") < version("0.15.0-dev"));
+        assert_eq!(
+            version("0.15.0-dev.7+a").cmp(&version("0.15.0-dev.7+a")),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_docs_flags() {
+        assert!(version("0.11.0").docs_flags().is_empty());
+        assert_eq!(version("0.12.0").docs_flags(), ["-fno-emit-bin"]);
+        assert_eq!(version("0.15.0-dev.1+a").docs_flags(), ["-fno-emit-bin"]);
+    }
+}
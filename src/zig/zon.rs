@@ -0,0 +1,281 @@
+//! Tolerant parsing of the few things lichen needs from `build.zig.zon` and `build.zig`.
+//! Anything that isn't understood is skipped rather than reported.
+
+use std::{iter::Peekable, str::Chars};
+
+/// Top-level fields of a `build.zig.zon` manifest
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    /// `.name`, either a string (before Zig 0.14) or an enum literal
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Dot,
+    Eq,
+    Comma,
+    Ident(String),
+    Str(String),
+    Other,
+}
+
+/// Reads a quoted literal up to `quote`, after the opening quote has been consumed
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            c if c == quote => break,
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '.' => Token::Dot,
+            '=' => Token::Eq,
+            ',' => Token::Comma,
+            '"' => Token::Str(read_quoted(&mut chars, '"')),
+            '\'' => {
+                read_quoted(&mut chars, '\'');
+                Token::Other
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+                continue;
+            }
+            // @"quoted identifier"
+            '@' if chars.peek() == Some(&'"') => {
+                chars.next();
+                Token::Ident(read_quoted(&mut chars, '"'))
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                Token::Ident(ident)
+            }
+            c if c.is_whitespace() => continue,
+            _ => Token::Other,
+        };
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Extracts `.name` and `.version` from the outermost struct of a `build.zig.zon`, ignoring
+/// the same fields of nested structs such as `.dependencies`
+pub fn parse_manifest(source: &str) -> Manifest {
+    let tokens = tokenize(source);
+    let mut manifest = Manifest::default();
+    let mut depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Dot if depth == 1 => {
+                let [Token::Ident(key), Token::Eq, rest @ ..] = &tokens[i + 1..] else {
+                    continue;
+                };
+                let value = match rest {
+                    [Token::Str(value), ..] | [Token::Dot, Token::Ident(value), ..] => {
+                        Some(value.clone())
+                    }
+                    _ => None,
+                };
+                match key.as_str() {
+                    "name" => manifest.name = manifest.name.take().or(value),
+                    "version" => manifest.version = manifest.version.take().or(value),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    manifest
+}
+
+/// Paths given as `.root_source_file` in a `build.zig`, in order of appearance. Both
+/// `b.path("src/root.zig")` and the older `.{ .path = "src/root.zig" }` are understood.
+pub fn root_source_hints(source: &str) -> Vec<String> {
+    let tokens = tokenize(source);
+    let mut hints = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if matches!(token, Token::Ident(ident) if ident == "root_source_file")
+            && let Some(path) = field_string(&tokens[i + 1..])
+        {
+            hints.push(path.clone());
+        }
+    }
+
+    hints
+}
+
+/// The first string in a field's value, given the tokens following the field name
+fn field_string(tokens: &[Token]) -> Option<&String> {
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            Token::Str(value) => return Some(value),
+            Token::Open => depth += 1,
+            Token::Close | Token::Comma if depth == 0 => return None,
+            Token::Close => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Modules a `build.zig` exposes with `b.addModule("name", .{ .root_source_file = ... })`,
+/// as `(name, root source file)` pairs in order of appearance
+pub fn public_modules(source: &str) -> Vec<(String, String)> {
+    let tokens = tokenize(source);
+    let mut modules = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Ident(call) = token else {
+            continue;
+        };
+        let [Token::Other, Token::Str(name), Token::Comma, rest @ ..] = &tokens[i + 1..] else {
+            continue;
+        };
+        if call != "addModule" {
+            continue;
+        }
+
+        // look for root_source_file directly inside the options struct
+        let mut depth = 0usize;
+        for (j, token) in rest.iter().enumerate() {
+            match token {
+                Token::Open => depth += 1,
+                Token::Close if depth <= 1 => break,
+                Token::Close => depth -= 1,
+                Token::Ident(field) if depth == 1 && field == "root_source_file" => {
+                    if let Some(path) = field_string(&rest[j + 1..]) {
+                        modules.push((name.clone(), path.clone()));
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest_enum_literal_name() {
+        let manifest = parse_manifest(
+            r#".{
+                .name = .my_lib,
+                .version = "1.2.3",
+                .fingerprint = 0x1234abcd,
+                .paths = .{ "src" },
+            }"#,
+        );
+        assert_eq!(manifest.name.as_deref(), Some("my_lib"));
+        assert_eq!(manifest.version.as_deref(), Some("1.2.3"));
+    }
+
+    #[test]
+    fn test_parse_manifest_string_and_quoted_names() {
+        let manifest = parse_manifest(r#".{ .name = "old-style", .version = "0.0.1" }"#);
+        assert_eq!(manifest.name.as_deref(), Some("old-style"));
+
+        let manifest = parse_manifest(r#".{ .name = .@"dashed-name" }"#);
+        assert_eq!(manifest.name.as_deref(), Some("dashed-name"));
+        assert_eq!(manifest.version, None);
+    }
+
+    #[test]
+    fn test_parse_manifest_ignores_nested_fields_and_comments() {
+        let manifest = parse_manifest(
+            r#".{
+                // .name = .commented_out,
+                .dependencies = .{
+                    .dep = .{ .name = "dependency", .version = "9.9.9" },
+                },
+                .name = .outer, // trailing comment with "quotes"
+            }"#,
+        );
+        assert_eq!(manifest.name.as_deref(), Some("outer"));
+        assert_eq!(manifest.version, None);
+    }
+
+    #[test]
+    fn test_parse_manifest_without_fields() {
+        assert_eq!(parse_manifest(""), Manifest::default());
+        assert_eq!(parse_manifest(".{ .paths = .{\"\"} }"), Manifest::default());
+        assert_eq!(parse_manifest("not zon at all {{"), Manifest::default());
+    }
+
+    #[test]
+    fn test_public_modules() {
+        let modules = public_modules(
+            r#"
+            const core = b.addModule("core", .{
+                .target = target,
+                .root_source_file = b.path("core/root.zig"),
+            });
+            const net = b.addModule("net", .{ .root_source_file = .{ .path = "net/net.zig" } });
+            // private modules aren't documented
+            const internal = b.createModule(.{ .root_source_file = b.path("internal.zig") });
+            const nested = b.addModule("nested", .{
+                .imports = &.{ .{ .name = "x", .root_source_file = b.path("wrong.zig") } },
+            });
+            "#,
+        );
+        assert_eq!(
+            modules,
+            [
+                ("core".to_string(), "core/root.zig".to_string()),
+                ("net".to_string(), "net/net.zig".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_root_source_hints() {
+        let hints = root_source_hints(
+            r#"
+            const sep = '"';
+            const lib = b.addStaticLibrary(.{
+                .name = "lib",
+                .root_source_file = b.path("src/lib.zig"),
+            });
+            const old = b.addExecutable(.{
+                .root_source_file = .{ .path = "src/main.zig" },
+            });
+            // .root_source_file = b.path("src/commented.zig"),
+            const generated = b.addModule("gen", .{ .root_source_file = generated_file, .target = "x" });
+            "#,
+        );
+        assert_eq!(hints, ["src/lib.zig", "src/main.zig"]);
+    }
+}
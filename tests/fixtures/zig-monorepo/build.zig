@@ -0,0 +1,26 @@
+const std = @import("std");
+
+pub fn build(b: *std.Build) void {
+    const target = b.standardTargetOptions(.{});
+    const optimize = b.standardOptimizeOption(.{});
+
+    const core = b.addModule("core", .{
+        .root_source_file = b.path("core/root.zig"),
+        .target = target,
+        .optimize = optimize,
+    });
+
+    const net = b.addModule("net", .{
+        .root_source_file = b.path("net/net.zig"),
+        .target = target,
+        .optimize = optimize,
+    });
+    net.addImport("core", core);
+
+    const fmt = b.addModule("fmt", .{
+        .root_source_file = b.path("fmt/fmt.zig"),
+        .target = target,
+        .optimize = optimize,
+    });
+    fmt.addImport("core", core);
+}
@@ -0,0 +1,3 @@
+//! Core types shared by the other modules
+
+pub const Id = u64;
@@ -0,0 +1,7 @@
+//! Formatting helpers
+
+const core = @import("core");
+
+pub fn format(id: core.Id) u64 {
+    return id;
+}
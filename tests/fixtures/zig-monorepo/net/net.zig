@@ -0,0 +1,7 @@
+//! Networking on top of core
+
+const core = @import("core");
+
+pub fn connect(id: core.Id) void {
+    _ = id;
+}
@@ -0,0 +1,5 @@
+//! Fixture library documented by lichen
+
+pub fn answer() u32 {
+    return 42;
+}
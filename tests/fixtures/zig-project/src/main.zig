@@ -0,0 +1,6 @@
+const std = @import("std");
+const lib = @import("zig_fixture");
+
+pub fn main() void {
+    std.debug.print("{d}\n", .{lib.answer()});
+}
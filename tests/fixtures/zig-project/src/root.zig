@@ -0,0 +1 @@
+//! Decoy matching the root.zig heuristic, build.zig points elsewhere
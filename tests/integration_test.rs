@@ -0,0 +1,133 @@
+use std::{
+    net::TcpListener,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use tempfile::TempDir;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("failed to find a free port")
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn serves_tls_and_stops_on_sigterm() {
+    let port = free_port();
+    // also the libs path, which the access times are saved to on the way out
+    let dir = TempDir::new().expect("failed to create temp dir");
+    let docs = dir.path().join("custom-project/docs");
+    std::fs::create_dir_all(&docs).expect("failed to create docs dir");
+    std::fs::write(docs.join("index.html"), "smoke test docs").expect("failed to write docs");
+
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+        .expect("failed to generate certificate");
+    let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    std::fs::write(&cert, certified.cert.pem()).expect("failed to write certificate");
+    std::fs::write(&key, certified.key_pair.serialize_pem()).expect("failed to write key");
+    let config = format!(
+        r#"
+libs_path = "{}"
+port = {}
+
+[server]
+shutdown_grace_period_secs = 1
+
+[tls]
+cert = "{}"
+key = "{}"
+
+[[projects]]
+path = "custom-project"
+build_system = "custom"
+build_command = "true"
+"#,
+        dir.path().display(),
+        port,
+        cert.display(),
+        key.display()
+    );
+    std::fs::write(dir.path().join("config.toml"), config).expect("failed to write config");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lichen"))
+        .arg("-q")
+        .current_dir(dir.path())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("failed to start lichen");
+    let root = reqwest::Certificate::from_pem(certified.cert.pem().as_bytes())
+        .expect("invalid certificate");
+    let client = reqwest::Client::builder()
+        .add_root_certificate(root)
+        .build()
+        .expect("failed to build client");
+    let base_url = format!("https://localhost:{}", port);
+
+    let mut ready = false;
+    for _ in 0..100 {
+        if client.get(&base_url).send().await.is_ok() {
+            ready = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    if !ready {
+        let _ = child.kill();
+        panic!("lichen did not start listening on {}", base_url);
+    }
+
+    // actix offers h2 first through ALPN
+    let response = client
+        .get(format!("{}/custom-project/", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.version(), reqwest::Version::HTTP_2);
+
+    let metrics = client
+        .get(format!("{}/metrics", base_url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let value = |name: &str| {
+        metrics
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or_else(|| panic!("no {} in {}", name, metrics))
+    };
+    // the scrape itself is being handled on one of them
+    assert!(value("lichen_http_connections_active") >= 1);
+    assert!(value("lichen_http_connections_total") >= 1);
+    value("lichen_http_connections_idle");
+
+    // requests are still answered during the grace period, then the server exits cleanly
+    assert_eq!(unsafe { libc::kill(child.id() as i32, libc::SIGTERM) }, 0);
+    let response = client
+        .get(format!("{}/custom-project/", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let mut status = None;
+    for _ in 0..100 {
+        status = child.try_wait().expect("failed to wait for lichen");
+        if status.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let Some(status) = status else {
+        let _ = child.kill();
+        panic!("lichen did not stop after SIGTERM");
+    };
+    assert!(status.success(), "{}", status);
+}